@@ -10,6 +10,11 @@ pub struct Arguments {
     /// URI of the torrent file you wish to download
     #[arg(short('m'))]
     pub magnet_uri: Option<String>,
+
+    /// Directory the torrent is downloaded into, its resume data and the DHT routing table are
+    /// kept inside of it as well
+    #[arg(short('d'), default_value = ".")]
+    pub directory: String,
 }
 
 impl Arguments {
@@ -0,0 +1,292 @@
+/*
+ * NOTE : This file contains the messages of the KRPC protocol, which the DHT nodes talk to each
+ * other with
+ *
+ * https://www.bittorrent.org/beps/bep_0005.html
+ *
+ * Every message is a bencoded dictionary sent in a single UDP packet, a query is answered with
+ * either a response or an error, which carry the same transaction ID as the query.
+ */
+
+use super::routing_table::{compact_nodes, to_compact_nodes, NodeId, COMPACT_NODE6_LENGTH, COMPACT_NODE_LENGTH};
+use crate::core::net;
+use serde_bencode::value::Value;
+use std::{collections::HashMap, net::SocketAddr};
+
+/// Error codes of an error message
+pub const GENERIC_ERROR: i64 = 201;
+pub const PROTOCOL_ERROR: i64 = 203;
+
+/// The queries a node can be sent, the node ID of the querying node is sent along with every one
+/// of them
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Checks whether the node is still there
+    Ping,
+
+    /// Asks for the nodes closest to the target the node knows about
+    FindNode { target: NodeId },
+
+    /// Asks for the peers of the torrent with the given info hash, or for the nodes closest to it
+    /// if the node knows about none
+    GetPeers { info_hash: NodeId },
+
+    /// Lets the node know that we're a peer of the torrent with the given info hash, listening on
+    /// the given port, or on the port the query is sent from if "implied_port" is set
+    AnnouncePeer {
+        info_hash: NodeId,
+        port: u16,
+        implied_port: bool,
+        token: Vec<u8>,
+    },
+}
+
+impl Query {
+    /// Name of the query, sent as "q"
+    fn method(&self) -> &'static [u8] {
+        match *self {
+            Self::Ping => b"ping",
+            Self::FindNode {
+                ..
+            } => b"find_node",
+            Self::GetPeers {
+                ..
+            } => b"get_peers",
+            Self::AnnouncePeer {
+                ..
+            } => b"announce_peer",
+        }
+    }
+}
+
+/// A response to a query, the keys that aren't sent in a response to a query are left empty
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Response {
+    /// Node ID of the responding node
+    pub id: NodeId,
+
+    /// The nodes closest to the target, sent in response to find_node and get_peers
+    pub nodes: Vec<(NodeId, SocketAddr)>,
+
+    /// The peers of the torrent, sent in response to get_peers
+    pub values: Vec<SocketAddr>,
+
+    /// The token to announce ourselves with, sent in response to get_peers
+    pub token: Option<Vec<u8>>,
+}
+
+/// Messages of the KRPC protocol
+///
+/// Keys of the dictionary :
+/// t       Transaction ID, given back as it is in the response or the error
+/// y       Type of the message, "q" for a query, "r" for a response and "e" for an error
+/// q       Name of the query, e.g "get_peers"
+/// a       Arguments of the query, "id", "target", "info_hash", "port", "implied_port", "token"
+/// r       Values of the response, "id", "nodes", "nodes6", "values", "token"
+/// e       List of the error code and the error message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Krpc {
+    Query {
+        transaction_id: Vec<u8>,
+        id: NodeId,
+        query: Query,
+    },
+    Response {
+        transaction_id: Vec<u8>,
+        response: Response,
+    },
+    Error {
+        transaction_id: Vec<u8>,
+        code: i64,
+        message: String,
+    },
+}
+
+impl Krpc {
+    /// Parses a KRPC message, "None" if it's malformed or it's a query we don't understand
+    pub fn from(v: &[u8]) -> Option<Self> {
+        let Value::Dict(dict) = serde_bencode::from_bytes::<Value>(v).ok()? else {
+            return None;
+        };
+        let bytes = |dict: &HashMap<Vec<u8>, Value>, key: &[u8]| match dict.get(key) {
+            Some(Value::Bytes(bytes)) => Some(bytes.clone()),
+            _ => None,
+        };
+        let node_id = |dict: &HashMap<Vec<u8>, Value>, key: &[u8]| NodeId::try_from(bytes(dict, key)?.as_slice()).ok();
+        let int = |dict: &HashMap<Vec<u8>, Value>, key: &[u8]| match dict.get(key) {
+            Some(Value::Int(int)) => Some(*int),
+            _ => None,
+        };
+
+        let transaction_id = bytes(&dict, b"t")?;
+        match bytes(&dict, b"y")?.as_slice() {
+            b"q" => {
+                let Some(Value::Dict(args)) = dict.get(b"a".as_ref()) else {
+                    return None;
+                };
+                let query = match bytes(&dict, b"q")?.as_slice() {
+                    b"ping" => Query::Ping,
+                    b"find_node" => Query::FindNode {
+                        target: node_id(args, b"target")?,
+                    },
+                    b"get_peers" => Query::GetPeers {
+                        info_hash: node_id(args, b"info_hash")?,
+                    },
+                    b"announce_peer" => Query::AnnouncePeer {
+                        info_hash: node_id(args, b"info_hash")?,
+                        port: int(args, b"port").and_then(|port| u16::try_from(port).ok()).unwrap_or(0),
+                        implied_port: int(args, b"implied_port").is_some_and(|implied_port| implied_port != 0),
+                        token: bytes(args, b"token")?,
+                    },
+                    _ => return None,
+                };
+                Some(Self::Query {
+                    transaction_id,
+                    id: node_id(args, b"id")?,
+                    query,
+                })
+            }
+
+            b"r" => {
+                let Some(Value::Dict(values)) = dict.get(b"r".as_ref()) else {
+                    return None;
+                };
+                let mut nodes = compact_nodes(&bytes(values, b"nodes").unwrap_or_default(), COMPACT_NODE_LENGTH);
+                nodes.extend(compact_nodes(&bytes(values, b"nodes6").unwrap_or_default(), COMPACT_NODE6_LENGTH));
+                let peers = match values.get(b"values".as_ref()) {
+                    Some(Value::List(peers)) => peers
+                        .iter()
+                        .filter_map(|peer| match peer {
+                            Value::Bytes(peer) => net::compact_peer(peer),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                Some(Self::Response {
+                    transaction_id,
+                    response: Response {
+                        id: node_id(values, b"id")?,
+                        nodes,
+                        values: peers,
+                        token: bytes(values, b"token"),
+                    },
+                })
+            }
+
+            b"e" => {
+                let (code, message) = match dict.get(b"e".as_ref()) {
+                    Some(Value::List(error)) => match error.as_slice() {
+                        [Value::Int(code), Value::Bytes(message), ..] => (*code, String::from_utf8_lossy(message).to_string()),
+                        _ => (GENERIC_ERROR, String::new()),
+                    },
+                    _ => (GENERIC_ERROR, String::new()),
+                };
+                Some(Self::Error {
+                    transaction_id,
+                    code,
+                    message,
+                })
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Bencodes the message into a dictionary
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut dict = HashMap::new();
+        match *self {
+            Self::Query {
+                ref transaction_id,
+                ref id,
+                ref query,
+            } => {
+                let mut args = HashMap::from([(b"id".to_vec(), Value::Bytes(id.to_vec()))]);
+                match *query {
+                    Query::Ping => {}
+                    Query::FindNode {
+                        ref target,
+                    } => {
+                        args.insert(b"target".to_vec(), Value::Bytes(target.to_vec()));
+                    }
+                    Query::GetPeers {
+                        ref info_hash,
+                    } => {
+                        args.insert(b"info_hash".to_vec(), Value::Bytes(info_hash.to_vec()));
+                    }
+                    Query::AnnouncePeer {
+                        ref info_hash,
+                        port,
+                        implied_port,
+                        ref token,
+                    } => {
+                        args.insert(b"info_hash".to_vec(), Value::Bytes(info_hash.to_vec()));
+                        args.insert(b"port".to_vec(), Value::Int(port as i64));
+                        args.insert(b"implied_port".to_vec(), Value::Int(implied_port as i64));
+                        args.insert(b"token".to_vec(), Value::Bytes(token.clone()));
+                    }
+                }
+                dict.insert(b"t".to_vec(), Value::Bytes(transaction_id.clone()));
+                dict.insert(b"y".to_vec(), Value::Bytes(b"q".to_vec()));
+                dict.insert(b"q".to_vec(), Value::Bytes(query.method().to_vec()));
+                dict.insert(b"a".to_vec(), Value::Dict(args));
+            }
+
+            Self::Response {
+                ref transaction_id,
+                ref response,
+            } => {
+                let mut values = HashMap::from([(b"id".to_vec(), Value::Bytes(response.id.to_vec()))]);
+                let (nodes, nodes6) = to_compact_nodes(&response.nodes);
+                if !nodes.is_empty() {
+                    values.insert(b"nodes".to_vec(), Value::Bytes(nodes));
+                }
+                if !nodes6.is_empty() {
+                    values.insert(b"nodes6".to_vec(), Value::Bytes(nodes6));
+                }
+                if !response.values.is_empty() {
+                    let peers = response
+                        .values
+                        .iter()
+                        .map(|socket_adr| Value::Bytes(net::to_compact_peer(*socket_adr)))
+                        .collect();
+                    values.insert(b"values".to_vec(), Value::List(peers));
+                }
+                if let Some(ref token) = response.token {
+                    values.insert(b"token".to_vec(), Value::Bytes(token.clone()));
+                }
+                dict.insert(b"t".to_vec(), Value::Bytes(transaction_id.clone()));
+                dict.insert(b"y".to_vec(), Value::Bytes(b"r".to_vec()));
+                dict.insert(b"r".to_vec(), Value::Dict(values));
+            }
+
+            Self::Error {
+                ref transaction_id,
+                code,
+                ref message,
+            } => {
+                let error = vec![Value::Int(code), Value::Bytes(message.clone().into_bytes())];
+                dict.insert(b"t".to_vec(), Value::Bytes(transaction_id.clone()));
+                dict.insert(b"y".to_vec(), Value::Bytes(b"e".to_vec()));
+                dict.insert(b"e".to_vec(), Value::List(error));
+            }
+        }
+        serde_bencode::to_bytes(&Value::Dict(dict)).unwrap_or_default()
+    }
+
+    /// Transaction ID of the message
+    pub fn transaction_id(&self) -> &[u8] {
+        match *self {
+            Self::Query {
+                ref transaction_id, ..
+            }
+            | Self::Response {
+                ref transaction_id, ..
+            }
+            | Self::Error {
+                ref transaction_id, ..
+            } => transaction_id,
+        }
+    }
+}
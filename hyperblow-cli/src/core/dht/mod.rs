@@ -0,0 +1,757 @@
+/*
+ * NOTE : This module contains the DHT node of the engine, through which the peers of a torrent are
+ * found without any tracker, i.e the Mainline DHT
+ *
+ * https://www.bittorrent.org/beps/bep_0005.html
+ *
+ * A single node is run for all the torrents of the engine, on its own UDP socket. The peers of a
+ * torrent are looked up by asking the nodes closest to its info hash, and closer and closer nodes
+ * are asked until the closest ones have all been asked, those nodes are then told that we're a peer
+ * of the torrent as well. The routing table is saved along with the resume data, so that the node
+ * doesn't have to bootstrap all over again the next time.
+ */
+
+pub mod krpc;
+pub mod routing_table;
+pub mod token;
+
+use self::{
+    krpc::{Krpc, Query, Response, PROTOCOL_ERROR},
+    routing_table::{distance, random_id, NodeId, RoutingTable, K},
+    token::{PeerStore, Tokens},
+};
+use crate::core::net;
+use crossbeam::atomic::AtomicCell;
+use futures::future::join_all;
+use rand::{thread_rng, Rng};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    net::SocketAddr,
+    ops::RangeInclusive,
+    path::PathBuf,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+use tokio::{
+    join,
+    net::{lookup_host, UdpSocket},
+    sync::{oneshot, Mutex},
+    time::{interval, interval_at, timeout, Instant},
+};
+
+/// Ports tried one after another, until the socket gets bound to one of them
+const DHT_PORTS: RangeInclusive<u16> = 6881..=6999;
+
+/// The nodes a new node bootstraps from, they're well known and always up
+pub const DEFAULT_BOOTSTRAP_NODES: [&str; 4] = [
+    "router.bittorrent.com:6881",
+    "router.utorrent.com:6881",
+    "dht.transmissionbt.com:6881",
+    "dht.libtorrent.org:25401",
+];
+
+/// Time given to a node to respond to a query
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// No of nodes queried at a time during a lookup
+const ALPHA: usize = 3;
+
+/// Time between the pings of the questionable nodes and the refreshes of the buckets
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time between the saves of the routing table
+const SAVE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The DHT node of the engine, see the NOTE at the top
+#[derive(Debug)]
+pub struct Dht {
+    /// The UDP socket of the node, "None" until it has been bound
+    socket: Mutex<Option<Arc<UdpSocket>>>,
+
+    /// Port the socket is bound to, it's 0 until the socket has been bound
+    port: AtomicCell<u16>,
+
+    /// The nodes we know about, along with our own node ID
+    table: Mutex<RoutingTable>,
+
+    /// The queries waiting for their response, by their transaction ID
+    ///
+    /// NOTE : It's a std Mutex, as a query that's given up on, e.g on a timeout, is removed from
+    /// here on drop
+    pending: StdMutex<HashMap<u16, PendingQuery>>,
+
+    /// The secrets the tokens given out are made with
+    tokens: Mutex<Tokens>,
+
+    /// The peers the other nodes have announced to us
+    peers: Mutex<PeerStore>,
+
+    /// The nodes bootstrapped from, as "host:port"
+    ///
+    /// NOTE : It's a std Mutex, as it's set by the UI outside of the async context
+    bootstrap_nodes: StdMutex<Vec<String>>,
+
+    /// Where the routing table is saved, it isn't saved if it's "None"
+    state_path: Option<PathBuf>,
+}
+
+/// A query sent to a node, for which we're waiting to get a response
+#[derive(Debug)]
+struct PendingQuery {
+    /// Socket address the query was sent to, a response from anywhere else is ignored
+    socket_adr: SocketAddr,
+
+    /// Where the response is handed over to
+    response: oneshot::Sender<Krpc>,
+}
+
+/// The transaction ID reserved for a query along with the response that'll come for it, the
+/// transaction ID is released once it's dropped
+#[derive(Debug)]
+struct PendingResponse<'a> {
+    dht: &'a Dht,
+
+    /// The transaction ID the query must be made with
+    transaction_id: u16,
+
+    response: oneshot::Receiver<Krpc>,
+}
+
+impl PendingResponse<'_> {
+    /// Waits for the response or the error, it will return "None" if the node stopped receiving
+    async fn recv(&mut self) -> Option<Krpc> {
+        (&mut self.response).await.ok()
+    }
+}
+
+impl Drop for PendingResponse<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.dht.pending.lock() {
+            pending.remove(&self.transaction_id);
+        }
+    }
+}
+
+/// The outcome of a lookup
+#[derive(Debug, Default)]
+struct Lookup {
+    /// The peers of the torrent, only for a lookup of an info hash
+    peers: Vec<SocketAddr>,
+
+    /// Upto K nodes that responded, closest to the target first, along with the token each one
+    /// gave us
+    nodes: Vec<(NodeId, SocketAddr, Option<Vec<u8>>)>,
+}
+
+impl Dht {
+    /// Creates a node with a new node ID, which is replaced by the one saved at the given path
+    /// once the node is run, see [Dht::run]
+    pub fn new(state_path: Option<PathBuf>) -> Self {
+        Self {
+            socket: Mutex::default(),
+            port: AtomicCell::new(0),
+            table: Mutex::new(RoutingTable::new(random_id())),
+            pending: StdMutex::default(),
+            tokens: Mutex::default(),
+            peers: Mutex::default(),
+            bootstrap_nodes: StdMutex::new(DEFAULT_BOOTSTRAP_NODES.iter().map(|node| node.to_string()).collect()),
+            state_path,
+        }
+    }
+
+    /// Binds the socket to the first free port out of [DHT_PORTS]
+    pub async fn bind(&self) -> io::Result<()> {
+        let mut last_error = io::Error::new(io::ErrorKind::AddrInUse, "no free port for the DHT");
+        for port in DHT_PORTS {
+            match self.bind_on(port).await {
+                Ok(()) => return Ok(()),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Binds the socket to the given port, a port 0 lets the OS pick one
+    async fn bind_on(&self, port: u16) -> io::Result<()> {
+        let socket = net::bind_udp(port)?;
+        self.port.store(socket.local_addr()?.port());
+        *self.socket.lock().await = Some(Arc::new(socket));
+        Ok(())
+    }
+
+    /// Port the socket is bound to, it's 0 until the socket has been bound
+    pub fn port(&self) -> u16 {
+        self.port.load()
+    }
+
+    /// Our own node ID
+    pub async fn id(&self) -> NodeId {
+        self.table.lock().await.id()
+    }
+
+    /// No of nodes in the routing table
+    pub async fn nodes_count(&self) -> usize {
+        self.table.lock().await.len()
+    }
+
+    /// The nodes bootstrapped from, as "host:port"
+    pub fn bootstrap_nodes(&self) -> Vec<String> {
+        match self.bootstrap_nodes.lock() {
+            Ok(nodes) => nodes.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Sets the nodes bootstrapped from, as "host:port", they're used the next time the routing
+    /// table runs short of nodes
+    pub fn set_bootstrap_nodes(&self, nodes: Vec<String>) {
+        match self.bootstrap_nodes.lock() {
+            Ok(mut current) => *current = nodes,
+            Err(poisoned) => *poisoned.into_inner() = nodes,
+        }
+    }
+
+    /// Runs the node forever, the routing table saved the last time is taken first, then the
+    /// queries of the other nodes are answered and the routing table is kept up to date
+    pub async fn run(&self) {
+        let socket = match *self.socket.lock().await {
+            Some(ref socket) => socket.clone(),
+            None => return,
+        };
+        if let Some(ref path) = self.state_path {
+            if let Some(table) = RoutingTable::load(path).await {
+                *self.table.lock().await = table;
+            }
+        }
+
+        join!(self.receive(&socket), self.maintain());
+    }
+
+    /// Looks up the peers of the torrent with the given info hash
+    pub async fn get_peers(&self, info_hash: NodeId) -> Vec<SocketAddr> {
+        self.lookup(info_hash, true, Vec::new()).await.peers
+    }
+
+    /// Looks up the peers of the torrent with the given info hash, and lets the nodes closest to
+    /// it know that we're a peer of the torrent listening on the given TCP port
+    pub async fn announce(&self, info_hash: NodeId, port: u16) -> Vec<SocketAddr> {
+        let lookup = self.lookup(info_hash, true, Vec::new()).await;
+        let announces = lookup.nodes.iter().filter_map(|(_, socket_adr, token)| {
+            let query = Query::AnnouncePeer {
+                info_hash,
+                port,
+                implied_port: false,
+                token: token.clone()?,
+            };
+            Some(self.query(*socket_adr, query))
+        });
+        join_all(announces).await;
+        lookup.peers
+    }
+
+    /// Pings the node at the given socket address, e.g the DHT node of a peer told to us in a Port
+    /// message, it's added into the routing table if it responds, gives back whether it did
+    pub async fn ping(&self, socket_adr: SocketAddr) -> bool {
+        self.query(socket_adr, Query::Ping).await.is_some()
+    }
+
+    /// Bootstraps from the nodes of [Dht::bootstrap_nodes]
+    pub async fn bootstrap(&self) {
+        let mut socket_adrs = Vec::new();
+        for node in self.bootstrap_nodes() {
+            if let Ok(resolved) = lookup_host(node.as_str()).await {
+                socket_adrs.extend(resolved);
+            }
+        }
+        self.bootstrap_from(socket_adrs).await;
+    }
+
+    /// Bootstraps from the given nodes, e.g the ones in the "nodes" field of a torrent, each one
+    /// being a host and a port
+    pub async fn add_nodes(&self, nodes: &[(String, u16)]) {
+        let mut socket_adrs = Vec::new();
+        for (host, port) in nodes {
+            if let Ok(resolved) = lookup_host((host.as_str(), *port)).await {
+                socket_adrs.extend(resolved);
+            }
+        }
+        self.bootstrap_from(socket_adrs).await;
+    }
+
+    /// Asks the nodes at the given socket addresses for the nodes closest to us, and then looks
+    /// up our own node ID starting from those, which fills up the routing table
+    async fn bootstrap_from(&self, socket_adrs: Vec<SocketAddr>) {
+        if socket_adrs.is_empty() {
+            return;
+        }
+        let id = self.id().await;
+        let responses = join_all(socket_adrs.iter().map(|socket_adr| {
+            self.query(
+                *socket_adr,
+                Query::FindNode {
+                    target: id,
+                },
+            )
+        }))
+        .await;
+        let seeds = responses.into_iter().flatten().flat_map(|response| response.nodes).collect();
+        self.lookup(id, false, seeds).await;
+    }
+
+    /// Saves the routing table, see [RoutingTable::save]
+    pub async fn save(&self) -> io::Result<()> {
+        match self.state_path {
+            Some(ref path) => self.table.lock().await.save(path).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Receives the queries of the other nodes and the responses to our queries forever, each
+    /// query is answered right away and each response is handed over to the query with the same
+    /// transaction ID, as long as it came from the node the query was sent to
+    async fn receive(&self, socket: &UdpSocket) {
+        // A buffer of 2KiB capacity, a KRPC message fits in a single UDP packet
+        let mut buf = [0; 2048];
+        loop {
+            let (len, socket_adr) = match socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(_) => continue,
+            };
+            let socket_adr = net::canonical(socket_adr);
+            let Some(message) = Krpc::from(&buf[..len]) else {
+                continue;
+            };
+
+            let Krpc::Query {
+                transaction_id,
+                id,
+                query,
+            } = message
+            else {
+                self.handle_response(socket_adr, message);
+                continue;
+            };
+            let reply = match self.handle_query(socket_adr, id, query).await {
+                Ok(response) => Krpc::Response {
+                    transaction_id,
+                    response,
+                },
+                Err(message) => Krpc::Error {
+                    transaction_id,
+                    code: PROTOCOL_ERROR,
+                    message: message.to_string(),
+                },
+            };
+            let _ = self.send_to(&reply.to_bytes(), socket_adr).await;
+        }
+    }
+
+    /// Hands the response or the error over to the query waiting for it
+    fn handle_response(&self, socket_adr: SocketAddr, message: Krpc) {
+        let Ok(transaction_id) = <[u8; 2]>::try_from(message.transaction_id()) else {
+            return;
+        };
+        let transaction_id = u16::from_be_bytes(transaction_id);
+        let query = {
+            let Ok(mut pending) = self.pending.lock() else {
+                return;
+            };
+            match pending.get(&transaction_id) {
+                Some(query) if query.socket_adr == socket_adr => pending.remove(&transaction_id),
+                _ => None,
+            }
+        };
+        if let Some(query) = query {
+            let _ = query.response.send(message);
+        }
+    }
+
+    /// Answers the query of the node with the given node ID, the node is added into the routing
+    /// table as it's clearly there. Gives back the message of the error sent instead, if the query
+    /// can't be answered.
+    async fn handle_query(&self, socket_adr: SocketAddr, id: NodeId, query: Query) -> Result<Response, &'static str> {
+        let mut response = {
+            let mut table = self.table.lock().await;
+            table.insert(id, socket_adr);
+            Response {
+                id: table.id(),
+                ..Default::default()
+            }
+        };
+
+        match query {
+            Query::Ping => {}
+
+            Query::FindNode {
+                target,
+            } => response.nodes = self.closest(&target).await,
+
+            Query::GetPeers {
+                info_hash,
+            } => {
+                response.token = Some(self.tokens.lock().await.token(socket_adr.ip()));
+                response.values = self.peers.lock().await.peers(&info_hash);
+                if response.values.is_empty() {
+                    response.nodes = self.closest(&info_hash).await;
+                }
+            }
+
+            Query::AnnouncePeer {
+                info_hash,
+                port,
+                implied_port,
+                token,
+            } => {
+                if !self.tokens.lock().await.is_valid(socket_adr.ip(), &token) {
+                    return Err("bad token");
+                }
+                let port = if implied_port { socket_adr.port() } else { port };
+                if port == 0 {
+                    return Err("bad port");
+                }
+                self.peers.lock().await.insert(info_hash, SocketAddr::new(socket_adr.ip(), port));
+            }
+        }
+        Ok(response)
+    }
+
+    /// Gives the K nodes closest to the given target
+    async fn closest(&self, target: &NodeId) -> Vec<(NodeId, SocketAddr)> {
+        self.table
+            .lock()
+            .await
+            .closest(target, K)
+            .into_iter()
+            .map(|node| (node.id, node.socket_adr))
+            .collect()
+    }
+
+    /// Sends the query to the node at the given socket address and waits for its response, it
+    /// will return "None" if the node responded with an error or didn't respond at all
+    ///
+    /// The node is added into the routing table once it responds, and a node in the routing
+    /// table that doesn't respond is let known to it, see [RoutingTable::failed]
+    async fn query(&self, socket_adr: SocketAddr, query: Query) -> Option<Response> {
+        let socket_adr = net::canonical(socket_adr);
+        let mut pending = self.register(socket_adr);
+        let message = Krpc::Query {
+            transaction_id: pending.transaction_id.to_be_bytes().to_vec(),
+            id: self.id().await,
+            query,
+        };
+
+        let sent = self.send_to(&message.to_bytes(), socket_adr).await.is_ok();
+        let reply = match sent {
+            true => timeout(QUERY_TIMEOUT, pending.recv()).await.ok().flatten(),
+            false => None,
+        };
+        match reply {
+            Some(Krpc::Response {
+                response, ..
+            }) => {
+                self.table.lock().await.insert(response.id, socket_adr);
+                Some(response)
+            }
+            // The node is there, it just won't answer the query
+            Some(_) => None,
+            None => {
+                self.table.lock().await.failed(socket_adr);
+                None
+            }
+        }
+    }
+
+    /// Looks up the given target, the K closest nodes we know about are queried first, and then
+    /// the closer nodes they give back, until the K closest nodes of all have been queried
+    ///
+    /// The peers are asked for if it's a lookup of an info hash, which also gets us a token from
+    /// each node to announce ourselves with. The seeds are queried along with the nodes of the
+    /// routing table.
+    async fn lookup(&self, target: NodeId, get_peers: bool, seeds: Vec<(NodeId, SocketAddr)>) -> Lookup {
+        let id = self.id().await;
+        let query = match get_peers {
+            true => Query::GetPeers {
+                info_hash: target,
+            },
+            false => Query::FindNode {
+                target,
+            },
+        };
+
+        // The nodes by their distance to the target
+        let mut candidates: BTreeMap<NodeId, (NodeId, SocketAddr)> = BTreeMap::new();
+        let mut responded: BTreeMap<NodeId, (NodeId, SocketAddr, Option<Vec<u8>>)> = BTreeMap::new();
+        for node in self.table.lock().await.closest(&target, K) {
+            candidates.insert(distance(&node.id, &target), (node.id, node.socket_adr));
+        }
+        for (node_id, socket_adr) in seeds {
+            candidates.insert(distance(&node_id, &target), (node_id, net::canonical(socket_adr)));
+        }
+
+        let mut queried = HashSet::new();
+        let mut peers = HashSet::new();
+        loop {
+            let picked: Vec<(NodeId, SocketAddr)> = candidates
+                .values()
+                .take(K)
+                .filter(|(_, socket_adr)| !queried.contains(socket_adr))
+                .take(ALPHA)
+                .copied()
+                .collect();
+            if picked.is_empty() {
+                break;
+            }
+            queried.extend(picked.iter().map(|(_, socket_adr)| *socket_adr));
+
+            let responses = join_all(picked.iter().map(|(_, socket_adr)| self.query(*socket_adr, query.clone()))).await;
+            for ((node_id, socket_adr), response) in picked.into_iter().zip(responses) {
+                let Some(response) = response else {
+                    candidates.remove(&distance(&node_id, &target));
+                    continue;
+                };
+                peers.extend(response.values);
+                for (closer_id, closer_adr) in response.nodes {
+                    let closer_adr = net::canonical(closer_adr);
+                    if closer_id != id && !queried.contains(&closer_adr) {
+                        candidates.entry(distance(&closer_id, &target)).or_insert((closer_id, closer_adr));
+                    }
+                }
+                responded.insert(distance(&response.id, &target), (response.id, socket_adr, response.token));
+            }
+        }
+
+        Lookup {
+            peers: peers.into_iter().collect(),
+            nodes: responded.into_values().take(K).collect(),
+        }
+    }
+
+    /// Keeps the routing table up to date forever, the questionable nodes are pinged and the
+    /// buckets that haven't changed for a while are refreshed, the routing table is saved every
+    /// [SAVE_INTERVAL]
+    async fn maintain(&self) {
+        let mut maintenance = interval(MAINTENANCE_INTERVAL);
+        let mut save = interval_at(Instant::now() + SAVE_INTERVAL, SAVE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = maintenance.tick() => self.refresh().await,
+                _ = save.tick() => {
+                    // TODO : Let the user know that the routing table couldn't be saved
+                    let _ = self.save().await;
+                }
+            }
+        }
+    }
+
+    /// Pings the questionable nodes and refreshes the buckets that haven't changed for a while,
+    /// the bootstrap nodes are asked again when the routing table is short of nodes
+    async fn refresh(&self) {
+        if self.nodes_count().await < K {
+            self.bootstrap().await;
+        }
+
+        let questionable = self.table.lock().await.questionable();
+        join_all(questionable.iter().map(|node| self.query(node.socket_adr, Query::Ping))).await;
+
+        let targets = self.table.lock().await.refresh_targets();
+        for target in targets {
+            self.lookup(target, false, Vec::new()).await;
+        }
+    }
+
+    /// Reserves a transaction ID that isn't used by any other query waiting for its response,
+    /// for a query to be sent to the node at the given socket address
+    fn register(&self, socket_adr: SocketAddr) -> PendingResponse<'_> {
+        let (sender, receiver) = oneshot::channel();
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let transaction_id = loop {
+            let transaction_id = thread_rng().gen();
+            if !pending.contains_key(&transaction_id) {
+                break transaction_id;
+            }
+        };
+        pending.insert(
+            transaction_id,
+            PendingQuery {
+                socket_adr,
+                response: sender,
+            },
+        );
+
+        PendingResponse {
+            dht: self,
+            transaction_id,
+            response: receiver,
+        }
+    }
+
+    /// Sends the given bytes to the node at the given socket address
+    async fn send_to(&self, bytes: &[u8], socket_adr: SocketAddr) -> io::Result<()> {
+        let socket = match *self.socket.lock().await {
+            Some(ref socket) => socket.clone(),
+            None => return Err(io::Error::new(io::ErrorKind::NotConnected, "the DHT socket isn't bound")),
+        };
+        let socket_is_ipv6 = socket.local_addr()?.is_ipv6();
+        socket.send_to(bytes, net::reachable_from(socket_adr, socket_is_ipv6)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    /// No of nodes in the swarm, enough for the lookups to have to go through a few of them
+    const SWARM_SIZE: usize = 24;
+
+    /// Runs a swarm of nodes on the loopback address, every node bootstrapping from the first one
+    async fn swarm() -> Vec<Arc<Dht>> {
+        let mut nodes = Vec::new();
+        for _ in 0..SWARM_SIZE {
+            let node = Arc::new(Dht::new(None));
+            node.bind_on(0).await.unwrap();
+            node.set_bootstrap_nodes(Vec::new());
+            let running = node.clone();
+            tokio::spawn(async move { running.run().await });
+            nodes.push(node);
+        }
+
+        let first = loopback(nodes[0].port());
+        for node in nodes.iter().skip(1) {
+            node.set_bootstrap_nodes(vec![first.to_string()]);
+            node.bootstrap().await;
+        }
+        nodes
+    }
+
+    fn loopback(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+    }
+
+    #[test]
+    fn krpc_messages_roundtrip() {
+        let messages = [
+            Krpc::Query {
+                transaction_id: b"aa".to_vec(),
+                id: [1; 20],
+                query: Query::AnnouncePeer {
+                    info_hash: [2; 20],
+                    port: 6881,
+                    implied_port: true,
+                    token: b"token".to_vec(),
+                },
+            },
+            Krpc::Response {
+                transaction_id: b"bb".to_vec(),
+                response: Response {
+                    id: [3; 20],
+                    nodes: vec![([4; 20], loopback(6881)), ([5; 20], "[2001:db8::1]:6882".parse().unwrap())],
+                    values: vec![loopback(51413)],
+                    token: Some(b"token".to_vec()),
+                },
+            },
+            Krpc::Error {
+                transaction_id: b"cc".to_vec(),
+                code: PROTOCOL_ERROR,
+                message: "bad token".to_string(),
+            },
+        ];
+        for message in messages {
+            assert_eq!(Krpc::from(&message.to_bytes()), Some(message));
+        }
+    }
+
+    #[tokio::test]
+    async fn swarm_bootstraps_from_a_single_node() {
+        let nodes = swarm().await;
+        for node in &nodes {
+            assert!(node.nodes_count().await > 0);
+        }
+
+        // The nodes found by a lookup are the K closest of the entire swarm, other than ourselves
+        let (looking_up, target) = (&nodes[SWARM_SIZE - 1], random_id());
+        let mut ids = Vec::new();
+        for node in &nodes[..SWARM_SIZE - 1] {
+            ids.push(node.id().await);
+        }
+        ids.sort_by_key(|id| distance(id, &target));
+        ids.truncate(K);
+
+        let lookup = looking_up.lookup(target, false, Vec::new()).await;
+        let found: Vec<NodeId> = lookup.nodes.iter().map(|(id, _, _)| *id).collect();
+        assert_eq!(found, ids);
+    }
+
+    #[tokio::test]
+    async fn announced_peer_is_found_by_the_others() {
+        let nodes = swarm().await;
+        let info_hash = random_id();
+        assert!(nodes[3].get_peers(info_hash).await.is_empty());
+
+        nodes[5].announce(info_hash, 51413).await;
+        for node in nodes.iter().skip(6) {
+            assert_eq!(node.get_peers(info_hash).await, vec![loopback(51413)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn announce_with_bad_token_is_refused() {
+        let nodes = swarm().await;
+        let info_hash = random_id();
+        let target = loopback(nodes[1].port());
+
+        let query = Query::AnnouncePeer {
+            info_hash,
+            port: 51413,
+            implied_port: false,
+            token: b"forged".to_vec(),
+        };
+        assert_eq!(nodes[2].query(target, query).await, None);
+
+        let token = nodes[2]
+            .query(
+                target,
+                Query::GetPeers {
+                    info_hash,
+                },
+            )
+            .await
+            .unwrap()
+            .token
+            .unwrap();
+        let query = Query::AnnouncePeer {
+            info_hash,
+            port: 51413,
+            implied_port: false,
+            token,
+        };
+        assert!(nodes[2].query(target, query).await.is_some());
+        assert_eq!(nodes[1].peers.lock().await.peers(&info_hash), vec![loopback(51413)]);
+    }
+
+    #[tokio::test]
+    async fn routing_table_is_kept_between_runs() {
+        let nodes = swarm().await;
+        let path = std::env::temp_dir()
+            .join(format!("hyperblow-dht-{}", nodes[0].port()))
+            .join("dht.state");
+        let (id, nodes_count) = (nodes[1].id().await, nodes[1].nodes_count().await);
+        nodes[1].table.lock().await.save(&path).await.unwrap();
+
+        let loaded = RoutingTable::load(&path).await.unwrap();
+        assert_eq!((loaded.id(), loaded.len()), (id, nodes_count));
+
+        // A node run with the saved routing table takes on its node ID
+        let node = Dht::new(Some(path.clone()));
+        node.bind_on(0).await.unwrap();
+        node.set_bootstrap_nodes(Vec::new());
+        tokio::select! {
+            _ = node.run() => {}
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+        assert_eq!(node.id().await, id);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
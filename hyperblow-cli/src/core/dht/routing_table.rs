@@ -0,0 +1,310 @@
+/*
+ * NOTE : This file contains the routing table of the DHT node, along with the way it's kept on
+ * the disk between the runs
+ *
+ * https://www.bittorrent.org/beps/bep_0005.html
+ *
+ * The nodes are put in buckets by the length of the prefix their node ID shares with ours, i.e
+ * the no of leading zero bits of the distance between the two, each bucket holding upto K nodes.
+ * It's the routing table of BEP5 with every bucket split as far as it goes, so we know a lot of
+ * the nodes close to us and only a few of the ones far away.
+ */
+
+use crate::core::{net, resume::RESUME_DIRECTORY};
+use rand::{thread_rng, Rng};
+use serde_bencode::value::Value;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::{fs, io};
+
+/// A 160 bit identifier, of a node as well as of a torrent, i.e its info hash
+pub type NodeId = [u8; 20];
+
+/// No of nodes a bucket holds, and no of nodes given back in the responses
+pub const K: usize = 8;
+
+/// No of buckets, one for every length of the prefix shared with our node ID
+const BUCKETS_COUNT: usize = 160;
+
+/// A node that has neither responded nor sent us a query for this long is questionable, and
+/// gets pinged to find out whether it's still there
+const QUESTIONABLE_AFTER: Duration = Duration::from_secs(15 * 60);
+
+/// A bucket that hasn't changed for this long is refreshed, by looking up a random node ID that
+/// would fall in it
+const REFRESH_AFTER: Duration = Duration::from_secs(15 * 60);
+
+/// No of queries in a row a node can fail to respond to, before it's removed
+const MAX_FAILED_QUERIES: usize = 3;
+
+/// Length of a node in the compact form, the node ID followed by the compact peer of its address
+pub const COMPACT_NODE_LENGTH: usize = 26;
+pub const COMPACT_NODE6_LENGTH: usize = 38;
+
+/// Gives the XOR distance between the given node IDs
+pub fn distance(a: &NodeId, b: &NodeId) -> NodeId {
+    let mut distance = [0; 20];
+    for (index, byte) in distance.iter_mut().enumerate() {
+        *byte = a[index] ^ b[index];
+    }
+    distance
+}
+
+/// Gives a random node ID
+pub fn random_id() -> NodeId {
+    thread_rng().gen()
+}
+
+/// Parses the nodes in the compact form, 26 bytes each for IPv4 and 38 bytes each for IPv6,
+/// given the length of a single node
+pub fn compact_nodes(v: &[u8], node_length: usize) -> Vec<(NodeId, SocketAddr)> {
+    v.chunks_exact(node_length)
+        .filter_map(|node| {
+            let id = NodeId::try_from(&node[..20]).ok()?;
+            Some((id, net::compact_peer(&node[20..])?))
+        })
+        .collect()
+}
+
+/// Gives the compact form of the given node, see [compact_nodes]
+pub fn to_compact_node(id: &NodeId, socket_adr: SocketAddr) -> Vec<u8> {
+    let mut v = id.to_vec();
+    v.extend(net::to_compact_peer(socket_adr));
+    v
+}
+
+/// Puts the given nodes in the compact form, the IPv4 ones and the IPv6 ones are given back apart,
+/// as they go under their own keys
+pub fn to_compact_nodes(nodes: &[(NodeId, SocketAddr)]) -> (Vec<u8>, Vec<u8>) {
+    let (mut nodes4, mut nodes6) = (Vec::new(), Vec::new());
+    for (id, socket_adr) in nodes {
+        match net::canonical(*socket_adr).is_ipv4() {
+            true => nodes4.extend(to_compact_node(id, *socket_adr)),
+            false => nodes6.extend(to_compact_node(id, *socket_adr)),
+        }
+    }
+    (nodes4, nodes6)
+}
+
+/// A node in the routing table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub id: NodeId,
+
+    /// Socket address the node is reached at, an IPv4 address is never IPv4-mapped
+    pub socket_adr: SocketAddr,
+
+    /// The last time the node responded to us or sent us a query
+    pub last_seen: Instant,
+
+    /// No of queries in a row the node hasn't responded to
+    pub failed_queries: usize,
+}
+
+/// The nodes of the DHT we know about, see the NOTE at the top
+#[derive(Debug)]
+pub struct RoutingTable {
+    /// Our own node ID
+    id: NodeId,
+
+    /// The nodes by the length of the prefix their node ID shares with ours
+    buckets: Vec<Vec<Node>>,
+
+    /// The last time each bucket had a node added or removed
+    changed_at: Vec<Instant>,
+}
+
+impl RoutingTable {
+    /// Creates an empty routing table of the node with the given node ID
+    pub fn new(id: NodeId) -> Self {
+        Self {
+            id,
+            buckets: vec![Vec::new(); BUCKETS_COUNT],
+            changed_at: vec![Instant::now(); BUCKETS_COUNT],
+        }
+    }
+
+    /// Our own node ID
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// No of nodes in the routing table
+    pub fn len(&self) -> usize {
+        self.buckets.iter().map(|bucket| bucket.len()).sum()
+    }
+
+    /// Index of the bucket the given node ID falls in, "None" for our own node ID
+    fn bucket_index(&self, id: &NodeId) -> Option<usize> {
+        let distance = distance(&self.id, id);
+        let leading_zeros = distance.iter().position(|byte| *byte != 0)?;
+        Some(leading_zeros * 8 + distance[leading_zeros].leading_zeros() as usize)
+    }
+
+    /// Lets the routing table know that the node has responded to us or sent us a query, it's
+    /// added if its bucket has room, or if a node of the bucket has gone bad. Gives back whether
+    /// the node is in the routing table.
+    pub fn insert(&mut self, id: NodeId, socket_adr: SocketAddr) -> bool {
+        let Some(index) = self.bucket_index(&id) else {
+            return false;
+        };
+        let socket_adr = net::canonical(socket_adr);
+        let bucket = &mut self.buckets[index];
+        if let Some(node) = bucket.iter_mut().find(|node| node.id == id) {
+            node.socket_adr = socket_adr;
+            node.last_seen = Instant::now();
+            node.failed_queries = 0;
+            return true;
+        }
+
+        // A node that's already in under some other node ID isn't let in again
+        if bucket.iter().any(|node| node.socket_adr == socket_adr) {
+            return false;
+        }
+        if bucket.len() >= K {
+            match bucket.iter().position(|node| node.failed_queries > 0) {
+                Some(position) => {
+                    bucket.remove(position);
+                }
+                None => return false,
+            }
+        }
+        bucket.push(Node {
+            id,
+            socket_adr,
+            last_seen: Instant::now(),
+            failed_queries: 0,
+        });
+        self.changed_at[index] = Instant::now();
+        true
+    }
+
+    /// Lets the routing table know that the node at the given socket address hasn't responded to
+    /// a query, it's removed once it has failed [MAX_FAILED_QUERIES] in a row
+    pub fn failed(&mut self, socket_adr: SocketAddr) {
+        let socket_adr = net::canonical(socket_adr);
+        for (index, bucket) in self.buckets.iter_mut().enumerate() {
+            if let Some(position) = bucket.iter().position(|node| node.socket_adr == socket_adr) {
+                bucket[position].failed_queries += 1;
+                if bucket[position].failed_queries >= MAX_FAILED_QUERIES {
+                    bucket.remove(position);
+                    self.changed_at[index] = Instant::now();
+                }
+                return;
+            }
+        }
+    }
+
+    /// Gives upto the given no of nodes closest to the given target, closest first
+    pub fn closest(&self, target: &NodeId, count: usize) -> Vec<Node> {
+        let mut nodes: Vec<Node> = self.buckets.iter().flatten().cloned().collect();
+        nodes.sort_by_key(|node| distance(&node.id, target));
+        nodes.truncate(count);
+        nodes
+    }
+
+    /// Gives the nodes that haven't been seen for [QUESTIONABLE_AFTER]
+    pub fn questionable(&self) -> Vec<Node> {
+        self.buckets
+            .iter()
+            .flatten()
+            .filter(|node| node.last_seen.elapsed() >= QUESTIONABLE_AFTER)
+            .cloned()
+            .collect()
+    }
+
+    /// Gives a random node ID in each bucket that hasn't changed for [REFRESH_AFTER], looking up
+    /// those node IDs fills the buckets up again
+    ///
+    /// Only the buckets upto the first empty one after the closest nodes are refreshed, there
+    /// aren't enough nodes in the entire DHT for the ones beyond it
+    pub fn refresh_targets(&mut self) -> Vec<NodeId> {
+        let deepest = self
+            .buckets
+            .iter()
+            .rposition(|bucket| !bucket.is_empty())
+            .map_or(0, |index| index + 1);
+        let mut targets = Vec::new();
+        for index in 0..=deepest.min(BUCKETS_COUNT - 1) {
+            if self.changed_at[index].elapsed() < REFRESH_AFTER {
+                continue;
+            }
+            self.changed_at[index] = Instant::now();
+
+            // Shares the first "index" bits with our node ID, differs in the next one and the
+            // rest are random
+            let mut target = random_id();
+            for bit in 0..=index {
+                let (byte, mask) = (bit / 8, 0x80 >> (bit % 8));
+                let own_bit = self.id[byte] & mask;
+                let bit_value = if bit == index { own_bit ^ mask } else { own_bit };
+                target[byte] = (target[byte] & !mask) | bit_value;
+            }
+            targets.push(target);
+        }
+        targets
+    }
+
+    /// Path of the routing table saved inside of the given directory, it's kept along with the
+    /// resume data of the torrents
+    ///
+    /// <directory>/.hyperblow/dht.state
+    pub fn path(directory: &String) -> PathBuf {
+        PathBuf::from(directory).join(RESUME_DIRECTORY).join("dht.state")
+    }
+
+    /// Reads the routing table saved at the given path, "None" if there's none or it can't be
+    /// read, in which case we start off with a new node ID
+    ///
+    /// The nodes read are taken as they are, the ones that have gone away get removed as they
+    /// fail to respond
+    pub async fn load(path: &PathBuf) -> Option<Self> {
+        let v = fs::read(path).await.ok()?;
+        let Value::Dict(dict) = serde_bencode::from_bytes::<Value>(&v).ok()? else {
+            return None;
+        };
+        let bytes = |key: &[u8]| match dict.get(key) {
+            Some(Value::Bytes(bytes)) => bytes.as_slice(),
+            _ => &[],
+        };
+
+        let mut table = Self::new(NodeId::try_from(bytes(b"id")).ok()?);
+        let nodes = [
+            compact_nodes(bytes(b"nodes"), COMPACT_NODE_LENGTH),
+            compact_nodes(bytes(b"nodes6"), COMPACT_NODE6_LENGTH),
+        ];
+        for (id, socket_adr) in nodes.concat() {
+            table.insert(id, socket_adr);
+        }
+        Some(table)
+    }
+
+    /// Writes our node ID and the nodes of the routing table to the given path, a temporary file
+    /// is written first and then renamed, so that it isn't left half written
+    ///
+    /// Keys of the dictionary :
+    /// id          Our own node ID
+    /// nodes       The IPv4 nodes in the compact form, 26 bytes each
+    /// nodes6      The IPv6 nodes in the compact form, 38 bytes each
+    pub async fn save(&self, path: &PathBuf) -> io::Result<()> {
+        let nodes: Vec<(NodeId, SocketAddr)> = self.buckets.iter().flatten().map(|node| (node.id, node.socket_adr)).collect();
+        let (nodes, nodes6) = to_compact_nodes(&nodes);
+        let dict = HashMap::from([
+            (b"id".to_vec(), Value::Bytes(self.id.to_vec())),
+            (b"nodes".to_vec(), Value::Bytes(nodes)),
+            (b"nodes6".to_vec(), Value::Bytes(nodes6)),
+        ]);
+        let v = serde_bencode::to_bytes(&Value::Dict(dict)).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).await?;
+        }
+        let temp_path = path.with_extension("state.tmp");
+        fs::write(&temp_path, v).await?;
+        fs::rename(&temp_path, path).await
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * NOTE : This file contains the tokens given out in the responses to get_peers, and the peers
+ * announced to us with them
+ *
+ * https://www.bittorrent.org/beps/bep_0005.html
+ *
+ * A token is the SHA1 hash of the IP address of the node asking for it along with a secret, so
+ * that only a node that has asked us for the peers of a torrent from that IP address can announce
+ * itself as a peer. The secret changes every 5 minutes and the previous one is still accepted, so
+ * a token is good for upto 10 minutes.
+ */
+
+use super::routing_table::NodeId;
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use sha1::{Digest, Sha1};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+/// Time after which the secret is changed
+const SECRET_LIFETIME: Duration = Duration::from_secs(5 * 60);
+
+/// Length of a token, the first bytes of the SHA1 hash
+const TOKEN_LENGTH: usize = 8;
+
+/// Time a peer announced to us is kept, unless it announces itself again
+const PEER_LIFETIME: Duration = Duration::from_secs(30 * 60);
+
+/// No of peers kept for a single torrent, the ones announced after that are dropped
+const MAX_PEERS_PER_TORRENT: usize = 200;
+
+/// No of peers given back in a single response to get_peers, so that it fits in a UDP packet
+pub const MAX_VALUES: usize = 50;
+
+/// The secrets the tokens are made with, see the NOTE at the top
+#[derive(Debug)]
+pub struct Tokens {
+    secret: [u8; 20],
+
+    /// The secret before the current one, the tokens made with it are still accepted
+    previous_secret: [u8; 20],
+
+    /// The last time the secret was changed
+    changed_at: Instant,
+}
+
+impl Default for Tokens {
+    fn default() -> Self {
+        let secret = thread_rng().gen();
+        Self {
+            secret,
+            previous_secret: secret,
+            changed_at: Instant::now(),
+        }
+    }
+}
+
+impl Tokens {
+    /// Gives the token for the node with the given IP address
+    pub fn token(&mut self, ip: IpAddr) -> Vec<u8> {
+        self.rotate();
+        Self::token_with(&self.secret, ip)
+    }
+
+    /// Whether the given token was given to the node with the given IP address, within the last
+    /// two secrets
+    pub fn is_valid(&mut self, ip: IpAddr, token: &[u8]) -> bool {
+        self.rotate();
+        token == Self::token_with(&self.secret, ip) || token == Self::token_with(&self.previous_secret, ip)
+    }
+
+    /// Changes the secret, if it's been in use for [SECRET_LIFETIME]
+    fn rotate(&mut self) {
+        if self.changed_at.elapsed() >= SECRET_LIFETIME {
+            self.previous_secret = self.secret;
+            self.secret = thread_rng().gen();
+            self.changed_at = Instant::now();
+        }
+    }
+
+    fn token_with(secret: &[u8; 20], ip: IpAddr) -> Vec<u8> {
+        let mut hasher = Sha1::new();
+        match ip.to_canonical() {
+            IpAddr::V4(ip) => hasher.update(ip.octets()),
+            IpAddr::V6(ip) => hasher.update(ip.octets()),
+        }
+        hasher.update(secret);
+        hasher.finalize()[..TOKEN_LENGTH].to_vec()
+    }
+}
+
+/// The peers announced to us, by the info hash of their torrent
+#[derive(Debug, Default)]
+pub struct PeerStore {
+    peers: HashMap<NodeId, HashMap<SocketAddr, Instant>>,
+}
+
+impl PeerStore {
+    /// Keeps the peer announced for the torrent with the given info hash, for [PEER_LIFETIME]
+    pub fn insert(&mut self, info_hash: NodeId, socket_adr: SocketAddr) {
+        self.expire();
+        let peers = self.peers.entry(info_hash).or_default();
+        if peers.len() < MAX_PEERS_PER_TORRENT || peers.contains_key(&socket_adr) {
+            peers.insert(socket_adr, Instant::now());
+        }
+    }
+
+    /// Gives upto [MAX_VALUES] peers of the torrent with the given info hash, picked at random
+    pub fn peers(&mut self, info_hash: &NodeId) -> Vec<SocketAddr> {
+        self.expire();
+        let Some(peers) = self.peers.get(info_hash) else {
+            return Vec::new();
+        };
+        let mut peers: Vec<SocketAddr> = peers.keys().copied().collect();
+        let count = peers.len().min(MAX_VALUES);
+        let (picked, _) = peers.partial_shuffle(&mut thread_rng(), count);
+        picked.to_vec()
+    }
+
+    /// Drops the peers that haven't announced themselves again within [PEER_LIFETIME]
+    fn expire(&mut self) {
+        for peers in self.peers.values_mut() {
+            peers.retain(|_, announced_at| announced_at.elapsed() < PEER_LIFETIME);
+        }
+        self.peers.retain(|_, peers| !peers.is_empty());
+    }
+}
@@ -0,0 +1,371 @@
+/*
+ * NOTE : This file contains the Local Service Discovery of the engine, through which the peers of
+ * a torrent are found on the local network
+ *
+ * https://www.bittorrent.org/beps/bep_0014.html
+ *
+ * The info hashes of the running torrents are announced every now and then in a BT-SEARCH message,
+ * a HTTP like request multicast to the LSD group, along with the port we're listening on. The
+ * announces received from the other hosts on the network are turned into the peers of our torrents
+ * with those info hashes. Every host puts a cookie of its own in its announces, so the announces
+ * looped back to us are told apart and ignored.
+ */
+
+use crate::core::{
+    net,
+    peer::{Peer, PeerSource},
+    state::{DownState, EngineState, State},
+};
+use crossbeam::atomic::AtomicCell;
+use futures::future::join_all;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    join,
+    net::UdpSocket,
+    sync::Mutex,
+    time::{interval, Instant},
+};
+
+/// The multicast groups the announces are sent to, one for IPv4 and one for IPv6, along with the
+/// port they're sent on
+const LSD_GROUP: Ipv4Addr = Ipv4Addr::new(239, 192, 152, 143);
+const LSD_GROUP6: Ipv6Addr = Ipv6Addr::new(0xff15, 0, 0, 0, 0, 0, 0xefc0, 0x988f);
+const LSD_PORT: u16 = 6771;
+
+/// Time between the announces of a single torrent
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Time between the checks for the torrents due to be announced, a torrent that has just been
+/// started is announced within this long
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// No of info hashes put in a single announce, so that it stays under 1400 bytes as BEP14 asks for
+const MAX_INFO_HASHES: usize = 20;
+
+/// Length of the cookie put in our announces
+const COOKIE_LENGTH: usize = 8;
+
+/// Size of the buffer an announce is received in, the bigger ones are cut short
+const MAX_MESSAGE_LENGTH: usize = 2048;
+
+/// A BT-SEARCH message, announcing that the host sending it is a peer of the torrents with the
+/// given info hashes
+///
+/// BT-SEARCH * HTTP/1.1\r\n
+/// Host: <group>:<port>\r\n
+/// Port: <port the host is listening on>\r\n
+/// Infohash: <info hash in hex>\r\n        (once for every info hash)
+/// cookie: <cookie of the host>\r\n
+/// \r\n
+/// \r\n
+#[derive(Debug, Clone, PartialEq)]
+pub struct Announce {
+    /// TCP port the host is listening on for the peers
+    pub port: u16,
+
+    pub info_hashes: Vec<Vec<u8>>,
+
+    /// Tells apart the announces of a host, it's optional
+    pub cookie: Option<String>,
+}
+
+impl Announce {
+    /// Parses a BT-SEARCH message, "None" if it's malformed or it has no info hash in it, the
+    /// names of the headers are matched regardless of their case
+    pub fn from(v: &[u8]) -> Option<Self> {
+        let message = std::str::from_utf8(v).ok()?;
+        let mut lines = message.lines();
+        if lines.next()?.trim() != "BT-SEARCH * HTTP/1.1" {
+            return None;
+        }
+
+        let (mut port, mut info_hashes, mut cookie) = (None, Vec::new(), None);
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "port" => port = value.parse::<u16>().ok(),
+                "infohash" => {
+                    if let Some(info_hash) = from_hex(value) {
+                        info_hashes.push(info_hash);
+                    }
+                }
+                "cookie" => cookie = Some(value.to_owned()),
+                _ => {}
+            }
+        }
+
+        if info_hashes.is_empty() {
+            return None;
+        }
+        Some(Self {
+            port: port.filter(|port| *port != 0)?,
+            info_hashes,
+            cookie,
+        })
+    }
+
+    /// Gives the BT-SEARCH message to be sent to the given multicast group
+    pub fn to_bytes(&self, group: SocketAddr) -> Vec<u8> {
+        let mut message = format!("BT-SEARCH * HTTP/1.1\r\nHost: {}\r\nPort: {}\r\n", group, self.port);
+        for info_hash in &self.info_hashes {
+            message.push_str(&format!("Infohash: {}\r\n", to_hex(info_hash)));
+        }
+        if let Some(ref cookie) = self.cookie {
+            message.push_str(&format!("cookie: {}\r\n", cookie));
+        }
+        message.push_str("\r\n\r\n");
+        message.into_bytes()
+    }
+}
+
+/// Parses an info hash written in hex, in either case
+fn from_hex(v: &str) -> Option<Vec<u8>> {
+    if v.len() != 40 {
+        return None;
+    }
+    (0..40).step_by(2).map(|i| u8::from_str_radix(v.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn to_hex(v: &[u8]) -> String {
+    v.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The Local Service Discovery of the engine, see the NOTE at the top
+#[derive(Debug)]
+pub struct Lsd {
+    /// The UDP sockets that have joined the multicast groups, along with the group each one sends
+    /// to, it's empty until they have been bound
+    sockets: Mutex<Vec<(Arc<UdpSocket>, SocketAddr)>>,
+
+    /// Whether the torrents are announced and the announces received are turned into peers
+    enabled: AtomicCell<bool>,
+
+    /// Put in our announces, so that the ones looped back to us are ignored
+    cookie: String,
+}
+
+impl Default for Lsd {
+    fn default() -> Self {
+        let cookie = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(COOKIE_LENGTH)
+            .map(char::from)
+            .collect();
+        Self {
+            sockets: Mutex::new(Vec::new()),
+            enabled: AtomicCell::new(true),
+            cookie,
+        }
+    }
+}
+
+impl Lsd {
+    /// Joins the IPv4 and the IPv6 multicast groups, it fails only if neither of them could be
+    /// joined
+    pub async fn bind(&self) -> io::Result<()> {
+        let mut sockets = Vec::new();
+        let mut last_error = io::Error::new(io::ErrorKind::AddrNotAvailable, "no multicast group could be joined");
+        for group in [IpAddr::V4(LSD_GROUP), IpAddr::V6(LSD_GROUP6)] {
+            match net::bind_multicast(group, LSD_PORT) {
+                Ok(socket) => sockets.push((Arc::new(socket), SocketAddr::new(group, LSD_PORT))),
+                Err(e) => last_error = e,
+            }
+        }
+        if sockets.is_empty() {
+            return Err(last_error);
+        }
+        *self.sockets.lock().await = sockets;
+        Ok(())
+    }
+
+    /// Whether the torrents are announced on the local network and the peers found there are
+    /// connected with
+    pub fn enabled(&self) -> bool {
+        self.enabled.load()
+    }
+
+    /// Turns the Local Service Discovery on or off for all the torrents, the torrents are announced
+    /// again within [CHECK_INTERVAL] when it's turned on
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled);
+    }
+
+    /// Announces the running torrents and receives the announces of the other hosts, for as long
+    /// as the engine runs, the sockets must have been bound with [Lsd::bind] beforehand
+    pub async fn run(&self, engine_state: &EngineState) {
+        let sockets = self.sockets.lock().await.clone();
+        let receive = join_all(sockets.iter().map(|(socket, _)| self.receive(socket, engine_state)));
+        join!(receive, self.announce(&sockets, engine_state));
+    }
+
+    /// Announces every running torrent that isn't private nor stopped to all the multicast groups,
+    /// once every [ANNOUNCE_INTERVAL]
+    async fn announce(&self, sockets: &[(Arc<UdpSocket>, SocketAddr)], engine_state: &EngineState) {
+        let mut announced_at: HashMap<Vec<u8>, Instant> = HashMap::new();
+        let mut interval = interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let port = engine_state.listen_port();
+            if !self.enabled() || port == 0 {
+                announced_at.clear();
+                continue;
+            }
+
+            let torrents = engine_state.torrents().await;
+            announced_at.retain(|info_hash, _| torrents.iter().any(|state| state.info_hash == *info_hash));
+            let info_hashes: Vec<Vec<u8>> = torrents
+                .iter()
+                .filter(|state| !state.meta_info.isPrivate() && state.d_state() != DownState::Stopped)
+                .filter(|state| {
+                    announced_at
+                        .get(&state.info_hash)
+                        .is_none_or(|at| at.elapsed() >= ANNOUNCE_INTERVAL)
+                })
+                .map(|state| state.info_hash.clone())
+                .collect();
+
+            for info_hashes in info_hashes.chunks(MAX_INFO_HASHES) {
+                let announce = Announce {
+                    port,
+                    info_hashes: info_hashes.to_vec(),
+                    cookie: Some(self.cookie.clone()),
+                };
+                for (socket, group) in sockets {
+                    let _ = socket.send_to(&announce.to_bytes(*group), group).await;
+                }
+            }
+            for info_hash in info_hashes {
+                announced_at.insert(info_hash, Instant::now());
+            }
+        }
+    }
+
+    /// Receives the announces sent to the multicast group the socket has joined, and connects with
+    /// the hosts sending them as the peers of our torrents
+    async fn receive(&self, socket: &UdpSocket, engine_state: &EngineState) {
+        let mut buf = [0; MAX_MESSAGE_LENGTH];
+        loop {
+            let Ok((length, socket_adr)) = socket.recv_from(&mut buf).await else {
+                continue;
+            };
+            if !self.enabled() {
+                continue;
+            }
+            let Some(announce) = self.parse(&buf[..length]) else {
+                continue;
+            };
+
+            // The peer listens on the port in the announce, not on the one the announce is sent from
+            let socket_adr = SocketAddr::new(net::canonical(socket_adr).ip(), announce.port);
+            for info_hash in announce.info_hashes {
+                if let Some(state) = engine_state.torrent(&info_hash).await {
+                    Self::connect(engine_state, state, socket_adr).await;
+                }
+            }
+        }
+    }
+
+    /// Parses an announce received from the multicast group, "None" if it's malformed or if it's
+    /// one of our own announces looped back to us
+    fn parse(&self, v: &[u8]) -> Option<Announce> {
+        Announce::from(v).filter(|announce| announce.cookie.as_deref() != Some(self.cookie.as_str()))
+    }
+
+    /// Runs a session with the peer found on the local network, unless the torrent is private or
+    /// the connection limits have been reached
+    async fn connect(engine_state: &EngineState, state: Arc<State>, socket_adr: SocketAddr) {
+        if state.meta_info.isPrivate() || state.d_state() == DownState::Stopped || !engine_state.can_connect(&state).await {
+            return;
+        }
+
+        let mut peer = Peer::new(socket_adr, state.clone());
+        peer.set_source(PeerSource::Lsd);
+        let peer = Arc::new(peer);
+        if state.add_peer(peer.clone()).await {
+            tokio::spawn(async move {
+                peer.run().await;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announce() -> Announce {
+        Announce {
+            port: 6881,
+            info_hashes: vec![vec![0xab; 20], (0..20).collect()],
+            cookie: Some("hb-12345".to_string()),
+        }
+    }
+
+    #[test]
+    fn announce_round_trip() {
+        let group = SocketAddr::new(IpAddr::V4(LSD_GROUP), LSD_PORT);
+        let bytes = announce().to_bytes(group);
+        assert!(bytes.starts_with(b"BT-SEARCH * HTTP/1.1\r\nHost: 239.192.152.143:6771\r\nPort: 6881\r\n"));
+        assert!(bytes.ends_with(b"cookie: hb-12345\r\n\r\n\r\n"));
+        assert_eq!(Announce::from(&bytes), Some(announce()));
+
+        let announce = Announce {
+            cookie: None,
+            ..announce()
+        };
+        assert_eq!(Announce::from(&announce.to_bytes(group)), Some(announce));
+    }
+
+    #[test]
+    fn host_of_ipv6_group_is_in_brackets() {
+        let bytes = announce().to_bytes(SocketAddr::new(IpAddr::V6(LSD_GROUP6), LSD_PORT));
+        let message = String::from_utf8(bytes).unwrap();
+        assert!(message.contains("\r\nHost: [ff15::efc0:988f]:6771\r\n"));
+    }
+
+    #[test]
+    fn headers_are_matched_regardless_of_their_case() {
+        let message = "BT-SEARCH * HTTP/1.1\r\nHOST: 239.192.152.143:6771\r\nport: 51413\r\n\
+            INFOHASH: ABABABABABABABABABABABABABABABABABABABAB\r\nCookie: other\r\n\r\n\r\n";
+        let announce = Announce::from(message.as_bytes()).unwrap();
+        assert_eq!(announce.port, 51413);
+        assert_eq!(announce.info_hashes, vec![vec![0xab; 20]]);
+        assert_eq!(announce.cookie.as_deref(), Some("other"));
+    }
+
+    #[test]
+    fn malformed_announces_are_refused() {
+        let info_hash = "Infohash: abababababababababababababababababababab\r\n";
+        for message in [
+            format!("BT-SEARCH * HTTP/1.1\r\nPort: 0\r\n{info_hash}\r\n\r\n"),
+            format!("BT-SEARCH * HTTP/1.1\r\n{info_hash}\r\n\r\n"),
+            "BT-SEARCH * HTTP/1.1\r\nPort: 6881\r\n\r\n\r\n".to_string(),
+            "BT-SEARCH * HTTP/1.1\r\nPort: 6881\r\nInfohash: abab\r\n\r\n\r\n".to_string(),
+            format!("M-SEARCH * HTTP/1.1\r\nPort: 6881\r\n{info_hash}\r\n\r\n"),
+        ] {
+            assert_eq!(Announce::from(message.as_bytes()), None, "{message}");
+        }
+    }
+
+    #[test]
+    fn own_announces_are_ignored() {
+        let lsd = Lsd::default();
+        let group = SocketAddr::new(IpAddr::V4(LSD_GROUP), LSD_PORT);
+        let own = Announce {
+            cookie: Some(lsd.cookie.clone()),
+            ..announce()
+        };
+        assert_eq!(lsd.parse(&own.to_bytes(group)), None);
+        assert_eq!(lsd.parse(&announce().to_bytes(group)), Some(announce()));
+    }
+}
@@ -1,11 +1,102 @@
-//enum HashType {}
-//struct MagnetURI {
-//    /// URI of the Magnet link
-//    uri: String,
-//
-//    /// Info hash of the torrent
-//    info_hash: Vec<u8>,
-//
-//    /// Type of hash being used
-//    hash_type: HashType,
-//}
+use crate::core::{state::EngineState, TorrentFile};
+use hyperblow::parser::{magnet_uri_parser::MagnetURIMeta, torrent_parser::FileMeta};
+use std::sync::{Arc, RwLock as StdRwLock};
+use tokio_util::sync::CancellationToken;
+
+/// A torrent added through a magnet URI
+///
+/// It runs as a [TorrentFile] without the "info" field at first, which only announces to the
+/// trackers of the magnet URI and fetches the "info" field from the peers. Once the "info" field
+/// is received, a regular [TorrentFile] takes over from that one to download the torrent, with the
+/// same trackers and the same peers, see [TorrentFile::take_over].
+#[derive(Debug)]
+pub struct MagnetURI {
+    /// The magnet URI the torrent was added through
+    pub uri: String,
+
+    /// Data parsed from the magnet URI
+    pub meta: MagnetURIMeta,
+
+    /// State shared by all the torrents of the engine
+    engine_state: Arc<EngineState>,
+
+    /// The torrent being run, the one fetching the "info" field until it's received and the one
+    /// downloading the torrent after that
+    ///
+    /// NOTE : It's a std RwLock, as the torrent is read by the UI outside of the async context
+    torrent: StdRwLock<Arc<TorrentFile>>,
+
+    /// Cancelled to stop running the torrent for good, i.e when it's removed
+    cancel: CancellationToken,
+}
+
+impl MagnetURI {
+    /// Tries to parse the given magnet URI and creates a torrent that's yet to fetch its "info"
+    /// field
+    pub async fn new(uri: &String, engine_state: Arc<EngineState>) -> Option<Self> {
+        let meta = MagnetURIMeta::fromMagnetURI(uri).ok()?;
+        let meta_info = FileMeta::fromMagnetURI(&meta);
+        let torrent = TorrentFile::fromFileMeta(meta_info, meta.info_hash.clone(), uri, engine_state.clone()).await;
+
+        Some(Self {
+            uri: uri.to_string(),
+            meta,
+            engine_state,
+            torrent: StdRwLock::new(Arc::new(torrent)),
+            cancel: CancellationToken::new(),
+        })
+    }
+
+    /// The torrent being run, the one fetching the "info" field until it's received and the one
+    /// downloading the torrent after that
+    pub fn torrent(&self) -> Arc<TorrentFile> {
+        match self.torrent.read() {
+            Ok(torrent) => torrent.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Fetches the "info" field of the torrent from the peers and then downloads the torrent, it
+    /// runs until the torrent is removed
+    pub async fn run(&self) {
+        tokio::select! {
+            _ = self.run_me() => {}
+            _ = self.cancel.cancelled() => {}
+        }
+    }
+
+    async fn run_me(&self) {
+        let fetching = self.torrent();
+        let meta_info = tokio::select! {
+            _ = fetching.run() => return,
+            meta_info = fetching.state.metadata() => meta_info,
+        };
+
+        // The torrent that downloads carries on with the peers and the trackers of this one
+        let torrent = TorrentFile::fromFileMeta(meta_info, self.meta.info_hash.clone(), &self.uri, self.engine_state.clone()).await;
+        let torrent = Arc::new(torrent);
+        torrent.take_over(&fetching).await;
+        match self.torrent.write() {
+            Ok(mut current) => *current = torrent.clone(),
+            Err(poisoned) => *poisoned.into_inner() = torrent.clone(),
+        }
+
+        torrent.run().await;
+    }
+
+    /// Stops the torrent, see [TorrentFile::stop]
+    pub async fn stop(&self) {
+        self.torrent().stop().await;
+    }
+
+    /// Starts the torrent again after it was stopped, see [TorrentFile::start]
+    pub async fn start(&self) {
+        self.torrent().start().await;
+    }
+
+    /// Stops the torrent for good, the "info" field isn't waited for anymore either
+    pub async fn remove(&self) {
+        self.cancel.cancel();
+        self.torrent().remove().await;
+    }
+}
@@ -1,11 +1,18 @@
+pub mod dht;
+pub mod lsd;
+pub mod magnetURI;
+pub mod net;
 pub mod peer;
+pub mod resume;
 pub mod state;
+pub mod storage;
 pub mod torrentFile;
 pub mod tracker;
 
 use async_recursion::async_recursion;
 use hyperblow::parser::torrent_parser::FileMeta;
 use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use std::{sync::Arc, vec};
 use tokio::sync::Mutex;
 pub use torrentFile::TorrentFile;
@@ -176,6 +183,25 @@ impl File {
         return None;
     }
 
+    /// Whether the file at the given path, which is relative to this file, is to be downloaded or
+    /// not, a file inside of a directory that isn't to be downloaded isn't downloaded either
+    #[async_recursion]
+    pub async fn should_download_path(&self, path: &[String]) -> bool {
+        if !self.should_download {
+            return false;
+        }
+
+        if let (Some((name, inner_path)), Some(ref inner_files)) = (path.split_first(), &self.inner_files) {
+            for file in inner_files {
+                let file = file.lock().await;
+                if file.name == *name {
+                    return file.should_download_path(inner_path).await;
+                }
+            }
+        }
+        true
+    }
+
     #[async_recursion]
     pub async fn tabs_traverse_names(&self, depth: usize) -> Vec<String> {
         let mut x = vec![];
@@ -198,6 +224,20 @@ impl File {
     }
 }
 
+/// Generates a Peer ID for this client using the Azureus style of BEP 20
+///
+/// "-HB0100-" followed by 12 random alphanumeric characters, where "HB" is the client ID of
+/// Hyperblow and "0100" is the version
+pub fn generate_peer_id() -> [u8; 20] {
+    let mut peer_id = [0_u8; 20];
+    peer_id[..8].copy_from_slice(b"-HB0100-");
+    let mut rng = thread_rng();
+    for byte in peer_id[8..].iter_mut() {
+        *byte = rng.sample(Alphanumeric);
+    }
+    peer_id
+}
+
 // TODO: Make use of AsRef
 /// Encode the given byte vector of info_hash into a String of
 /// Percent Encoded info_hash
@@ -0,0 +1,131 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// No of connections waiting to be accepted at a time, before the new ones get refused
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// An address out on the IPv6 internet, used to find out the IPv6 address of the host, nothing is
+/// ever sent to it
+const IPV6_PROBE_ADR: (Ipv6Addr, u16) = (Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888), 53);
+
+/// Binds a UDP socket to the given port on all the IPv6 and IPv4 addresses of the host, or only
+/// on the IPv4 ones if the host doesn't support IPv6
+pub fn bind_udp(port: u16) -> io::Result<UdpSocket> {
+    let socket = bind_dual_stack(port, Type::DGRAM, Protocol::UDP)?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// Binds a TCP listener to the given port on all the IPv6 and IPv4 addresses of the host, or
+/// only on the IPv4 ones if the host doesn't support IPv6
+pub fn bind_tcp(port: u16) -> io::Result<TcpListener> {
+    let socket = bind_dual_stack(port, Type::STREAM, Protocol::TCP)?;
+    socket.listen(LISTEN_BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Binds an IPv6 socket that accepts the IPv4 traffic as well, through the IPv4-mapped IPv6
+/// addresses, falling back to an IPv4 socket when IPv6 isn't availaible
+fn bind_dual_stack(port: u16, ty: Type, protocol: Protocol) -> io::Result<Socket> {
+    let bind = |domain: Domain, address: SocketAddr| -> io::Result<Socket> {
+        let socket = Socket::new(domain, ty, Some(protocol))?;
+        if domain == Domain::IPV6 {
+            socket.set_only_v6(false)?;
+        }
+        if ty == Type::STREAM {
+            socket.set_reuse_address(true)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&address.into())?;
+        Ok(socket)
+    };
+
+    bind(Domain::IPV6, SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port))
+        .or_else(|_| bind(Domain::IPV4, SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port)))
+}
+
+/// Binds a UDP socket to the given port of the given multicast group and joins the group on the
+/// default interface, the port is shared with the other sockets that have joined the group, e.g
+/// other clients running on the same host
+///
+/// The datagrams sent to the group are looped back, so the ones we send are received as well
+pub fn bind_multicast(group: IpAddr, port: u16) -> io::Result<UdpSocket> {
+    let domain = match group {
+        IpAddr::V4(_) => Domain::IPV4,
+        IpAddr::V6(_) => Domain::IPV6,
+    };
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    match group {
+        IpAddr::V4(group) => {
+            socket.bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port).into())?;
+            socket.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?;
+            socket.set_multicast_loop_v4(true)?;
+        }
+        IpAddr::V6(group) => {
+            socket.set_only_v6(true)?;
+            socket.bind(&SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port).into())?;
+            socket.join_multicast_v6(&group, 0)?;
+            socket.set_multicast_loop_v6(true)?;
+        }
+    }
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// Gives the IPv6 address of the host, that the traffic to the IPv6 internet is sent from, "None"
+/// if the host has no such address
+///
+/// Nothing is sent, connecting a UDP socket only picks the route and thereby the source address
+pub fn local_ipv6() -> Option<Ipv6Addr> {
+    let socket = std::net::UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect(IPV6_PROBE_ADR).ok()?;
+    match socket.local_addr().ok()?.ip() {
+        // Link local addresses can't be reached by anyone outside of the link
+        IpAddr::V6(ip) if !ip.is_loopback() && !ip.is_unspecified() && !ip.is_unicast_link_local() => Some(ip),
+        _ => None,
+    }
+}
+
+/// Turns an IPv4-mapped IPv6 address, which is how a dual stack socket sees the IPv4 peers and
+/// trackers, back into the IPv4 address
+pub fn canonical(socket_adr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(socket_adr.ip().to_canonical(), socket_adr.port())
+}
+
+/// Gives the address to send to through a socket of the given family, a dual stack IPv6 socket
+/// reaches an IPv4 address through its IPv4-mapped IPv6 address
+pub fn reachable_from(socket_adr: SocketAddr, socket_is_ipv6: bool) -> SocketAddr {
+    match socket_adr.ip() {
+        IpAddr::V4(ip) if socket_is_ipv6 => SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), socket_adr.port()),
+        _ => socket_adr,
+    }
+}
+
+/// Parses a single peer in the compact form, the 4 bytes of an IPv4 address or the 16 bytes of
+/// an IPv6 address, followed by 2 bytes of the port, both in network byte order
+pub fn compact_peer(v: &[u8]) -> Option<SocketAddr> {
+    let (ip, port) = v.split_at(v.len().checked_sub(2)?);
+    let ip = match <[u8; 4]>::try_from(ip) {
+        Ok(ip) => IpAddr::from(ip),
+        Err(_) => IpAddr::from(<[u8; 16]>::try_from(ip).ok()?),
+    };
+    Some(SocketAddr::new(ip, u16::from_be_bytes([port[0], port[1]])))
+}
+
+/// Gives the compact form of the given peer, the 4 bytes of an IPv4 address or the 16 bytes of an
+/// IPv6 address, followed by 2 bytes of the port, see [compact_peer]
+pub fn to_compact_peer(socket_adr: SocketAddr) -> Vec<u8> {
+    let socket_adr = canonical(socket_adr);
+    let mut v = match socket_adr.ip() {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    };
+    v.extend_from_slice(&socket_adr.port().to_be_bytes());
+    v
+}
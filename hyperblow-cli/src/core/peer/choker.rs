@@ -0,0 +1,161 @@
+use super::{messages::Message, Peer, PeerState};
+use crate::core::state::{DownState, EngineState, State};
+use rand::{seq::SliceRandom, thread_rng};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::time::interval;
+
+/// Time between each round of choking and unchoking the peers
+pub const RECHOKE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The optimistic unchoke is moved onto another peer every this many rounds, i.e every 30 seconds
+const OPTIMISTIC_UNCHOKE_ROUNDS: usize = 3;
+
+/// No of peers unchoked at a time for a single torrent, including the optimistic unchoke, unless
+/// it's changed through [State::set_upload_slots]
+pub const DEFAULT_UPLOAD_SLOTS: usize = 4;
+
+/// No of peers unchoked at a time across all the torrents of the engine, unless it's changed
+/// through [EngineState::set_upload_slots]
+pub const DEFAULT_ENGINE_UPLOAD_SLOTS: usize = 20;
+
+/// Decides which peers of a torrent we upload to, using tit-for-tat
+///
+/// Every [RECHOKE_INTERVAL], the interested peers that upload to us the fastest get unchoked and
+/// the rest get choked. While seeding, nobody uploads to us, so the peers we upload to the fastest
+/// are unchoked instead. On top of that, one more peer is unchoked at random regardless of its
+/// rate, i.e the optimistic unchoke, which gives new peers a chance to show how fast they are.
+#[derive(Debug)]
+pub struct Choker {
+    /// State of the torrent whose peers are choked and unchoked
+    state: Arc<State>,
+
+    /// State shared by all the torrents of the engine, used to limit the upload slots of the
+    /// entire engine
+    engine_state: Arc<EngineState>,
+
+    /// No of rounds that have taken place so far
+    round: usize,
+
+    /// Socket address of the peer that's been optimistically unchoked
+    optimistic_unchoke: Option<SocketAddr>,
+
+    /// Total bytes downloaded from and uploaded to each peer, as of the previous round, used to
+    /// figure out the rates over the last round
+    last_transferred: HashMap<SocketAddr, (usize, usize)>,
+
+    /// Total bytes uploaded to all the peers of the torrent, as of the previous round
+    last_bytes_uploaded: usize,
+}
+
+impl Choker {
+    pub fn new(state: Arc<State>, engine_state: Arc<EngineState>) -> Self {
+        let last_bytes_uploaded = state.bytes_uploaded();
+        Self {
+            state,
+            engine_state,
+            round: 0,
+            optimistic_unchoke: None,
+            last_transferred: HashMap::new(),
+            last_bytes_uploaded,
+        }
+    }
+
+    /// Chokes and unchokes the peers every [RECHOKE_INTERVAL], forever
+    pub async fn run(&mut self) {
+        let mut rechoke_interval = interval(RECHOKE_INTERVAL);
+        loop {
+            rechoke_interval.tick().await;
+            self.rechoke().await;
+            self.round += 1;
+        }
+    }
+
+    /// A single round of choking and unchoking the peers
+    async fn rechoke(&mut self) {
+        // A stopped torrent has no peers, its upload slots are left for the other torrents
+        if self.state.d_state() == DownState::Stopped {
+            return;
+        }
+
+        let bytes_uploaded = self.state.bytes_uploaded();
+        let upload_speed = bytes_uploaded.saturating_sub(self.last_bytes_uploaded) / RECHOKE_INTERVAL.as_secs() as usize;
+        self.state.set_upload_speed(upload_speed);
+        self.last_bytes_uploaded = bytes_uploaded;
+
+        let is_seeding = self.state.picker.lock().await.is_complete();
+        let peers: Vec<Arc<Peer>> = self.state.peers.lock().await.clone();
+
+        // Figure out the rates of every connected peer over the last round, and rank the
+        // interested ones by it
+        let mut connected = Vec::new();
+        let mut interested = Vec::new();
+        let mut transferred = HashMap::new();
+        for peer in peers.iter() {
+            let mut info = peer.info.lock().await;
+            let is_connected = matches!(
+                info.peer_state,
+                PeerState::Handshaked | PeerState::RequestingPiece | PeerState::Downloading
+            );
+            if !is_connected {
+                continue;
+            }
+
+            let (last_downloaded, last_uploaded) = self.last_transferred.get(&peer.socket_adr).copied().unwrap_or_default();
+            let seconds = RECHOKE_INTERVAL.as_secs() as usize;
+            info.download_rate = info.downloaded.saturating_sub(last_downloaded) / seconds;
+            info.upload_rate = info.uploaded.saturating_sub(last_uploaded) / seconds;
+            transferred.insert(peer.socket_adr, (info.downloaded, info.uploaded));
+
+            if info.peer_interested {
+                let rate = if is_seeding { info.upload_rate } else { info.download_rate };
+                interested.push((peer.clone(), rate));
+            }
+            connected.push(peer.clone());
+        }
+        self.last_transferred = transferred;
+        interested.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        let upload_slots = self
+            .engine_state
+            .claim_upload_slots(&self.state.info_hash, self.state.upload_slots())
+            .await;
+
+        // One of the slots is kept for the optimistic unchoke
+        let regular_slots = upload_slots.saturating_sub(1);
+        let mut unchoked: Vec<SocketAddr> = interested.iter().take(regular_slots).map(|(peer, _)| peer.socket_adr).collect();
+
+        if upload_slots > 0 {
+            let optimistic_unchoke = self.optimistic_unchoke.filter(|socket_adr| {
+                let is_still_interested = interested.iter().any(|(peer, _)| peer.socket_adr == *socket_adr);
+                is_still_interested && !unchoked.contains(socket_adr)
+            });
+            self.optimistic_unchoke = match optimistic_unchoke {
+                Some(socket_adr) if !self.round.is_multiple_of(OPTIMISTIC_UNCHOKE_ROUNDS) => Some(socket_adr),
+                _ => {
+                    let candidates: Vec<SocketAddr> = interested
+                        .iter()
+                        .map(|(peer, _)| peer.socket_adr)
+                        .filter(|socket_adr| !unchoked.contains(socket_adr))
+                        .collect();
+                    candidates.choose(&mut thread_rng()).copied()
+                }
+            };
+            if let Some(socket_adr) = self.optimistic_unchoke {
+                unchoked.push(socket_adr);
+            }
+        }
+
+        for peer in connected {
+            let should_choke = !unchoked.contains(&peer.socket_adr);
+            let mut info = peer.info.lock().await;
+            if info.am_choking != should_choke {
+                // The requests of a choked peer are dropped
+                if should_choke {
+                    info.peer_requests.clear();
+                }
+                info.am_choking = should_choke;
+                peer.send(vec![if should_choke { Message::Choke } else { Message::Unchoke }]);
+            }
+        }
+    }
+}
@@ -1,60 +1,333 @@
 use super::{
-    messages::{Bitfield, Block, Cancel, Handshake, Have, Port, Request},
+    messages::{
+        Bitfield, Block, Cancel, Extended, Handshake, Have, Port, Request, BITFIELD_ID, CANCEL_ID, CHOKE_ID, EXTENDED_ID, HAVE_ID,
+        INTERESTED_ID, NOT_INTERESTED_ID, PIECE_ID, PORT_ID, REQUEST_ID, UNCHOKE_ID,
+    },
     Message,
 };
-use bytes::BufMut;
+use byteorder::{BigEndian, ReadBytesExt};
+use bytes::{BufMut, BytesMut};
+use std::io;
+use thiserror::Error;
 use tokio_util::codec::{Decoder, Encoder};
 
+/// Maximum value of the length prefix we accept from a peer, a Piece Message carrying a 16 KiB
+/// block is only (9 + 16384) bytes long, but a Bitfield Message of a torrent with a lot of pieces
+/// can get a lot bigger than that
+pub const MAX_FRAME_LENGTH: u32 = 1 << 20;
+
+/// Length of the length prefix that comes before every Message Frame, except Handshake
+const LENGTH_PREFIX_LENGTH: usize = 4;
+
+/// The "pstr" of BitTorrent protocol V1 sent in the Handshake Message
+const PSTR: &[u8] = b"BitTorrent protocol";
+
+#[derive(Error, Debug)]
+pub enum PeerCodecError {
+    #[error("Io - error : {0:?}")]
+    Io(#[from] io::Error),
+
+    #[error("InvalidHandshake - pstrlen : {pstrlen:?}, pstr : {pstr:?}")]
+    InvalidHandshake { pstrlen: u8, pstr: Vec<u8> },
+
+    #[error("FrameTooLarge - length : {length:?}, max : {max:?}")]
+    FrameTooLarge { length: u32, max: u32 },
+
+    #[error("MalformedMessage - id : {id:?}, length : {length:?}")]
+    MalformedMessage { id: u8, length: u32 },
+}
+
+/// Decodes the bytes received from the peer into [Message] and encodes [Message] into bytes to be
+/// sent to the peer
+///
+/// The very first message on a connection is always a Handshake, every message after that is a
+/// Message Frame of <length prefix><message ID><payload>. Peers often send multiple messages in
+/// the same TCP segment (Handshake, Bitfield and Have all at once) or a single message split
+/// across many TCP segments (Piece), so the decoder only ever looks at the length prefix and
+/// waits until the entire frame has arrived before it decodes anything.
 #[derive(Debug)]
-pub struct PeerMessageCodec;
+pub struct PeerMessageCodec {
+    /// Whether the Handshake Message of the peer has already been decoded or not, until then
+    /// the bytes are read as a Handshake Message rather than as a length prefixed Message Frame
+    handshake_received: bool,
+
+    /// Maximum value of the length prefix that is accepted
+    max_frame_length: u32,
+}
+
+impl PeerMessageCodec {
+    /// Creates a codec for a fresh connection, where the first message expected from the peer is
+    /// a Handshake
+    pub fn new() -> Self {
+        Self {
+            handshake_received: false,
+            max_frame_length: MAX_FRAME_LENGTH,
+        }
+    }
+
+    /// Whether the peer's Handshake has been decoded or not
+    pub fn handshake_received(&self) -> bool {
+        self.handshake_received
+    }
+
+    /// Decodes the Handshake Message, which unlike other messages has no length prefix
+    ///
+    /// Handshake : <pstrlen><pstr><reserved><info_hash><peer_id>
+    fn decode_handshake(&mut self, src: &mut BytesMut) -> Result<Option<Message>, PeerCodecError> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+
+        let pstrlen = src[0];
+        let handshake_length = 1 + pstrlen as usize + 8 + 20 + 20;
+        if src.len() < handshake_length {
+            // Wait for the rest of the Handshake, but fail early if the bytes we already have
+            // can't be a Handshake of BitTorrent protocol V1
+            let received_pstr = &src[1..src.len().min(1 + pstrlen as usize)];
+            if pstrlen as usize != PSTR.len() || !PSTR.starts_with(received_pstr) {
+                return Err(PeerCodecError::InvalidHandshake {
+                    pstrlen,
+                    pstr: received_pstr.to_vec(),
+                });
+            }
+            src.reserve(handshake_length - src.len());
+            return Ok(None);
+        }
+
+        let pstr = &src[1..(1 + pstrlen as usize)];
+        if pstr != PSTR {
+            return Err(PeerCodecError::InvalidHandshake {
+                pstrlen,
+                pstr: pstr.to_vec(),
+            });
+        }
+
+        self.handshake_received = true;
+        Ok(Some(Message::Handshake(Handshake::from(src))))
+    }
+
+    /// Decodes a single length prefixed Message Frame, it's only called after the entire frame
+    /// has arrived, "frame" includes the length prefix as well
+    fn decode_frame(frame: &mut BytesMut, length: u32) -> Result<Message, PeerCodecError> {
+        if length == 0 {
+            return Ok(Message::KeepAlive);
+        }
+
+        let id = frame[LENGTH_PREFIX_LENGTH];
+
+        // Every message with a known ID has either a fixed length or a minimum length, anything
+        // else is a malformed message
+        let is_valid_length = match id {
+            CHOKE_ID | UNCHOKE_ID | INTERESTED_ID | NOT_INTERESTED_ID => length == 1,
+            HAVE_ID => length == 5,
+            BITFIELD_ID => length >= 1,
+            REQUEST_ID | CANCEL_ID => length == 13,
+            PIECE_ID => length >= 9,
+            PORT_ID => length == 3,
+            EXTENDED_ID => length >= 2,
+            _ => true,
+        };
+        if !is_valid_length {
+            return Err(PeerCodecError::MalformedMessage {
+                id,
+                length,
+            });
+        }
+
+        let message = match id {
+            CHOKE_ID => Message::Choke,
+            UNCHOKE_ID => Message::Unchoke,
+            INTERESTED_ID => Message::Interested,
+            NOT_INTERESTED_ID => Message::NotInterested,
+            HAVE_ID => Message::Have(Have::from_bytes(frame)),
+            BITFIELD_ID => Message::Bitfield(Bitfield::from_bytes(frame)),
+            REQUEST_ID => Message::Request(Request::from_bytes(frame)),
+            PIECE_ID => Message::Piece(Block::from_bytes(frame)),
+            CANCEL_ID => Message::Cancel(Cancel::from_bytes(frame)),
+            PORT_ID => Message::Port(Port::from_bytes(frame)),
+            EXTENDED_ID => Message::Extended(Extended::from_bytes(frame)),
+            _ => Message::Unknown(id),
+        };
+        Ok(message)
+    }
+}
+
+impl Default for PeerMessageCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Decoder for PeerMessageCodec {
     type Item = Message;
-    type Error = std::io::Error;
-    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        return if src.len() == 0 {
-            Ok(None)
-        } else if Message::is_handshake_message(src) {
-            Ok(Some(Message::Handshake(Handshake::from(src))))
-        } else if Message::is_keep_alive_message(src) {
-            let _ = src.split_to(4);
-            Ok(Some(Message::KeepAlive))
-        } else if Message::is_choke_message(src) {
-            let _ = src.split_to(5);
-            Ok(Some(Message::Choke))
-        } else if Message::is_unchoke_message(src) {
-            let _ = src.split_to(5);
-            Ok(Some(Message::Unchoke))
-        } else if Message::is_interested_message(src) {
-            let _ = src.split_to(5);
-            Ok(Some(Message::Interested))
-        } else if Message::is_not_interested_message(src) {
-            let _ = src.split_to(5);
-            Ok(Some(Message::NotInterested))
-        } else if Message::is_have_message(src) {
-            Ok(Some(Message::Have(Have::from_bytes(src))))
-        } else if Message::is_bitfield_message(src) {
-            Ok(Some(Message::Bitfield(Bitfield::from_bytes(src))))
-        } else if Message::is_request_message(src) {
-            Ok(Some(Message::Request(Request::from_bytes(src))))
-        } else if Message::is_piece_message(src) {
-            Ok(Some(Message::Piece(Block::from_bytes(src))))
-        } else if Message::is_cancel_message(src) {
-            Ok(Some(Message::Cancel(Cancel::from_bytes(src))))
-        } else if Message::is_port_message(src) {
-            Ok(Some(Message::Port(Port::from_bytes(src))))
-        } else {
+    type Error = PeerCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if !self.handshake_received {
+            return self.decode_handshake(src);
+        }
+
+        // Wait until we've got the entire length prefix
+        if src.len() < LENGTH_PREFIX_LENGTH {
             return Ok(None);
-        };
+        }
+
+        let mut length_prefix_bytes = &src[0..LENGTH_PREFIX_LENGTH];
+        let length = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes)?;
+        if length > self.max_frame_length {
+            return Err(PeerCodecError::FrameTooLarge {
+                length,
+                max: self.max_frame_length,
+            });
+        }
+
+        // Wait until the entire Message Frame has arrived, reserving the space for it so that a
+        // big Piece Message doesn't reallocate the buffer on every TCP segment
+        let frame_length = LENGTH_PREFIX_LENGTH + length as usize;
+        if src.len() < frame_length {
+            src.reserve(frame_length - src.len());
+            return Ok(None);
+        }
+
+        let mut frame = src.split_to(frame_length);
+        Self::decode_frame(&mut frame, length).map(Some)
     }
 }
 
 impl Encoder<Vec<Message>> for PeerMessageCodec {
-    type Error = std::io::Error;
-    fn encode(&mut self, item: Vec<Message>, dst: &mut bytes::BytesMut) -> Result<(), Self::Error> {
+    type Error = PeerCodecError;
+    fn encode(&mut self, item: Vec<Message>, dst: &mut BytesMut) -> Result<(), Self::Error> {
         for ref message in item {
             dst.put(message.to_bytes());
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Handshake of BitTorrent protocol V1 with the given info hash and peer ID, with the Extension
+    /// Protocol bit set
+    fn handshake(info_hash: [u8; 20], peer_id: [u8; 20]) -> BytesMut {
+        let mut v = BytesMut::new();
+        v.put_u8(PSTR.len() as u8);
+        v.put_slice(PSTR);
+        v.put_slice(&[0, 0, 0, 0, 0, 0x10, 0, 0]);
+        v.put_slice(&info_hash);
+        v.put_slice(&peer_id);
+        v
+    }
+
+    /// A codec that has already decoded the Handshake of the peer
+    fn handshaked_codec() -> PeerMessageCodec {
+        let mut codec = PeerMessageCodec::new();
+        codec.decode(&mut handshake([1; 20], [2; 20])).unwrap();
+        codec
+    }
+
+    #[test]
+    fn handshake_followed_by_bitfield() {
+        let mut src = handshake([1; 20], [2; 20]);
+        src.put_u32(2);
+        src.put_u8(BITFIELD_ID);
+        src.put_u8(0b1011_0001);
+
+        let mut codec = PeerMessageCodec::new();
+        let expected = Handshake::from(&mut handshake([1; 20], [2; 20]));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Message::Handshake(expected)));
+        assert!(codec.handshake_received());
+
+        let bitfield = Bitfield {
+            have: vec![0, 2, 3, 7],
+            not_have: vec![1, 4, 5, 6],
+        };
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Message::Bitfield(bitfield)));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn handshake_of_another_protocol_is_refused() {
+        let mut src = BytesMut::from(&b"\x13BitTorrent protokol"[..]);
+        assert!(matches!(
+            PeerMessageCodec::new().decode(&mut src),
+            Err(PeerCodecError::InvalidHandshake { .. })
+        ));
+    }
+
+    #[test]
+    fn frame_split_across_decodes() {
+        let mut bytes = BytesMut::new();
+        bytes.put_u32(13);
+        bytes.put_u8(REQUEST_ID);
+        bytes.put_u32(3);
+        bytes.put_u32(16384);
+        bytes.put_u32(16384);
+        let request = Message::Request(Request::from_bytes(&mut bytes.clone()));
+
+        let mut codec = handshaked_codec();
+        let mut src = bytes.split_to(6);
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        assert_eq!(src.len(), 6);
+
+        src.extend_from_slice(&bytes);
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(request));
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn oversized_length_prefix_is_refused() {
+        let mut src = BytesMut::new();
+        src.put_u32(MAX_FRAME_LENGTH + 1);
+        src.put_u8(PIECE_ID);
+        assert!(matches!(
+            handshaked_codec().decode(&mut src),
+            Err(PeerCodecError::FrameTooLarge {
+                length,
+                max: MAX_FRAME_LENGTH,
+            }) if length == MAX_FRAME_LENGTH + 1
+        ));
+    }
+
+    #[test]
+    fn wrong_length_of_have_and_request_is_refused() {
+        let mut src = BytesMut::new();
+        src.put_u32(4);
+        src.put_u8(HAVE_ID);
+        src.put_slice(&[0, 0, 1]);
+        assert!(matches!(
+            handshaked_codec().decode(&mut src),
+            Err(PeerCodecError::MalformedMessage {
+                id: HAVE_ID,
+                length: 4
+            })
+        ));
+
+        let mut src = BytesMut::new();
+        src.put_u32(17);
+        src.put_u8(REQUEST_ID);
+        src.put_slice(&[0; 16]);
+        assert!(matches!(
+            handshaked_codec().decode(&mut src),
+            Err(PeerCodecError::MalformedMessage {
+                id: REQUEST_ID,
+                length: 17
+            })
+        ));
+    }
+
+    #[test]
+    fn unknown_id_and_keep_alive() {
+        let mut src = BytesMut::new();
+        src.put_u32(0);
+        src.put_u32(3);
+        src.put_slice(&[42, 0, 0]);
+
+        let mut codec = handshaked_codec();
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Message::KeepAlive));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Message::Unknown(42)));
+        assert!(src.is_empty());
+    }
+}
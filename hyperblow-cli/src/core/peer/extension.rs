@@ -0,0 +1,87 @@
+/*
+ * NOTE : This file contains the trait every extension of the Extension Protocol implements, along
+ * with the extensions a torrent supports
+ *
+ * https://www.bittorrent.org/beps/bep_0010.html
+ *
+ * Each extension is given an extended message ID of our own, which the peers send its messages
+ * with, told to them in the "m" dictionary of our extended handshake. The peers do the same, so
+ * the messages of an extension are sent to a peer with the ID the peer asked for.
+ */
+
+use super::{
+    messages::{ExtendedHandshake, Message, EXTENDED_HANDSHAKE_ID},
+    Peer,
+};
+use crate::core::state::State;
+use async_trait::async_trait;
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+/// Client name and version sent as "v" in our extended handshake
+pub const CLIENT_VERSION: &str = concat!("Hyperblow ", env!("CARGO_PKG_VERSION"));
+
+/// Time between the ticks of the extensions, see [Extension::on_tick], ut_pex sends at most one
+/// message a minute
+pub const TICK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// An extension of the Extension Protocol, such as ut_metadata or ut_pex
+///
+/// The messages handed over to an extension are only the ones sent with its own extended message
+/// ID, the messages it gives back are sent to the peer as they are, see [Peer::extension_message]
+#[async_trait]
+pub trait Extension: Debug + Send + Sync {
+    /// Name of the extension in the "m" dictionary of the extended handshake, e.g "ut_metadata"
+    fn name(&self) -> &'static str;
+
+    /// Adds the keys the extension needs into our extended handshake, e.g "metadata_size"
+    fn extend_handshake(&self, _state: &State, _handshake: &mut ExtendedHandshake) {}
+
+    /// Called once the extended handshake of the peer is received, only if the peer supports the
+    /// extension too, gives back the messages to be sent to the peer
+    async fn on_handshake(&self, _peer: &Peer, _handshake: &ExtendedHandshake) -> Vec<Message> {
+        Vec::new()
+    }
+
+    /// Called with the payload of every message of the extension sent by the peer, gives back
+    /// the messages to be sent to the peer
+    async fn on_message(&self, peer: &Peer, payload: &[u8]) -> Vec<Message>;
+
+    /// Called every [TICK_INTERVAL] while the session with the peer runs, only if the peer
+    /// supports the extension too, gives back the messages to be sent to the peer
+    async fn on_tick(&self, _peer: &Peer) -> Vec<Message> {
+        Vec::new()
+    }
+
+    /// Called once the connection with the peer has been closed
+    async fn on_disconnect(&self, _peer: &Peer) {}
+}
+
+/// The extensions supported by a torrent, each one's extended message ID is its position in the
+/// list, starting at 1 as 0 is of the extended handshake
+#[derive(Debug, Default)]
+pub struct Extensions {
+    extensions: Vec<Arc<dyn Extension>>,
+}
+
+impl Extensions {
+    /// Adds the given extension, the peers connected after this get to know about it
+    pub fn register(&mut self, extension: Arc<dyn Extension>) {
+        self.extensions.push(extension);
+    }
+
+    /// Gives the extension the peers send the messages of with the given extended message ID
+    pub fn get(&self, id: u8) -> Option<&Arc<dyn Extension>> {
+        if id == EXTENDED_HANDSHAKE_ID {
+            return None;
+        }
+        self.extensions.get(id as usize - 1)
+    }
+
+    /// Gives every extension along with its extended message ID
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &Arc<dyn Extension>)> {
+        self.extensions
+            .iter()
+            .enumerate()
+            .map(|(index, extension)| (index as u8 + 1, extension))
+    }
+}
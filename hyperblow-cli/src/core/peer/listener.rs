@@ -0,0 +1,96 @@
+use super::{codec::PeerMessageCodec, messages::Message, Peer, PeerSource, HANDSHAKE_TIMEOUT};
+use crate::core::{net, state::EngineState};
+use futures::StreamExt;
+use std::{io, net::SocketAddr, ops::RangeInclusive, sync::Arc};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tokio_util::codec::Framed;
+
+/// Ports tried one after another, until the listener gets bound to one of them
+const LISTEN_PORTS: RangeInclusive<u16> = 6881..=6999;
+
+/// No of peers a single torrent can be connected with at a time, unless it's changed through
+/// [State::set_max_connections](crate::core::state::State::set_max_connections)
+pub const DEFAULT_MAX_CONNECTIONS: usize = 50;
+
+/// No of peers all the torrents of the engine together can be connected with at a time, unless
+/// it's changed through [EngineState::set_max_connections]
+pub const DEFAULT_ENGINE_MAX_CONNECTIONS: usize = 200;
+
+/// Accepts the TCP connections made by the peers to us, for all the torrents of the engine
+///
+/// The peer that connects to us sends its Handshake first, so the Handshake is read right here
+/// and the connection is handed over to the torrent whose info hash is in it. Connections for
+/// the torrents we aren't running, or beyond the connection limits, are simply dropped.
+#[derive(Debug)]
+pub struct PeerListener {
+    /// State shared by all the torrents of the engine, where the torrents are looked up
+    engine_state: Arc<EngineState>,
+
+    /// The TCP socket bound to the port the peers connect to
+    listener: TcpListener,
+}
+
+impl PeerListener {
+    /// Binds the listener to the first free port out of [LISTEN_PORTS], on both IPv6 and IPv4
+    /// when the host supports IPv6, and lets the engine know about the port
+    pub async fn bind(engine_state: Arc<EngineState>) -> io::Result<Self> {
+        let mut last_error = io::Error::new(io::ErrorKind::AddrInUse, "no free port to listen on");
+        for port in LISTEN_PORTS {
+            match net::bind_tcp(port) {
+                Ok(listener) => {
+                    engine_state.set_listen_port(port);
+                    return Ok(Self {
+                        engine_state,
+                        listener,
+                    });
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Accepts the connections forever, each connection is handled in its own task
+    pub async fn run(&self) {
+        loop {
+            if let Ok((tcp_stream, socket_adr)) = self.listener.accept().await {
+                let engine_state = self.engine_state.clone();
+                // The IPv4 peers are seen through their IPv4-mapped address on a dual stack
+                // listener
+                let socket_adr = net::canonical(socket_adr);
+                tokio::spawn(async move {
+                    Self::handle_connection(engine_state, tcp_stream, socket_adr).await;
+                });
+            }
+        }
+    }
+
+    /// Reads the Handshake of the peer and runs the session of the peer under the torrent it's
+    /// meant for
+    async fn handle_connection(engine_state: Arc<EngineState>, tcp_stream: TcpStream, socket_adr: SocketAddr) {
+        let mut stream = Framed::new(tcp_stream, PeerMessageCodec::new());
+        let handshake = match timeout(HANDSHAKE_TIMEOUT, stream.next()).await {
+            Ok(Some(Ok(Message::Handshake(handshake)))) => handshake,
+            _ => return,
+        };
+
+        let state = match engine_state.torrent(handshake.info_hash()).await {
+            Some(state) => state,
+            None => return,
+        };
+
+        if !engine_state.can_connect(&state).await {
+            return;
+        }
+
+        let mut peer = Peer::new(socket_adr, state.clone());
+        peer.set_source(PeerSource::Incoming);
+        let peer = Arc::new(peer);
+        if state.add_peer(peer.clone()).await {
+            peer.run_inbound(stream, handshake).await;
+        }
+    }
+}
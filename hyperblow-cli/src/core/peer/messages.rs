@@ -23,9 +23,43 @@
 use crate::core::state::State;
 use byteorder::{BigEndian, ReadBytesExt};
 use bytes::{BufMut, BytesMut};
-use std::sync::Arc;
+use serde_bencode::value::Value;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv6Addr},
+    sync::Arc,
+};
 //use serde_derive::{Deserialize, Serialize};
 
+/// Message ID of each of the Message Frame that comes after the length prefix
+///
+/// Structure of every Message Frame, except Handshake and KeepAlive :
+///
+/// <length prefix><message ID><payload>
+pub const CHOKE_ID: u8 = 0;
+pub const UNCHOKE_ID: u8 = 1;
+pub const INTERESTED_ID: u8 = 2;
+pub const NOT_INTERESTED_ID: u8 = 3;
+pub const HAVE_ID: u8 = 4;
+pub const BITFIELD_ID: u8 = 5;
+pub const REQUEST_ID: u8 = 6;
+pub const PIECE_ID: u8 = 7;
+pub const CANCEL_ID: u8 = 8;
+pub const PORT_ID: u8 = 9;
+pub const EXTENDED_ID: u8 = 20;
+
+/// Extended message ID of the extended handshake, the IDs of the extensions are the ones the peer
+/// asks for in its extended handshake
+pub const EXTENDED_HANDSHAKE_ID: u8 = 0;
+
+/// Bit of the reserved bytes of the Handshake, that's set by the peers supporting the Extension
+/// Protocol, i.e the 20th bit from the right
+const EXTENSION_PROTOCOL_BIT: (usize, u8) = (5, 0x10);
+
+/// Bit of the reserved bytes of the Handshake, that's set by the peers running a DHT node, i.e the
+/// last bit, see [Port]
+const DHT_BIT: (usize, u8) = (7, 0x01);
+
 /// Messages sent to the peer and recieved form the peer takes
 /// the following forms
 #[derive(PartialEq, Debug, Clone)]
@@ -37,12 +71,15 @@ pub enum Message {
     Interested,
     NotInterested,
     Bitfield(Bitfield),
-    //    EXTENDED(Extended)
+    Extended(Extended),
     Have(Have),
     Request(Request),
     Piece(Block),
     Cancel(Cancel),
     Port(Port),
+    /// A well framed message whose message ID we don't understand, the entire frame is consumed
+    /// and only its ID is kept, so that the stream can move on to the next Message Frame
+    Unknown(u8),
 }
 
 impl Message {
@@ -64,242 +101,78 @@ impl Message {
 
             Message::Choke => {
                 buf.put_u32(1);
-                buf.put_u8(1);
+                buf.put_u8(CHOKE_ID);
             }
 
             Message::Unchoke => {
                 buf.put_u32(1);
-                buf.put_u8(2);
+                buf.put_u8(UNCHOKE_ID);
             }
 
             Message::Interested => {
                 buf.put_u32(1);
-                buf.put_u8(3);
+                buf.put_u8(INTERESTED_ID);
             }
 
             Message::NotInterested => {
                 buf.put_u32(1);
-                buf.put_u8(4);
+                buf.put_u8(NOT_INTERESTED_ID);
             }
-            // TODO : Do it for all messages
 
-            //Message::Have(ref have) => {}
-            _ => {}
-        }
-        return buf;
-    }
-
-    /// Checks in the given src buffer if the first Message Frame is A Handshake Message Frame
-    pub fn is_handshake_message(src: &BytesMut) -> bool {
-        let _expected_pstr = String::from("BitTorrent protocol");
-        // First check if there is enough bytes to be a Handshake Message Frame
-        if src.len() >= 68 {
-            let pstr_len = src[0];
-            // TODO : Check pstr
-            // let pstr: Vec<u8> = [1..=19].iter().collect();
-            //let pstr = String::from_utf8(pstr).unwrap();
-            if pstr_len == 19 {
-                return true;
-                //   return pstr == expected_pstr;
-                // TODO : Check for info hash
-                // TODO : Check the peer id
-                // Then only validate the Handshake Message Frame
+            Message::Have(ref have) => {
+                buf.put_u32(5);
+                buf.put_u8(HAVE_ID);
+                buf.put_u32(have.piece_index);
             }
-        }
-        return false;
-    }
-
-    /// Checks in the given src buffer if the first Message Frame is A KeepAlive Message Frame
-    ///
-    /// Structure :
-    ///
-    /// <0000>
-    ///
-    /// Which just contains the length prefix and does not have any id and
-    /// payload
-    ///
-    /// Steps :
-    /// Firstly we check if there are enough bytes to contain the length_prefix field i.e <0000>
-    /// field of the KeepAlive Message, if it does then and we need to deserialize those
-    /// length_prefix to u32, if the value is 0_u32 then it's a KeepAlive Message.
-    ///
-    /// Because, no other Message Frame has a message that starts with bytes 0000, so there is no chance
-    /// for this collide with other messages as far as i know
-    ///
-    pub fn is_keep_alive_message(src: &BytesMut) -> bool {
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-            return length_prefix == 0;
-        }
-        return false;
-    }
 
-    /// Checks in the given src buffer if the first Message Frame is A Choke Message Frame
-    pub fn is_choke_message(src: &BytesMut) -> bool {
-        // First check if there is enough bytes to get the length prefix
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-            // Once we get the length prefix, we check if its value is equal to 1, and has enough
-            // bytes for the entire Choke Message Frame and the message id is 0
-            if length_prefix == 0 && src.len() >= 5 {
-                let message_id = src[4];
-                return message_id == 0;
+            Message::Bitfield(ref bitfield) => {
+                let bits = bitfield.to_bits();
+                buf.put_u32(1 + bits.len() as u32);
+                buf.put_u8(BITFIELD_ID);
+                buf.put_slice(&bits);
             }
-        }
-        return false;
-    }
-
-    /// Checks in the given src buffer if the first Message Frame is A Unchoke Message Frame
-    pub fn is_unchoke_message(src: &BytesMut) -> bool {
-        // First check if there is enough bytes to get the length prefix
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-            // Once we get the length prefix, we check if its value is equal to 1, and has enough
-            // bytes for the entire Unchoke Message Frame and the message id is 1
-            if length_prefix == 0 && src.len() >= 5 {
-                let message_id = src[4];
-                return message_id == 1;
-            }
-        }
-        return false;
-    }
 
-    /// Checks in the given src buffer if the first Message Frame is A Interested Message Frame
-    pub fn is_interested_message(src: &BytesMut) -> bool {
-        // First check if there is enough bytes to get the length prefix
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-            // Once we get the length prefix, we check if its value is equal to 1, and has enough
-            // bytes for the entire Unchoke Message Frame and the message id is 2
-            if length_prefix == 0 && src.len() >= 5 {
-                let message_id = src[4];
-                return message_id == 2;
+            Message::Request(ref request) => {
+                buf.put_u32(13);
+                buf.put_u8(REQUEST_ID);
+                buf.put_u32(request.index);
+                buf.put_u32(request.begin);
+                buf.put_u32(request.length);
             }
-        }
-        return false;
-    }
 
-    /// Checks in the given src buffer if the first Message Frame is A NotInterested Message Frame
-    pub fn is_not_interested_message(src: &BytesMut) -> bool {
-        // First check if there is enough bytes to get the length prefix
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-            // Once we get the length prefix, we check if its value is equal to 1, and has enough
-            // bytes for the entire Unchoke Message Frame and the message id is 3
-            if length_prefix == 0 && src.len() >= 5 {
-                let message_id = src[4];
-                return message_id == 3;
+            Message::Piece(ref block) => {
+                buf.put_u32(9 + block.raw_block.len() as u32);
+                buf.put_u8(PIECE_ID);
+                buf.put_u32(block.piece_index);
+                buf.put_u32(block.byte_index);
+                buf.put_slice(&block.raw_block);
             }
-        }
-        return false;
-    }
-
-    /// Checks in the given src buffer if the first Message Frame is A Have Message Frame
-    pub fn is_have_message(src: &BytesMut) -> bool {
-        // First check if there is enough bytes to get the length prefix
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-
-            // Once we get the length prefix, then we simply check if the length_prefix matches
-            // or not and the source is atleast (4 + length_prefix) i.e (4 + 5) bytes or not
-            if length_prefix == 5 && src.len() >= 9 {
-                let message_id = src[4];
-                return message_id == 4;
-            }
-        }
-        return false;
-    }
-
-    /// Checks in the given src buffer if the first Message Frame is A Bitfield Message Frame
-    pub fn is_bitfield_message(src: &BytesMut) -> bool {
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-
-            // Expected length of the entire Bitfield Message Frame
-            let expected_frame_length = 4 + length_prefix;
-
-            // Second, check if there is enough data in the buffer
-            // as mentioned in length_prefix
-            if src.len() as u32 >= expected_frame_length {
-                let message_id = src[4];
-                return message_id == 5;
-            }
-        }
-        return false;
-    }
 
-    /// Checks in the given src buffer if the first Message Frame is A Request Message Frame
-    pub fn is_request_message(src: &BytesMut) -> bool {
-        // First check if there is enough bytes to get the length prefix
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-
-            // Once we get the length prefix, then we simply check if the source is atleast
-            // (4 + lengthprefix) bytes or not
-            if length_prefix == 13 && src.len() >= 17 {
-                let message_id = src[4];
-                return message_id == 6;
+            Message::Cancel(ref cancel) => {
+                buf.put_u32(13);
+                buf.put_u8(CANCEL_ID);
+                buf.put_u32(cancel.index);
+                buf.put_u32(cancel.begin);
+                buf.put_u32(cancel.length);
             }
-        }
-        return false;
-    }
 
-    /// Checks in the given src buffer if the first Message Frame is A Piece Message Frame
-    pub fn is_piece_message(src: &BytesMut) -> bool {
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-
-            // Expected length of the entire message
-            let expected_length = 4 + length_prefix;
-            // Second, check if there is enough data in the buffer
-            // as mentioned in length_prefix
-            if src.len() as u32 >= expected_length {
-                let message_id = src[4];
-                return message_id == 7;
+            Message::Port(ref port) => {
+                buf.put_u32(3);
+                buf.put_u8(PORT_ID);
+                buf.put_u16(port.listen_port);
             }
-        }
-        return false;
-    }
 
-    /// Checks in the given src buffer if the first Message Frame is A Cancel Message Frame
-    pub fn is_cancel_message(src: &BytesMut) -> bool {
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-
-            // Once we get the length prefix, then we simply check if the source is atleast
-            // (4 + lengthprefix) bytes or not
-            if length_prefix == 13 && src.len() >= 17 {
-                let message_id = src[4];
-                return message_id == 8;
+            Message::Extended(ref extended) => {
+                buf.put_u32(2 + extended.payload.len() as u32);
+                buf.put_u8(EXTENDED_ID);
+                buf.put_u8(extended.id);
+                buf.put_slice(&extended.payload);
             }
-        }
-        return false;
-    }
 
-    /// Checks in the given src buffer if the first Message Frame is A Port Message Frame
-    pub fn is_port_message(src: &BytesMut) -> bool {
-        if src.len() >= 4 {
-            let mut length_prefix_bytes = &src[0..=3];
-            let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
-
-            // Once we get the length prefix, then we simply check if the source is atleast
-            // (4 + lengthprefix) bytes or not
-            if length_prefix == 3 && src.len() >= 7 {
-                let message_id = src[4];
-                return message_id == 9;
-            }
+            // We never send a message we don't understand
+            Message::Unknown(_) => {}
         }
-        return false;
+        buf
     }
 }
 
@@ -313,6 +186,22 @@ pub struct Bitfield {
 }
 
 impl Bitfield {
+    /// Creates a Bitfield of the given pieces, where the piece at the zero based index is in
+    /// "have" if it's true
+    pub fn new(pieces_have: &[bool]) -> Self {
+        let (have, not_have) = (0..pieces_have.len()).partition(|index| pieces_have[*index]);
+        Self {
+            have,
+            not_have,
+        }
+    }
+
+    /// Creates a Bitfield instance from the Bitfield Message Frame bytes.
+    /// It consumes the frame bytes and produces an instance of Bitfield
+    ///
+    /// Each byte of the payload holds 8 pieces, where the high bit of the first byte is
+    /// the piece at index 0. The spare bits at the end are set to zero by the peer, so they end up
+    /// in "not_have"
     pub fn from_bytes(src: &mut BytesMut) -> Self {
         let mut have = Vec::new();
         let mut not_have = Vec::new();
@@ -323,66 +212,200 @@ impl Bitfield {
         let bitfield_frame_length = (length_prefix + 4) as usize;
 
         let bitfield_bytes = &src[5..bitfield_frame_length];
-        let bitfield = BytesMut::from(bitfield_bytes);
 
-        for (index, bit) in bitfield.iter().enumerate() {
-            match bit {
-                0 => not_have.push(index),
-                1 => have.push(index),
-                _ => {}
+        for (byte_index, byte) in bitfield_bytes.iter().enumerate() {
+            for bit_index in 0..8 {
+                let index = byte_index * 8 + bit_index;
+                if byte & (0b1000_0000 >> bit_index) != 0 {
+                    have.push(index);
+                } else {
+                    not_have.push(index);
+                }
             }
         }
-        src.split_to(bitfield_frame_length as usize);
+        src.split_to(bitfield_frame_length);
         Self {
             have,
             not_have,
         }
     }
+
+    /// Packs the pieces in "have" back into the bytes of the Bitfield payload, the total no of bits
+    /// is taken from "have" and "not_have" together and rounded up to the next byte
+    pub fn to_bits(&self) -> Vec<u8> {
+        let total_pieces = self.have.len() + self.not_have.len();
+        let mut bits = vec![0_u8; total_pieces.div_ceil(8)];
+        for index in &self.have {
+            bits[index / 8] |= 0b1000_0000 >> (index % 8);
+        }
+        bits
+    }
+}
+
+/// Extended Message :
+///
+/// A message type for those who implement the Extension Protocol
+/// from - http://www.bittorrent.org/beps/bep_0010.html
+///
+/// Structure :
+///
+/// <len=0002+X><id=20><extended message ID><payload>
+///
+/// extended message ID : 0 for the extended handshake, any other ID is of an extension, as told by
+///                       the receiver in the "m" dictionary of its extended handshake
+/// payload : X bytes, a bencoded dictionary, which some extensions follow with raw data
+///
+/// It has a total frame length of (4 + 2 + X) bytes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extended {
+    /// ID of the extended message, see [EXTENDED_HANDSHAKE_ID]
+    pub id: u8,
+
+    /// Bytes that come after the extended message ID
+    pub payload: Vec<u8>,
+}
+
+impl Extended {
+    /// Creates an Extended with the given extended message ID and payload
+    pub fn new(id: u8, payload: Vec<u8>) -> Self {
+        Self {
+            id,
+            payload,
+        }
+    }
+
+    /// Creates an Extended instance from the Extended Message Frame bytes.
+    /// It consumes the frame bytes and produces an instance of Extended
+    ///
+    /// src - It must be an entire Extended Message Frame of atleast (4 + 2) bytes, which is
+    /// made sure by the [PeerMessageCodec](super::codec::PeerMessageCodec)
+    pub fn from_bytes(src: &mut BytesMut) -> Self {
+        let mut length_prefix_bytes = &src[0..=3];
+        let length_prefix = ReadBytesExt::read_u32::<BigEndian>(&mut length_prefix_bytes).unwrap();
+
+        let id = src[5];
+        let payload = src[6..(4 + length_prefix as usize)].to_vec();
+        src.split_to(4 + length_prefix as usize);
+        Self {
+            id,
+            payload,
+        }
+    }
+}
+
+/// Payload of the extended handshake, a bencoded dictionary sent by both the peers right after
+/// the Handshake, if both of them have set the extension protocol bit in the reserved bytes
+///
+/// Keys of the dictionary, every one of them is optional :
+/// m               Dictionary of the extensions supported, mapping the name of each extension to
+///                 the extended message ID the sender wants to receive its messages with
+/// v               Name and version of the client of the sender
+/// p               TCP port the sender is listening on
+/// reqq            No of outstanding block requests the sender queues up without dropping any
+/// yourip          IP address of the receiver as seen by the sender, in the compact form
+/// ipv6            IPv6 address of the sender, in the compact form
+/// metadata_size   Size of the "info" field in bytes, if the sender has it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtendedHandshake {
+    /// Extended message ID of each extension, by its name
+    pub m: HashMap<String, u8>,
+
+    /// Name and version of the client
+    pub v: Option<String>,
+
+    /// TCP port the sender is listening on
+    pub p: Option<u16>,
+
+    /// No of outstanding block requests the sender queues up
+    pub reqq: Option<usize>,
+
+    /// IP address of the receiver as seen by the sender
+    pub yourip: Option<IpAddr>,
+
+    /// IPv6 address of the sender
+    pub ipv6: Option<Ipv6Addr>,
+
+    /// Size of the "info" field in bytes
+    pub metadata_size: Option<usize>,
+}
+
+impl ExtendedHandshake {
+    /// Parses the bencoded payload of the extended handshake, the extensions with an ID of 0 are
+    /// the ones the sender has disabled, so they're left out, and so are the keys that aren't of
+    /// the type they're supposed to be
+    pub fn from(v: &[u8]) -> Option<Self> {
+        let Value::Dict(dict) = serde_bencode::from_bytes::<Value>(v).ok()? else {
+            return None;
+        };
+
+        let mut m = HashMap::new();
+        if let Some(Value::Dict(extensions)) = dict.get(b"m".as_ref()) {
+            for (name, id) in extensions {
+                if let Value::Int(id) = id {
+                    if let Ok(id @ 1..) = u8::try_from(*id) {
+                        m.insert(String::from_utf8_lossy(name).to_string(), id);
+                    }
+                }
+            }
+        }
+        let int = |key: &[u8]| match dict.get(key) {
+            Some(Value::Int(int)) => Some(*int),
+            _ => None,
+        };
+        let bytes = |key: &[u8]| match dict.get(key) {
+            Some(Value::Bytes(bytes)) => Some(bytes.as_slice()),
+            _ => None,
+        };
+
+        Some(Self {
+            m,
+            v: bytes(b"v").map(|v| String::from_utf8_lossy(v).to_string()),
+            p: int(b"p").and_then(|p| u16::try_from(p).ok()),
+            reqq: int(b"reqq").and_then(|reqq| usize::try_from(reqq).ok()),
+            yourip: bytes(b"yourip").and_then(|ip| match ip.len() {
+                4 => Some(IpAddr::from(<[u8; 4]>::try_from(ip).ok()?)),
+                16 => Some(IpAddr::from(<[u8; 16]>::try_from(ip).ok()?)),
+                _ => None,
+            }),
+            ipv6: bytes(b"ipv6").and_then(|ip| Some(Ipv6Addr::from(<[u8; 16]>::try_from(ip).ok()?))),
+            metadata_size: int(b"metadata_size").and_then(|size| usize::try_from(size).ok()),
+        })
+    }
+
+    /// Bencodes the extended handshake into its payload
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let m = self
+            .m
+            .iter()
+            .map(|(name, id)| (name.clone().into_bytes(), Value::Int(*id as i64)))
+            .collect();
+        let mut dict = HashMap::from([(b"m".to_vec(), Value::Dict(m))]);
+        if let Some(ref v) = self.v {
+            dict.insert(b"v".to_vec(), Value::Bytes(v.clone().into_bytes()));
+        }
+        if let Some(p) = self.p {
+            dict.insert(b"p".to_vec(), Value::Int(p as i64));
+        }
+        if let Some(reqq) = self.reqq {
+            dict.insert(b"reqq".to_vec(), Value::Int(reqq as i64));
+        }
+        if let Some(yourip) = self.yourip {
+            let yourip = match yourip {
+                IpAddr::V4(ip) => ip.octets().to_vec(),
+                IpAddr::V6(ip) => ip.octets().to_vec(),
+            };
+            dict.insert(b"yourip".to_vec(), Value::Bytes(yourip));
+        }
+        if let Some(ipv6) = self.ipv6 {
+            dict.insert(b"ipv6".to_vec(), Value::Bytes(ipv6.octets().to_vec()));
+        }
+        if let Some(metadata_size) = self.metadata_size {
+            dict.insert(b"metadata_size".to_vec(), Value::Int(metadata_size as i64));
+        }
+        serde_bencode::to_bytes(&Value::Dict(dict)).unwrap_or_default()
+    }
 }
 
-///// Extended Message :
-/////
-///// A message type for those who implement the Extension Protocol
-///// from - http://www.bittorrent.org/beps/bep_0010.html
-/////
-///// Structure :
-///// length_prefix => u32 (No of bytes for the entire message) : Offset [0,3]
-///// message_id => u8 (value = 20) (id of the message) : Offset : [4]
-///// extension_message_id => u8 (id of the message) : Offset : [5]
-//#[derive(Debug, Clone, PartialEq)]
-//pub struct Extended {
-//    length_prefix: u32,
-//    message_id: u8,
-//    extension_message_id: u8,
-//    payload: ExtendedPayload,
-//}
-//
-//impl Extended {
-//    pub fn from(bytes: &mut BytesMut) -> Self {
-//        let length_prefix: u32 = ReadBytesExt::read_u32::<BigEndian>(&mut &bytes[0..=3]).unwrap();
-//        let message_id: u8 = bytes[4];
-//        let extension_message_id: u8 = bytes[5];
-//        bytes.split_to(6);
-//
-//        let payload_length = (length_prefix - 2) as usize;
-//        let payload = bytes.split_to(payload_length);
-//        let payload: ExtendedPayload = serde_bencode::de::from_bytes(&payload).unwrap();
-//
-//        Extended {
-//            length_prefix,
-//            message_id,
-//            extension_message_id,
-//            payload,
-//        }
-//    }
-//}
-//
-//#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
-//struct ExtendedPayload {
-//    pub v: Option<String>,
-//}
-//
-//
 /// HAVE Message :
 /// TODO : Add info about HAVE message
 #[derive(Debug, PartialEq, Clone)]
@@ -391,10 +414,22 @@ pub struct Have {
 }
 
 impl Have {
+    /// Creates a Have for the piece at the given index, sent to the peers once we've verified it
+    pub fn new(piece_index: u32) -> Self {
+        Self {
+            piece_index,
+        }
+    }
+
+    /// Creates a Have instance from the Have Message Frame bytes.
+    /// It consumes the frame bytes and produces an instance of Have
+    ///
+    /// src - It must be an entire Have Message Frame of (4 + 5) bytes, which is
+    /// made sure by the [PeerMessageCodec](super::codec::PeerMessageCodec)
     pub fn from_bytes(src: &mut BytesMut) -> Self {
         let mut piece_index_bytes = &src[5..=8];
-        // TODO : Make sure unpwrap here is safe
         let piece_index = ReadBytesExt::read_u32::<BigEndian>(&mut piece_index_bytes).unwrap();
+        src.split_to(9);
         Self {
             piece_index,
         }
@@ -446,13 +481,18 @@ pub struct Handshake {
 
 impl Handshake {
     /// Creates a instance of Handshake in order to send it to a peer.
+    ///
+    /// The DHT bit isn't set for a private torrent, as it doesn't use the DHT
     pub fn new(state: Arc<State>) -> Self {
         let pstrlen: u8 = 19;
         let pstr = b"BitTorrent protocol".to_vec();
-        let reserved = vec![0; 8];
+        let mut reserved = vec![0; 8];
+        reserved[EXTENSION_PROTOCOL_BIT.0] |= EXTENSION_PROTOCOL_BIT.1;
+        if !state.meta_info.isPrivate() {
+            reserved[DHT_BIT.0] |= DHT_BIT.1;
+        }
         let info_hash = state.info_hash.clone();
-        let peer_id = b"-HYBLOW-110011001100".map(|v| v).into_iter().collect();
-        // TODO : Create a Peer Id field in state field and use that field here
+        let peer_id = state.peer_id.to_vec();
         Self {
             pstrlen,
             pstr,
@@ -462,15 +502,18 @@ impl Handshake {
         }
     }
 
-    /// Deserializes given bytes into Handshake instance
+    /// Deserializes given bytes into Handshake instance, it consumes the bytes of the
+    /// Handshake Message Frame
+    ///
+    /// v - It must contain atleast (49 + pstrlen) bytes, which is made sure by the
+    /// [PeerMessageCodec](super::codec::PeerMessageCodec)
     pub fn from(v: &mut BytesMut) -> Self {
         let pstrlen = v.split_to(1).to_vec()[0];
-        let pstr = v.split_to(19).to_vec();
+        let pstr = v.split_to(pstrlen as usize).to_vec();
         let reserved = v.split_to(8).to_vec();
         let info_hash = v.split_to(20).to_vec();
         let peer_id = v.split_to(20).to_vec();
 
-        v.split_to(68);
         Self {
             pstrlen,
             pstr,
@@ -479,6 +522,33 @@ impl Handshake {
             peer_id,
         }
     }
+
+    /// Info hash of the torrent the peer wants to exchange pieces of
+    pub fn info_hash(&self) -> &[u8] {
+        &self.info_hash
+    }
+
+    /// Peer ID of the peer that sent this Handshake
+    pub fn peer_id(&self) -> &[u8] {
+        &self.peer_id
+    }
+
+    /// The eight reserved bytes, used to signal the extensions the peer supports
+    pub fn reserved(&self) -> &[u8] {
+        &self.reserved
+    }
+
+    /// Whether the peer supports the Extension Protocol, see [Extended]
+    pub fn supports_extensions(&self) -> bool {
+        self.reserved
+            .get(EXTENSION_PROTOCOL_BIT.0)
+            .is_some_and(|byte| byte & EXTENSION_PROTOCOL_BIT.1 != 0)
+    }
+
+    /// Whether the peer runs a DHT node, in which case it's sent a [Port] message
+    pub fn supports_dht(&self) -> bool {
+        self.reserved.get(DHT_BIT.0).is_some_and(|byte| byte & DHT_BIT.1 != 0)
+    }
 }
 
 /// Unchoke message
@@ -544,19 +614,28 @@ impl Interested {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Request {
     /// Zero based piece index
-    index: u32,
+    pub index: u32,
     /// Zero based byte offset within the piecec
-    begin: u32,
+    pub begin: u32,
     /// Requested length
-    length: u32,
+    pub length: u32,
 }
 
 impl Request {
+    /// Creates a Request for the block of given length at the given byte offset of the piece
+    pub fn new(index: u32, begin: u32, length: u32) -> Self {
+        Self {
+            index,
+            begin,
+            length,
+        }
+    }
+
     /// Creates a Request instance from the Request Message Frame bytes.
     /// It consumes the frame bytes and produces an instance of Request
     ///
-    /// src - It must be validated by using is_request_message() method
-    /// before calling this from_bytes() method
+    /// src - It must be an entire Request Message Frame of (4 + 13) bytes, which is
+    /// made sure by the [PeerMessageCodec](super::codec::PeerMessageCodec)
     pub fn from_bytes(src: &mut BytesMut) -> Self {
         let mut index_bytes = &src[5..=8];
         let mut begin_bytes = &src[9..=12];
@@ -604,6 +683,15 @@ pub struct Block {
 }
 
 impl Block {
+    /// Creates a Block of the piece at the given index, starting at the given byte offset
+    pub fn new(piece_index: u32, byte_index: u32, raw_block: BytesMut) -> Self {
+        Self {
+            piece_index,
+            byte_index,
+            raw_block,
+        }
+    }
+
     //  /// Creates a "Block" instance from the raw "Piece" message sent by the client
     //  /// NOTE : It removes the data it read from the buffer
     pub fn from_bytes(src: &mut BytesMut) -> Self {
@@ -616,7 +704,7 @@ impl Block {
         let byte_index: u32 = ReadBytesExt::read_u32::<BigEndian>(&mut bytes_index_bytes).unwrap();
 
         let block_length = (length_prefix - 9) as usize;
-        let block_bytes = &src[13..block_length];
+        let block_bytes = &src[13..(13 + block_length)];
         let raw_block = BytesMut::from(block_bytes);
 
         let total_frame_length = 4 + length_prefix;
@@ -646,19 +734,30 @@ impl Block {
 /// It has a total frame length of 4 + 13 = 17 bytes
 #[derive(PartialEq, Debug, Clone)]
 pub struct Cancel {
-    index: u32,
-    begin: u32,
-    length: u32,
+    /// Zero based piece index
+    pub index: u32,
+    /// Zero based byte offset within the piece
+    pub begin: u32,
+    /// Requested length
+    pub length: u32,
 }
 
 impl Cancel {
+    /// Creates a Cancel for the given Request, that was sent to the peer earlier
+    pub fn new(request: &Request) -> Self {
+        Self {
+            index: request.index,
+            begin: request.begin,
+            length: request.length,
+        }
+    }
+
     /// Creates a Cancel instance from the bytes of Cancel Message Frame
-    /// src - It must be atleast 17 bytes long and must be validated by
-    /// Cancel::is_cancel_message() function before actually creating it
+    /// src - It must be an entire Cancel Message Frame of (4 + 13) bytes, which is
+    /// made sure by the [PeerMessageCodec](super::codec::PeerMessageCodec)
     ///
     /// It will consume the Cancel Message Frame bytes and create the Cancel instance
     pub fn from_bytes(src: &mut BytesMut) -> Self {
-        // TODO : Add some sort of error handling by using is_cancel_message() method
         let mut index_bytes = &src[5..=8];
         let mut begin_bytes = &src[9..=12];
         let mut length_bytes = &src[13..=16];
@@ -695,14 +794,25 @@ pub struct Port {
 }
 
 impl Port {
+    /// Creates a Port message, telling the peer about the port our DHT node is listening on
+    pub fn new(listen_port: u16) -> Self {
+        Self {
+            listen_port,
+        }
+    }
+
+    /// Port the DHT node of the peer is listening on
+    pub fn listen_port(&self) -> u16 {
+        self.listen_port
+    }
+
     /// Creates a Port instance from the bytes of Port Message Frame
-    /// src - It must be atleast 7 bytes long and must be validated by
-    /// Port::is_port_message() function before actually creating it.
+    /// src - It must be an entire Port Message Frame of (4 + 3) bytes, which is
+    /// made sure by the [PeerMessageCodec](super::codec::PeerMessageCodec)
     ///
     /// It will consume the Port Message Frame bytes and create the Port instance
     pub fn from_bytes(src: &mut BytesMut) -> Self {
-        // TODO : Add some sort of error handling by using is_por_message() method
-        let mut listen_port_bytes = &src[4..=6];
+        let mut listen_port_bytes = &src[5..=6];
 
         let listen_port = ReadBytesExt::read_u16::<BigEndian>(&mut listen_port_bytes).unwrap();
         src.split_to(7);
@@ -0,0 +1,578 @@
+/*
+ * NOTE : This file contains all the structs and methods related to the Extension for Peers to
+ * Send Metadata Files, i.e "ut_metadata"
+ *
+ * https://www.bittorrent.org/beps/bep_0009.html
+ *
+ * It lets a torrent added through a magnet URI fetch the "info" field from the peers, the "info"
+ * field is split into pieces of 16 KiB, which are requested one by one from the peers that have
+ * told us the size of the "info" field in their extended handshake.
+ */
+
+use super::{
+    extension::Extension,
+    messages::{ExtendedHandshake, Message},
+    Peer,
+};
+use crate::core::state::State;
+use async_trait::async_trait;
+use hyperblow::parser::torrent_parser::FileMeta;
+use serde_bencode::value::Value;
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// Name of the extension in the "m" dictionary of the extended handshake
+pub const UT_METADATA: &str = "ut_metadata";
+
+/// Size of each piece of the "info" field, except the last one which may be smaller
+pub const METADATA_PIECE_SIZE: usize = 16 * 1024;
+
+/// Largest "info" field we're willing to fetch, a peer telling us about anything bigger is ignored
+pub const MAX_METADATA_SIZE: usize = 8 * 1024 * 1024;
+
+/// No of pieces of the "info" field requested from a single peer at a time
+const MAX_METADATA_REQUESTS: usize = 4;
+
+/// No of requests that may be rejected or time out in a row, before the size of the "info" field
+/// being fetched is given up on and another size told by the peers is tried
+const MAX_METADATA_MISSES: usize = 8;
+
+/// A piece requested from a peer that hasn't been sent for this long can be requested from some
+/// other peer
+const METADATA_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Message types of ut_metadata, given by the "msg_type" key
+const REQUEST_TYPE: i64 = 0;
+const DATA_TYPE: i64 = 1;
+const REJECT_TYPE: i64 = 2;
+
+/// Messages of ut_metadata, sent as the payload of an Extended message
+///
+/// Structure :
+///
+/// <bencoded dictionary><data>
+///
+/// msg_type : 0 for Request, 1 for Data and 2 for Reject
+/// piece : Zero based index of the piece of the "info" field
+/// total_size : Size of the entire "info" field, only in Data
+/// data : Bytes of the piece, only in Data, they come right after the dictionary
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataMessage {
+    /// Asks for the piece at the given index
+    Request(u32),
+
+    /// Sends the piece at the given index
+    Data { piece: u32, total_size: usize, data: Vec<u8> },
+
+    /// The sender doesn't have the piece at the given index, or won't send it
+    Reject(u32),
+}
+
+impl MetadataMessage {
+    /// Parses the payload of an Extended message of ut_metadata, "None" if it's malformed or of a
+    /// message type we don't understand
+    pub fn from(v: &[u8]) -> Option<Self> {
+        // The data of a Data message comes right after the dictionary, so the dictionary is parsed
+        // on its own
+        let dict_end = FileMeta::skipValue(v, 0)?;
+        let Value::Dict(dict) = serde_bencode::from_bytes::<Value>(&v[..dict_end]).ok()? else {
+            return None;
+        };
+
+        let int = |key: &[u8]| match dict.get(key) {
+            Some(Value::Int(int)) => Some(*int),
+            _ => None,
+        };
+        let piece = u32::try_from(int(b"piece")?).ok()?;
+        match int(b"msg_type")? {
+            REQUEST_TYPE => Some(Self::Request(piece)),
+            DATA_TYPE => Some(Self::Data {
+                piece,
+                total_size: usize::try_from(int(b"total_size")?).ok()?,
+                data: v[dict_end..].to_vec(),
+            }),
+            REJECT_TYPE => Some(Self::Reject(piece)),
+            _ => None,
+        }
+    }
+
+    /// Bencodes the message into the payload of an Extended message
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (msg_type, piece) = match *self {
+            Self::Request(piece) => (REQUEST_TYPE, piece),
+            Self::Data {
+                piece, ..
+            } => (DATA_TYPE, piece),
+            Self::Reject(piece) => (REJECT_TYPE, piece),
+        };
+        let mut dict = HashMap::from([
+            (b"msg_type".to_vec(), Value::Int(msg_type)),
+            (b"piece".to_vec(), Value::Int(piece as i64)),
+        ]);
+        if let Self::Data {
+            total_size, ..
+        } = *self
+        {
+            dict.insert(b"total_size".to_vec(), Value::Int(total_size as i64));
+        }
+
+        let mut bytes = serde_bencode::to_bytes(&Value::Dict(dict)).unwrap_or_default();
+        if let Self::Data {
+            ref data, ..
+        } = *self
+        {
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    /// Gives the Data message of the piece at the given index of the "info" field, or a Reject
+    /// message if there's no such piece
+    pub fn piece_of(raw_info: &[u8], piece: u32) -> Self {
+        let start = piece as usize * METADATA_PIECE_SIZE;
+        if start >= raw_info.len() {
+            return Self::Reject(piece);
+        }
+        let end = raw_info.len().min(start + METADATA_PIECE_SIZE);
+        Self::Data {
+            piece,
+            total_size: raw_info.len(),
+            data: raw_info[start..end].to_vec(),
+        }
+    }
+}
+
+/// The "info" field being fetched from the peers, piece by piece, shared by all the peers of the
+/// torrent
+///
+/// The peers may not agree on the size of the "info" field, so the peers are grouped by the size
+/// they told us about, and the pieces are fetched from the peers of a single size at a time. The
+/// size told by the most peers is tried first, and another one is tried once the requests of that
+/// size keep getting rejected or timing out.
+#[derive(Debug, Default)]
+pub struct MetadataFetch {
+    /// Size of the "info" field being fetched, it's 0 until some peer has told us about it
+    size: usize,
+
+    /// The pieces received so far, by their index, along with the peer that sent each of them
+    pieces: Vec<Option<(Vec<u8>, SocketAddr)>>,
+
+    /// The pieces requested from the peers, which they are yet to send, by their index
+    requested: HashMap<u32, (SocketAddr, Instant)>,
+
+    /// The pieces each peer has rejected, they aren't requested from that peer again
+    rejected: HashSet<(u32, SocketAddr)>,
+
+    /// Size of the "info" field each peer has told us about in its extended handshake, the pieces
+    /// are only requested from the peers that told us the size being fetched
+    sizes: HashMap<SocketAddr, usize>,
+
+    /// No of requests that have been rejected or have timed out since a piece was last received
+    misses: usize,
+
+    /// The sizes given up on after [MAX_METADATA_MISSES], they're tried again only once there's no
+    /// other size left to try
+    abandoned: HashSet<usize>,
+
+    /// The peers that sent the pieces of an "info" field that turned out to be not of this torrent,
+    /// nothing is requested from them again
+    excluded: HashSet<SocketAddr>,
+}
+
+impl MetadataFetch {
+    /// Lets the fetch know the size of the "info" field the peer with the given socket address has
+    /// told us about, gives back whether the pieces can be requested from that peer, i.e whether
+    /// the size is the one being fetched
+    pub fn set_size(&mut self, socket_adr: SocketAddr, size: usize) -> bool {
+        if size == 0 || size > MAX_METADATA_SIZE || self.excluded.contains(&socket_adr) {
+            return false;
+        }
+        self.sizes.insert(socket_adr, size);
+        if self.size == 0 {
+            self.select_size();
+        }
+        self.size == size
+    }
+
+    /// Picks the pieces that are to be requested from the peer with the given socket address, the
+    /// ones neither received nor requested from any other peer nor rejected by this peer, upto
+    /// [MAX_METADATA_REQUESTS] outstanding with the peer at a time
+    ///
+    /// The requests that have timed out are dropped first, so that their pieces can be picked, and
+    /// another size is picked when the one being fetched has missed too many times or none of the
+    /// peers has it anymore
+    pub fn pick_requests(&mut self, socket_adr: SocketAddr) -> Vec<u32> {
+        let now = Instant::now();
+        let requested = self.requested.len();
+        self.requested
+            .retain(|_, (_, requested_at)| now.duration_since(*requested_at) < METADATA_REQUEST_TIMEOUT);
+        self.misses += requested - self.requested.len();
+
+        if self.misses >= MAX_METADATA_MISSES {
+            self.abandoned.insert(self.size);
+            self.select_size();
+        } else if !self.sizes.is_empty() && !self.sizes.values().any(|size| *size == self.size) {
+            self.select_size();
+        }
+
+        if self.size == 0 || self.sizes.get(&socket_adr) != Some(&self.size) {
+            return Vec::new();
+        }
+        let outstanding = self.requested.values().filter(|(peer, _)| *peer == socket_adr).count();
+        let picked: Vec<u32> = (0..self.pieces.len() as u32)
+            .filter(|piece| {
+                self.pieces[*piece as usize].is_none()
+                    && !self.requested.contains_key(piece)
+                    && !self.rejected.contains(&(*piece, socket_adr))
+            })
+            .take(MAX_METADATA_REQUESTS.saturating_sub(outstanding))
+            .collect();
+        for piece in &picked {
+            self.requested.insert(*piece, (socket_adr, now));
+        }
+        picked
+    }
+
+    /// Keeps the piece sent by the peer with the given socket address, and once all the pieces
+    /// have been received, gives back the entire "info" field
+    ///
+    /// The piece is dropped if it's not of the size it's supposed to be
+    pub fn on_piece(&mut self, socket_adr: SocketAddr, piece: u32, total_size: usize, data: Vec<u8>) -> Option<Vec<u8>> {
+        // A piece received twice is dropped, so that the "info" field is only given back once
+        if total_size != self.size || self.pieces.get(piece as usize)?.is_some() {
+            return None;
+        }
+        if self.requested.get(&piece).is_some_and(|(peer, _)| *peer == socket_adr) {
+            self.requested.remove(&piece);
+        }
+
+        let expected_size = METADATA_PIECE_SIZE.min(self.size - piece as usize * METADATA_PIECE_SIZE);
+        if data.len() != expected_size {
+            return None;
+        }
+        self.pieces[piece as usize] = Some((data, socket_adr));
+        self.misses = 0;
+
+        if self.pieces.iter().any(|piece| piece.is_none()) {
+            return None;
+        }
+        Some(self.pieces.iter().flatten().flat_map(|(data, _)| data).copied().collect())
+    }
+
+    /// Lets the piece at the given index be requested from the other peers, as the peer with the
+    /// given socket address won't be sending it
+    pub fn on_reject(&mut self, socket_adr: SocketAddr, piece: u32) {
+        self.rejected.insert((piece, socket_adr));
+        if self.requested.get(&piece).is_some_and(|(peer, _)| *peer == socket_adr) {
+            self.requested.remove(&piece);
+            self.misses += 1;
+        }
+    }
+
+    /// Lets all the pieces requested from the peer with the given socket address be requested
+    /// from the other peers, it's called once the connection with the peer is closed
+    pub fn release_requests(&mut self, socket_adr: SocketAddr) {
+        self.requested.retain(|_, (peer, _)| *peer != socket_adr);
+        self.rejected.retain(|(_, peer)| *peer != socket_adr);
+        self.sizes.remove(&socket_adr);
+    }
+
+    /// Throws away everything received so far, it's called when the "info" field that was put
+    /// together turns out to be not of this torrent, so that it's fetched all over again from the
+    /// peers other than the ones that sent its pieces
+    ///
+    /// The sizes the other peers have told us about are kept, so that the connected peers can be
+    /// asked again without another extended handshake
+    pub fn reset(&mut self) {
+        let senders = self.pieces.iter().flatten().map(|(_, peer)| *peer);
+        self.excluded.extend(senders);
+        let excluded = &self.excluded;
+        self.sizes.retain(|peer, _| !excluded.contains(peer));
+        self.size = 0;
+        self.select_size();
+    }
+
+    /// Picks the size told by the most peers, leaving out the sizes that have been given up on
+    /// while there are others left, everything received for the previous size is thrown away
+    ///
+    /// The peers get asked for the pieces they've rejected again, as the size is tried afresh
+    fn select_size(&mut self) {
+        let mut peers_count: HashMap<usize, usize> = HashMap::new();
+        for size in self.sizes.values() {
+            *peers_count.entry(*size).or_default() += 1;
+        }
+        if peers_count.keys().all(|size| self.abandoned.contains(size)) {
+            self.abandoned.clear();
+        }
+
+        // The smaller size wins a tie, so that the same size is picked every time
+        let size = peers_count
+            .into_iter()
+            .filter(|(size, _)| !self.abandoned.contains(size))
+            .max_by_key(|(size, peers_count)| (*peers_count, Reverse(*size)))
+            .map_or(0, |(size, _)| size);
+        self.misses = 0;
+        self.rejected.clear();
+        if size != self.size || self.pieces.is_empty() {
+            self.size = size;
+            self.pieces = vec![None; size.div_ceil(METADATA_PIECE_SIZE)];
+            self.requested.clear();
+        }
+    }
+}
+
+/// The ut_metadata extension of a torrent, it sends the pieces of the "info" field to the peers
+/// asking for them, and fetches the "info" field from the peers while the torrent doesn't have it
+#[derive(Debug, Default)]
+pub struct UtMetadata {
+    /// The pieces of the "info" field received from the peers so far
+    fetch: Mutex<MetadataFetch>,
+}
+
+#[async_trait]
+impl Extension for UtMetadata {
+    fn name(&self) -> &'static str {
+        UT_METADATA
+    }
+
+    fn extend_handshake(&self, state: &State, handshake: &mut ExtendedHandshake) {
+        if state.has_metadata() {
+            handshake.metadata_size = Some(state.meta_info.raw_info.len());
+        }
+    }
+
+    /// Starts fetching the "info" field from the peer, if we don't have it and the peer does
+    async fn on_handshake(&self, peer: &Peer, handshake: &ExtendedHandshake) -> Vec<Message> {
+        match handshake.metadata_size {
+            Some(size) if !peer.state.has_metadata() && self.fetch.lock().await.set_size(peer.socket_adr, size) => {
+                self.request_pieces(peer).await
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Sends the pieces of the "info" field the peer asks for, and puts together the "info" field
+    /// from the pieces sent by the peer, the messages that can't be parsed are ignored
+    async fn on_message(&self, peer: &Peer, payload: &[u8]) -> Vec<Message> {
+        match MetadataMessage::from(payload) {
+            Some(MetadataMessage::Request(piece)) => {
+                let response = match peer.state.has_metadata() {
+                    true => MetadataMessage::piece_of(&peer.state.meta_info.raw_info, piece),
+                    false => MetadataMessage::Reject(piece),
+                };
+                peer.extension_message(UT_METADATA, response.to_bytes()).await.into_iter().collect()
+            }
+
+            Some(MetadataMessage::Data {
+                piece,
+                total_size,
+                data,
+            }) => {
+                if peer.state.has_metadata() {
+                    return Vec::new();
+                }
+                let raw_info = self.fetch.lock().await.on_piece(peer.socket_adr, piece, total_size, data);
+                match raw_info {
+                    Some(raw_info) => {
+                        if peer.state.set_metadata(raw_info).await {
+                            return Vec::new();
+                        }
+                        // The "info" field isn't of this torrent, some peer sent us a wrong piece,
+                        // so it's fetched all over again from the peers that didn't send any of
+                        // its pieces, they're asked on their tick
+                        self.fetch.lock().await.reset();
+                        Vec::new()
+                    }
+                    None => self.request_pieces(peer).await,
+                }
+            }
+
+            // The rejected piece is left for the other peers, this peer is asked for the rest
+            Some(MetadataMessage::Reject(piece)) => {
+                if peer.state.has_metadata() {
+                    return Vec::new();
+                }
+                self.fetch.lock().await.on_reject(peer.socket_adr, piece);
+                self.request_pieces(peer).await
+            }
+
+            None => Vec::new(),
+        }
+    }
+
+    /// Requests the pieces nobody else has been asked for, while we don't have the "info" field, so
+    /// that the pieces rejected, timed out or thrown away after a failed fetch are asked for again
+    async fn on_tick(&self, peer: &Peer) -> Vec<Message> {
+        match peer.state.has_metadata() {
+            true => Vec::new(),
+            false => self.request_pieces(peer).await,
+        }
+    }
+
+    async fn on_disconnect(&self, peer: &Peer) {
+        self.fetch.lock().await.release_requests(peer.socket_adr);
+    }
+}
+
+impl UtMetadata {
+    /// Requests the pieces of the "info" field from the peer, that no other peer has been asked for
+    async fn request_pieces(&self, peer: &Peer) -> Vec<Message> {
+        let pieces = self.fetch.lock().await.pick_requests(peer.socket_adr);
+        let mut requests = Vec::new();
+        for piece in pieces {
+            requests.extend(
+                peer.extension_message(UT_METADATA, MetadataMessage::Request(piece).to_bytes())
+                    .await,
+            );
+        }
+        requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Size of an "info" field of 3 pieces, the last of which is short
+    const SIZE: usize = 2 * METADATA_PIECE_SIZE + 100;
+
+    fn adr(port: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, 1], port))
+    }
+
+    fn piece(piece: u32) -> Vec<u8> {
+        let length = METADATA_PIECE_SIZE.min(SIZE - piece as usize * METADATA_PIECE_SIZE);
+        vec![piece as u8; length]
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            MetadataMessage::Request(3),
+            MetadataMessage::Reject(7),
+            MetadataMessage::Data {
+                piece: 1,
+                total_size: SIZE,
+                data: b"d4:infoe".to_vec(),
+            },
+        ];
+        for message in messages {
+            assert_eq!(MetadataMessage::from(&message.to_bytes()), Some(message));
+        }
+        assert_eq!(MetadataMessage::from(b"d8:msg_typei9e5:piecei0ee"), None);
+        assert_eq!(MetadataMessage::from(b"d5:piecei0ee"), None);
+    }
+
+    #[test]
+    fn info_is_split_into_pieces_with_a_short_last_piece() {
+        let raw_info = vec![1; SIZE];
+        assert!(matches!(
+            MetadataMessage::piece_of(&raw_info, 2),
+            MetadataMessage::Data { data, total_size: SIZE, .. } if data.len() == 100
+        ));
+        assert_eq!(MetadataMessage::piece_of(&raw_info, 3), MetadataMessage::Reject(3));
+
+        let mut fetch = MetadataFetch::default();
+        assert!(fetch.set_size(adr(1), SIZE));
+        assert_eq!(fetch.pick_requests(adr(1)), vec![0, 1, 2]);
+
+        // A last piece of the size of a full piece is dropped
+        assert_eq!(fetch.on_piece(adr(1), 2, SIZE, vec![2; METADATA_PIECE_SIZE]), None);
+        assert!(fetch.pieces[2].is_none());
+        assert_eq!(fetch.on_piece(adr(1), 2, SIZE, piece(2)), None);
+        assert_eq!(fetch.on_piece(adr(1), 0, SIZE, piece(0)), None);
+        let raw_info = fetch.on_piece(adr(1), 1, SIZE, piece(1)).unwrap();
+        assert_eq!(raw_info, [piece(0), piece(1), piece(2)].concat());
+    }
+
+    #[test]
+    fn duplicate_piece_is_dropped() {
+        let mut fetch = MetadataFetch::default();
+        fetch.set_size(adr(1), SIZE);
+        fetch.set_size(adr(2), SIZE);
+        fetch.pick_requests(adr(1));
+
+        assert_eq!(fetch.on_piece(adr(1), 0, SIZE, piece(0)), None);
+        assert_eq!(fetch.on_piece(adr(2), 0, SIZE, vec![9; METADATA_PIECE_SIZE]), None);
+        assert_eq!(fetch.on_piece(adr(1), 1, SIZE, piece(1)), None);
+        assert!(fetch.on_piece(adr(1), 2, SIZE, piece(2)).is_some());
+
+        // The "info" field is given back only once
+        assert_eq!(fetch.on_piece(adr(2), 2, SIZE, piece(2)), None);
+    }
+
+    #[test]
+    fn rejected_piece_is_requested_from_another_peer() {
+        let mut fetch = MetadataFetch::default();
+        fetch.set_size(adr(1), SIZE);
+        fetch.set_size(adr(2), SIZE);
+        assert_eq!(fetch.pick_requests(adr(1)), vec![0, 1, 2]);
+        assert!(fetch.pick_requests(adr(2)).is_empty());
+
+        fetch.on_reject(adr(1), 1);
+        assert!(fetch.pick_requests(adr(1)).is_empty());
+        assert_eq!(fetch.pick_requests(adr(2)), vec![1]);
+    }
+
+    #[test]
+    fn timed_out_piece_is_requested_again() {
+        let mut fetch = MetadataFetch::default();
+        fetch.set_size(adr(1), SIZE);
+        fetch.set_size(adr(2), SIZE);
+        assert_eq!(fetch.pick_requests(adr(1)), vec![0, 1, 2]);
+
+        fetch.requested.get_mut(&0).unwrap().1 = Instant::now() - METADATA_REQUEST_TIMEOUT;
+        assert_eq!(fetch.pick_requests(adr(2)), vec![0]);
+        assert_eq!(fetch.misses, 1);
+    }
+
+    #[test]
+    fn size_of_the_most_peers_is_fetched_and_another_is_tried_after_misses() {
+        let mut fetch = MetadataFetch::default();
+        assert!(fetch.set_size(adr(1), SIZE + 1));
+        assert!(!fetch.set_size(adr(2), SIZE));
+        assert!(!fetch.set_size(adr(3), MAX_METADATA_SIZE + 1));
+        assert!(fetch.pick_requests(adr(2)).is_empty());
+
+        // The size the most peers told us about wins once the fetch starts over
+        fetch.set_size(adr(3), SIZE);
+        fetch.select_size();
+        assert_eq!(fetch.size, SIZE);
+
+        // The pieces of that size are rejected by one peer and never sent by the other
+        for piece in fetch.pick_requests(adr(2)) {
+            fetch.on_reject(adr(2), piece);
+        }
+        while fetch.size == SIZE {
+            fetch.pick_requests(adr(3));
+            for (_, requested_at) in fetch.requested.values_mut() {
+                *requested_at = Instant::now() - METADATA_REQUEST_TIMEOUT;
+            }
+        }
+        assert_eq!(fetch.pick_requests(adr(1)), vec![0, 1, 2]);
+        assert_eq!(fetch.size, SIZE + 1);
+        assert!(fetch.abandoned.contains(&SIZE));
+    }
+
+    #[test]
+    fn reset_leaves_out_the_peers_that_sent_pieces() {
+        let mut fetch = MetadataFetch::default();
+        fetch.set_size(adr(1), SIZE);
+        fetch.set_size(adr(2), SIZE);
+        assert_eq!(fetch.pick_requests(adr(1)), vec![0, 1, 2]);
+        fetch.on_piece(adr(1), 0, SIZE, piece(0));
+        fetch.on_piece(adr(1), 1, SIZE, piece(1));
+        assert!(fetch.on_piece(adr(1), 2, SIZE, piece(2)).is_some());
+
+        fetch.reset();
+        assert!(fetch.pieces.iter().all(|piece| piece.is_none()));
+        assert!(fetch.pick_requests(adr(1)).is_empty());
+        assert!(!fetch.set_size(adr(1), SIZE));
+        assert_eq!(fetch.pick_requests(adr(2)), vec![0, 1, 2]);
+    }
+}
@@ -1,23 +1,84 @@
+pub mod choker;
 mod codec;
+pub mod extension;
+pub mod listener;
 mod messages;
+pub mod metadata;
+pub mod pex;
+pub mod picker;
 mod piece;
 
-use super::state::State;
+use super::{net, state::State};
 use crate::ArcMutex;
-use messages::Message;
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use bytes::BytesMut;
+use extension::{CLIENT_VERSION, TICK_INTERVAL};
+use futures::{SinkExt, StreamExt};
+use messages::{Bitfield, Block, Cancel, Extended, ExtendedHandshake, Handshake, Have, Message, Port, Request, EXTENDED_HANDSHAKE_ID};
+use picker::{BlockResult, PiecePicker};
+use piece::Piece;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    future::ready,
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
+};
 use tokio::{
     net::TcpStream,
-    sync::Mutex,
-    time::{sleep, timeout},
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        Mutex,
+    },
+    time::{interval_at, sleep, sleep_until, timeout, Instant},
 };
 
 use codec::PeerMessageCodec;
 use tokio_util::codec::Framed;
 
+/// A TCP connection with the peer, that speaks in [Message]
+type PeerStream = Framed<TcpStream, PeerMessageCodec>;
+
+/// A 16 seconds of connection timeout time is kept to make a reliable TCP Connection
+/// with the peer.
+///
+/// A higher connection timeout time could be added too, but even if we get a TCP
+/// Connection keeping the timeout higher, the connection won't be reliable enough
+/// to exchange pieces with the peer.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(16);
+
+/// No of times we try to make a TCP Connection with the peer before giving up on it
+const MAX_CONNECTION_ATTEMPTS: u32 = 3;
+
+/// Time to stay idle after a failed connection attempt, it gets multiplied by the no of
+/// attempts made so far
+const CONNECTION_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time we wait for the peer to send its Handshake after we've sent ours
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// A KeepAlive is sent to the peer if we haven't sent anything for this long
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(120);
+
+/// The peer is dropped if it hasn't sent anything, not even a KeepAlive, for this long
+const INACTIVITY_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// No of block requests kept outstanding with each peer at a time, unless it's changed through
+/// [State::set_max_outstanding_requests]
+pub const DEFAULT_MAX_OUTSTANDING_REQUESTS: usize = 16;
+
+/// No of pieces that failed the hash check, that a peer can send blocks for before we drop it
+const MAX_HASH_FAILS: u32 = 3;
+
+/// Largest block a peer can request from us, the peer is dropped if it requests anything bigger
+const MAX_REQUEST_LENGTH: u32 = 128 * 1024;
+
+/// No of block requests of a peer we queue up, the requests beyond it are dropped
+const MAX_PEER_REQUESTS: usize = 250;
+
 /// PeerState denotes high level overview of the current state of
 /// relationship of this client with the remote Peer
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PeerState {
     /// Haven't even made a TCP Connection
     NotConnected,
@@ -38,9 +99,95 @@ pub enum PeerState {
 
     /// Sent a Handshake to the Peer
     SentHandshake,
-    // TODO: Add more states later on
-    //RequestingPiece,
-    // DOWNLOADING_PIECE,
+
+    /// Handshake was exchanged with the peer, but we aren't requesting any piece from the peer,
+    /// either because the peer is choking us or because the peer has nothing we're interested in
+    Handshaked,
+
+    /// We're interested in the peer and the peer has unchoked us, so we can request pieces
+    RequestingPiece,
+
+    /// The peer is sending us the blocks of the pieces we requested
+    Downloading,
+
+    /// The connection with the peer was closed, the reason is kept in [PeerInfo]
+    Disconnected,
+}
+
+impl Display for PeerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::NotConnected => write!(f, "Not Connected"),
+            Self::TryingToConnect => write!(f, "Trying To Connect"),
+            Self::ConnectionTimeoutIdle => write!(f, "Connection Timeout"),
+            Self::ConnectionErrorIdle => write!(f, "Connection Error"),
+            Self::Connected => write!(f, "Connected"),
+            Self::SentHandshake => write!(f, "Sent Handshake"),
+            Self::Handshaked => write!(f, "Handshaked"),
+            Self::RequestingPiece => write!(f, "Requesting Piece"),
+            Self::Downloading => write!(f, "Downloading"),
+            Self::Disconnected => write!(f, "Disconnected"),
+        }
+    }
+}
+
+/// The reason, the connection with the peer was closed
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisconnectReason {
+    /// Couldn't make a TCP Connection within [CONNECTION_TIMEOUT] in all the attempts
+    ConnectionTimeout,
+
+    /// Error occured on the TCP Connection
+    ConnectionError,
+
+    /// The peer didn't send its Handshake within [HANDSHAKE_TIMEOUT]
+    HandshakeTimeout,
+
+    /// The peer sent a Handshake for some other torrent
+    InfoHashMismatch,
+
+    /// The peer sent a Peer ID other than the one the tracker told us about
+    PeerIdMismatch,
+
+    /// The peer is this client itself, happens when the tracker gives us our own address
+    ConnectedToSelf,
+
+    /// The peer sent a message that couldn't be decoded
+    InvalidMessage(String),
+
+    /// The peer sent a message that isn't allowed at that point of the connection
+    ProtocolViolation(&'static str),
+
+    /// The peer didn't send anything for [INACTIVITY_TIMEOUT]
+    InactivityTimeout,
+
+    /// The peer closed the TCP Connection
+    ClosedByPeer,
+
+    /// The peer sent blocks for [MAX_HASH_FAILS] pieces that failed the hash check
+    TooManyHashFails,
+
+    /// We closed the connection ourselves
+    Requested,
+}
+
+impl Display for DisconnectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::ConnectionTimeout => write!(f, "Connection timeout"),
+            Self::ConnectionError => write!(f, "Connection error"),
+            Self::HandshakeTimeout => write!(f, "Handshake timeout"),
+            Self::InfoHashMismatch => write!(f, "Info hash mismatch"),
+            Self::PeerIdMismatch => write!(f, "Peer ID mismatch"),
+            Self::ConnectedToSelf => write!(f, "Connected to self"),
+            Self::InvalidMessage(ref error) => write!(f, "Invalid message ({error})"),
+            Self::ProtocolViolation(violation) => write!(f, "Protocol violation ({violation})"),
+            Self::InactivityTimeout => write!(f, "Inactivity timeout"),
+            Self::ClosedByPeer => write!(f, "Closed by peer"),
+            Self::TooManyHashFails => write!(f, "Too many hash fails"),
+            Self::Requested => write!(f, "Closed by us"),
+        }
+    }
 }
 
 /// It defines the type of Peer
@@ -59,30 +206,144 @@ pub enum PeerType {
     Unknown,
 }
 
+impl Display for PeerType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Leecher => write!(f, "Leecher"),
+            Self::Seeder => write!(f, "Seeder"),
+            Self::PartialSeeder => write!(f, "Partial Seeder"),
+            Self::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// Where we got to know about the peer from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PeerSource {
+    /// A tracker gave us the peer in an announce
+    Tracker,
+
+    /// The peer connected to us
+    Incoming,
+
+    /// Another peer told us about the peer through ut_pex
+    Pex,
+
+    /// A node of the DHT gave us the peer in a response to get_peers
+    Dht,
+
+    /// The peer announced itself on the local network
+    Lsd,
+}
+
+impl Display for PeerSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Tracker => write!(f, "Tracker"),
+            Self::Incoming => write!(f, "Incoming"),
+            Self::Pex => write!(f, "PEX"),
+            Self::Dht => write!(f, "DHT"),
+            Self::Lsd => write!(f, "LSD"),
+        }
+    }
+}
+
 /// PeerInfo holds crucial informations about the Peer, such as the pieces the peer has
 /// or doesn't have, the type of the peer
 #[derive(Debug, Clone)]
 pub struct PeerInfo {
-    /// Zero based index of the piece the peer has
-    pieces_have: Vec<u32>,
-
-    /// Zero based index of the piece the peer does not have
-    pieces_not_have: Vec<u32>,
+    /// Whether the peer has the piece at the zero based index or not, built from the Bitfield
+    /// and Have messages sent by the peer
+    pub pieces_have: Vec<bool>,
 
     /// Whether the peer is a Seeder or Leecher
-    peer_type: PeerType,
+    pub peer_type: PeerType,
 
     /// State of the peer
-    peer_state: PeerState,
+    pub peer_state: PeerState,
+
+    /// Peer ID sent by the peer in its Handshake
+    pub peer_id: Option<Vec<u8>>,
+
+    /// Whether the peer has set the Extension Protocol bit in its Handshake
+    pub supports_extensions: bool,
+
+    /// Whether the peer has set the DHT bit in its Handshake
+    pub supports_dht: bool,
+
+    /// Extended message ID the peer wants to receive the messages of each extension with, by the
+    /// name of the extension, as told in its extended handshake
+    pub extensions: HashMap<String, u8>,
+
+    /// Name and version of the client of the peer, as told in its extended handshake
+    pub client: Option<String>,
+
+    /// TCP port the peer is listening on, as told in its extended handshake, the port of a peer
+    /// that connected to us isn't the one it listens on
+    pub listen_port: Option<u16>,
+
+    /// No of outstanding block requests the peer queues up, as told in its extended handshake,
+    /// we never keep more requests than this outstanding with the peer
+    pub max_requests: Option<usize>,
+
+    /// Whether we are choking the peer, i.e we won't upload to the peer
+    pub am_choking: bool,
+
+    /// Whether we are interested in the pieces the peer has
+    pub am_interested: bool,
+
+    /// Whether the peer is choking us, i.e the peer won't upload to us
+    pub peer_choking: bool,
+
+    /// Whether the peer is interested in the pieces we have
+    pub peer_interested: bool,
+
+    /// The reason of the disconnection, once [PeerState::Disconnected] is reached
+    pub disconnect_reason: Option<DisconnectReason>,
+
+    /// Block requests sent to the peer, for which the peer hasn't sent the block yet
+    pub outstanding_requests: Vec<Request>,
+
+    /// Block requests sent by the peer, that we are yet to upload, in the order they were sent
+    pub peer_requests: VecDeque<Request>,
+
+    /// No of pieces that failed the hash check, that the peer sent blocks for
+    pub hash_fails: u32,
+
+    /// Total bytes of the blocks downloaded from the peer
+    pub downloaded: usize,
+
+    /// Total bytes of the blocks uploaded to the peer
+    pub uploaded: usize,
+
+    /// Rate at which we've downloaded from the peer in "bytes/second", over the last round of
+    /// the [Choker](choker::Choker)
+    pub download_rate: usize,
+
+    /// Rate at which we've uploaded to the peer in "bytes/second", over the last round of the
+    /// [Choker](choker::Choker)
+    pub upload_rate: usize,
 }
 
+impl PeerInfo {
+    /// Total no of pieces the peer has
+    pub fn pieces_count(&self) -> usize {
+        self.pieces_have.iter().filter(|have| **have).count()
+    }
+}
+
+/// Commands sent to a running peer session from outside of it
 #[derive(Debug)]
-pub struct Peer {
-    ///// An Owned Read Split Half of the connected TcpStream
-    //tcp_read_half: Arc<Mutex<Option<OwnedReadHalf>>>,
+pub enum PeerCommand {
+    /// Sends the given messages to the peer
+    Send(Vec<Message>),
 
-    ///// An Owned Write Split Half of the connected TcpStream
-    //tcp_write_half: Arc<Mutex<Option<OwnedWriteHalf>>>,
+    /// Closes the connection with the peer with the given reason
+    Disconnect(DisconnectReason),
+}
+
+#[derive(Debug)]
+pub struct Peer {
     /// Holds the information and state of the Peer
     pub info: Arc<Mutex<PeerInfo>>,
 
@@ -92,7 +353,16 @@ pub struct Peer {
     /// The socket address of the peer
     pub socket_adr: SocketAddr,
 
-    stream: Arc<Mutex<Option<Framed<TcpStream, PeerMessageCodec>>>>,
+    /// Where we got to know about the peer from
+    pub source: PeerSource,
+
+    /// Peer ID of the peer, if the tracker told us about it, the Handshake of the peer must
+    /// contain the same Peer ID
+    expected_peer_id: Option<Vec<u8>>,
+
+    /// A channel to send [PeerCommand] to the session of the peer, the receiver is only used
+    /// by the run() method
+    commands: (UnboundedSender<PeerCommand>, Arc<Mutex<UnboundedReceiver<PeerCommand>>>),
 }
 
 impl Peer {
@@ -102,60 +372,162 @@ impl Peer {
     /// state : The State of teh torrent session
     pub fn new(socket_adr: SocketAddr, state: Arc<State>) -> Self {
         let info = ArcMutex!(PeerInfo {
-            pieces_have: Vec::new(),
-            pieces_not_have: Vec::new(),
+            pieces_have: vec![false; state.pieces_hash.len()],
             peer_type: PeerType::Unknown,
             peer_state: PeerState::NotConnected,
+            peer_id: None,
+            supports_extensions: false,
+            supports_dht: false,
+            extensions: HashMap::new(),
+            client: None,
+            listen_port: None,
+            max_requests: None,
+            am_choking: true,
+            am_interested: false,
+            peer_choking: true,
+            peer_interested: false,
+            disconnect_reason: None,
+            outstanding_requests: Vec::new(),
+            peer_requests: VecDeque::new(),
+            hash_fails: 0,
+            downloaded: 0,
+            uploaded: 0,
+            download_rate: 0,
+            upload_rate: 0,
         });
 
-        let stream = ArcMutex!(None);
+        let (sd, rx) = unbounded_channel::<PeerCommand>();
+        let commands = (sd, ArcMutex!(rx));
 
         Self {
             info,
             state,
             socket_adr,
-            stream,
+            source: PeerSource::Tracker,
+            expected_peer_id: None,
+            commands,
         }
     }
 
-    /// It will run infinitely, non blockingly, until it gets a TCP connection with the given
-    /// socket address
-    ///
-    /// A 16 seconds of connection timeout time is kept to make a reliable TCP Connection
-    /// with the peer.
+    /// Sets where we got to know about the peer from, it's [PeerSource::Tracker] unless it's set
+    pub fn set_source(&mut self, source: PeerSource) {
+        self.source = source;
+    }
+
+    /// Sets the Peer ID that the peer must send in its Handshake
+    pub fn set_expected_peer_id(&mut self, peer_id: Vec<u8>) {
+        self.expected_peer_id = Some(peer_id);
+    }
+
+    /// Gets you the current state of the peer
+    pub async fn peer_state(&self) -> PeerState {
+        self.info.lock().await.peer_state.clone()
+    }
+
+    async fn set_peer_state(&self, peer_state: PeerState) {
+        self.info.lock().await.peer_state = peer_state;
+    }
+
+    /// Sends the given messages to the peer, if the session of the peer is running, they're
+    /// dropped once the peer has disconnected
+    pub fn send(&self, messages: Vec<Message>) {
+        let _ = self.commands.0.send(PeerCommand::Send(messages));
+    }
+
+    /// Asks the session of the peer to close the connection with the given reason
+    pub fn disconnect(&self, reason: DisconnectReason) {
+        let _ = self.commands.0.send(PeerCommand::Disconnect(reason));
+    }
+
+    /// Runs the entire lifecycle of the connection with the peer, it returns only after the
+    /// connection has been closed, the reason of which is stored in [PeerInfo]
     ///
-    /// A higher connection timeout time could be added too, but even if we get a TCP
-    /// Connection keeping the timeout higher, the connection won't be reliable enough
-    /// to exchange pieces with the peer.
+    /// Connect -> Handshake -> Exchange Messages -> Disconnect
+    pub async fn run(&self) {
+        let reason = match self.connect().await {
+            Ok(mut stream) => match self.handshake(&mut stream).await {
+                Ok(_) => self.run_session(stream).await,
+                Err(reason) => reason,
+            },
+            Err(reason) => reason,
+        };
+        self.on_disconnect(reason).await;
+    }
+
+    /// Runs the lifecycle of a connection made by the peer to us, whose Handshake has already
+    /// been read by the [listener], so we reply with our Handshake instead of sending it first
     ///
-    /// TODO : Figure out the sleep duration for connection timeout or socket error, i.e after a
-    /// socket error or connection timeout, figure out the time until next connection attempt
-    async fn connect(&self, socket_adr: SocketAddr) {
-        let CONNECTION_TIMEOUT = Duration::from_secs(16);
-        loop {
-            match timeout(CONNECTION_TIMEOUT, TcpStream::connect(socket_adr)).await {
-                Ok(connection) => match connection {
-                    Ok(tcp_stream) => {
-                        let peer_message_codec = codec::PeerMessageCodec;
-                        let codec_stream = Framed::new(tcp_stream, peer_message_codec);
-                        let mut stream = self.stream.lock().await;
-                        *stream = Some(codec_stream);
-                    }
-                    Err(_) => {
-                        // Err while trying to achieve a TCP Connection with the peer
-                        // TODO : Handle Connection timeout properly with
-                        // proper protocol implementation rather than this 1000 secs of sleep
-                        sleep(Duration::from_secs(1000)).await;
-                    }
-                },
+    /// Check Handshake -> Handshake -> Exchange Messages -> Disconnect
+    async fn run_inbound(&self, mut stream: PeerStream, handshake: Handshake) {
+        self.set_peer_state(PeerState::Connected).await;
+        let reason = match self.check_handshake(&handshake).await {
+            Ok(_) => {
+                let handshake = Message::Handshake(Handshake::new(self.state.clone()));
+                match stream.send(vec![handshake]).await {
+                    Ok(_) => self.run_session(stream).await,
+                    Err(_) => DisconnectReason::ConnectionError,
+                }
+            }
+            Err(reason) => reason,
+        };
+        self.on_disconnect(reason).await;
+    }
+
+    /// Keeps the reason of the disconnection and gives up everything the peer was holding on to
+    async fn on_disconnect(&self, reason: DisconnectReason) {
+        let mut info = self.info.lock().await;
+        info.peer_state = PeerState::Disconnected;
+        info.disconnect_reason = Some(reason);
+
+        // The blocks requested from the peer won't be coming anymore, so let other peers have them
+        info.outstanding_requests.clear();
+        let mut picker = self.state.picker.lock().await;
+        picker.release_requests(self.socket_adr);
+        picker.remove_availability(&info.pieces_have);
+        drop(picker);
+        drop(info);
+
+        for (_, extension) in self.state.extensions.iter() {
+            extension.on_disconnect(self).await;
+        }
+
+        // Nothing reads the commands anymore, so they're refused from now on rather than piling up
+        let mut commands = self.commands.1.lock().await;
+        commands.close();
+        while commands.try_recv().is_ok() {}
+        drop(commands);
+
+        self.state.remove_peer(self).await;
+    }
+
+    /// Tries to make a TCP connection with the peer for [MAX_CONNECTION_ATTEMPTS] times, staying
+    /// idle between each attempt for a little longer than the previous one
+    async fn connect(&self) -> Result<PeerStream, DisconnectReason> {
+        let mut reason = DisconnectReason::ConnectionTimeout;
+        for attempt in 1..=MAX_CONNECTION_ATTEMPTS {
+            self.set_peer_state(PeerState::TryingToConnect).await;
+            match timeout(CONNECTION_TIMEOUT, TcpStream::connect(self.socket_adr)).await {
+                Ok(Ok(tcp_stream)) => {
+                    self.set_peer_state(PeerState::Connected).await;
+                    return Ok(Framed::new(tcp_stream, PeerMessageCodec::new()));
+                }
+                Ok(Err(_)) => {
+                    // Err while trying to achieve a TCP Connection with the peer
+                    self.set_peer_state(PeerState::ConnectionErrorIdle).await;
+                    reason = DisconnectReason::ConnectionError;
+                }
                 Err(_) => {
                     // TCP Connection timeout
-                    // TODO : Handle Connection timeout properly with
-                    // proper protocol implementation rather than this 1000 secs of sleep
-                    sleep(Duration::from_secs(1000)).await;
+                    self.set_peer_state(PeerState::ConnectionTimeoutIdle).await;
+                    reason = DisconnectReason::ConnectionTimeout;
                 }
             }
+
+            if attempt < MAX_CONNECTION_ATTEMPTS {
+                sleep(CONNECTION_RETRY_INTERVAL * attempt).await;
+            }
         }
+        Err(reason)
     }
 
     /// It's a required and first message sent to a peer after creating a TCP connection
@@ -163,57 +535,482 @@ impl Peer {
     ///
     /// A Handshake is (49 + len(pstr)) bytes long
     ///
-    /// Creates and sends a Handshake message to the peer and returns the
-    /// responses of that Handshake Message
-    ///
-    /// NOTE : This method should only be called after calling self.connect() method
-    /// i.e after successfully establishing a TCP Connection with the peer
-    async fn send_handshake_message(&self) {
-        // Steps :
-        //
-        // 1. Send the Handshake message to the peer
-        // 2. Wait for messages that the peer is gonna send to us
-        // 3. After receiving the messages
-        //const HANDSHAKE_RESPONSE_WAIT_TIME: u64 = 2;
-
-        //// Creates a Handshake Message
-        //let handshake_message = Handshake::new(self.state.clone()).to_bytes();
-
-        //let write_half = write_half_lock.as_mut().unwrap();
-        //let read_half = read_half_lock.as_mut().unwrap();
-
-        //// Waits for all the messages that peer is gonna send as response
-        //// to the Handshake message we sent
-
-        //// A 4 Kb buffer for the response of Handshake message
-        //// TODO: Find the perfect buffer size
-
-        ////let mut messages = Vec::new();
-        ////    messages.append(&mut msgs);
-        ////    //    // Store all responses sent after 2 seconds of receiving HANDSHAKE response, its usually BITFIELD/HAVE/EXTENDED
-        ////    //    timeout(Duration::from_secs(HANDSHAKE_RESPONSE_WAIT_TIME), async {
-        ////    //        loop {
-        ////    //            if let Some(mut _msgs) = self.receiver.recv().await {
-        ////    //                messages.append(&mut _msgs);
-        ////    //            }
-        ////    //        }
-        ////    //    })
-        ////    //    .await;
-
-        ////// If the peer sends CHOKE, then we'll disconnect from that peer
-        ////if messages.contains(&Message::CHOKE) {
-        ////    self.write_half.shutdown();
-        ////}
-        ////Ok(messages)
+    /// Sends our Handshake to the peer and waits for the Handshake of the peer, any message sent
+    /// along with the Handshake such as Bitfield and Have stays in the buffer of the codec, and
+    /// gets read by the session later on
+    async fn handshake(&self, stream: &mut PeerStream) -> Result<(), DisconnectReason> {
+        let handshake = Message::Handshake(Handshake::new(self.state.clone()));
+        stream.send(vec![handshake]).await.map_err(|_| DisconnectReason::ConnectionError)?;
+        self.set_peer_state(PeerState::SentHandshake).await;
+
+        match timeout(HANDSHAKE_TIMEOUT, stream.next()).await {
+            Err(_) => Err(DisconnectReason::HandshakeTimeout),
+            Ok(None) => Err(DisconnectReason::ClosedByPeer),
+            Ok(Some(Err(e))) => Err(DisconnectReason::InvalidMessage(e.to_string())),
+            Ok(Some(Ok(Message::Handshake(ref handshake)))) => self.check_handshake(handshake).await,
+            Ok(Some(Ok(_))) => Err(DisconnectReason::ProtocolViolation("expected handshake")),
+        }
     }
 
+    /// Checks the Handshake of the peer, the info hash must be of this torrent and the Peer ID
+    /// must be the one the tracker told us about, if it told us about it
+    async fn check_handshake(&self, handshake: &Handshake) -> Result<(), DisconnectReason> {
+        if handshake.info_hash() != self.state.info_hash.as_slice() {
+            return Err(DisconnectReason::InfoHashMismatch);
+        }
+
+        if handshake.peer_id() == self.state.peer_id.as_slice() {
+            return Err(DisconnectReason::ConnectedToSelf);
+        }
+
+        if let Some(ref expected_peer_id) = self.expected_peer_id {
+            if handshake.peer_id() != expected_peer_id.as_slice() {
+                return Err(DisconnectReason::PeerIdMismatch);
+            }
+        }
+
+        let mut info = self.info.lock().await;
+        info.peer_id = Some(handshake.peer_id().to_vec());
+        info.supports_extensions = handshake.supports_extensions();
+        info.supports_dht = handshake.supports_dht();
+        info.peer_state = PeerState::Handshaked;
+        Ok(())
+    }
+
+    /// Exchanges messages with the peer until the connection is closed, by either of us, it's
+    /// called once the Handshakes have been exchanged
+    async fn run_session(&self, mut stream: PeerStream) -> DisconnectReason {
+        // Let the peer know about the pieces we have, if we have any
+        let pieces_have = self.state.picker.lock().await.have_pieces().to_vec();
+        if pieces_have.iter().any(|have| *have) {
+            let bitfield = Message::Bitfield(Bitfield::new(&pieces_have));
+            if stream.send(vec![bitfield]).await.is_err() {
+                return DisconnectReason::ConnectionError;
+            }
+        }
+
+        // Let the peer know about the extensions we support, if it supports the Extension Protocol
+        if self.info.lock().await.supports_extensions {
+            let handshake = self.extended_handshake().await;
+            let handshake = Message::Extended(Extended::new(EXTENDED_HANDSHAKE_ID, handshake.to_bytes()));
+            if stream.send(vec![handshake]).await.is_err() {
+                return DisconnectReason::ConnectionError;
+            }
+        }
+
+        // Let the peer know about the port of our DHT node, if it runs a DHT node too
+        let dht = self.state.dht.lock().await.clone();
+        if let Some(dht) = dht {
+            if self.info.lock().await.supports_dht && dht.port() != 0 {
+                let port = Message::Port(Port::new(dht.port()));
+                if stream.send(vec![port]).await.is_err() {
+                    return DisconnectReason::ConnectionError;
+                }
+            }
+        }
+
+        let mut commands = self.commands.1.lock().await;
+        let mut keep_alive = interval_at(Instant::now() + KEEP_ALIVE_INTERVAL, KEEP_ALIVE_INTERVAL);
+        let mut extensions_tick = interval_at(Instant::now() + TICK_INTERVAL, TICK_INTERVAL);
+        let mut last_received = Instant::now();
+        let mut is_first_message = true;
+
+        let reason = loop {
+            let has_peer_requests = !self.info.lock().await.peer_requests.is_empty();
+            let outgoing = tokio::select! {
+                message = stream.next() => match message {
+                    None => break DisconnectReason::ClosedByPeer,
+                    Some(Err(e)) => break DisconnectReason::InvalidMessage(e.to_string()),
+                    Some(Ok(message)) => {
+                        last_received = Instant::now();
+                        // KeepAlive, Extended and the messages we don't understand don't count as
+                        // the first message of the session, as some peers send their extended
+                        // handshake before the Bitfield
+                        let is_skippable = matches!(message, Message::KeepAlive | Message::Extended(_) | Message::Unknown(_));
+                        match self.handle_message(message, is_first_message).await {
+                            Ok(outgoing) => {
+                                is_first_message = is_first_message && is_skippable;
+                                outgoing
+                            }
+                            Err(reason) => break reason,
+                        }
+                    }
+                },
+                command = commands.recv() => match command {
+                    Some(PeerCommand::Send(messages)) => messages,
+                    Some(PeerCommand::Disconnect(reason)) => break reason,
+                    None => break DisconnectReason::Requested,
+                },
+                // Uploads a single block at a time, so that the messages of the peer such as Cancel
+                // still get read in between the blocks
+                _ = ready(()), if has_peer_requests => self.serve_request().await,
+                _ = keep_alive.tick() => vec![Message::KeepAlive],
+                _ = extensions_tick.tick() => self.tick_extensions().await,
+                _ = sleep_until(last_received + INACTIVITY_TIMEOUT) => break DisconnectReason::InactivityTimeout,
+            };
+            let outgoing = [outgoing, self.refresh_requests().await].concat();
+
+            if !outgoing.is_empty() {
+                if stream.send(outgoing).await.is_err() {
+                    break DisconnectReason::ConnectionError;
+                }
+                keep_alive.reset();
+            }
+        };
+
+        // Flushes whatever is left and shuts down the writing half of the connection
+        let _ = stream.close().await;
+        reason
+    }
+
+    /// Updates the state of the peer from the given message sent by the peer and gives back the
+    /// messages that are to be sent to the peer in response
+    async fn handle_message(&self, message: Message, is_first_message: bool) -> Result<Vec<Message>, DisconnectReason> {
+        match message {
+            Message::Piece(block) => return self.handle_block(block).await,
+            Message::Extended(extended) => return Ok(self.handle_extended(extended).await),
+            _ => {}
+        }
+
+        // The no of pieces isn't known until the "info" field of a torrent added through a magnet
+        // URI is received, so the pieces the peer has can't be kept track of until then
+        if !self.state.has_metadata() && matches!(message, Message::Bitfield(_) | Message::Have(_)) {
+            return Ok(Vec::new());
+        }
+
+        let mut info = self.info.lock().await;
+        match message {
+            Message::Bitfield(Bitfield {
+                ref have, ..
+            }) => {
+                // Bitfield is optional, but can only be sent as the message immediately following
+                // the handshake message
+                if !is_first_message {
+                    return Err(DisconnectReason::ProtocolViolation("bitfield after first message"));
+                }
+                let pieces_count = info.pieces_have.len();
+                for index in have {
+                    // Spare bits at the end of the bitfield must be cleared
+                    if *index >= pieces_count {
+                        return Err(DisconnectReason::ProtocolViolation("bitfield spare bits set"));
+                    }
+                    info.pieces_have[*index] = true;
+                }
+                self.state.picker.lock().await.add_availability(&info.pieces_have);
+                Self::update_peer_type(&mut info);
+            }
+
+            Message::Have(Have {
+                piece_index,
+            }) => {
+                let piece_index = piece_index as usize;
+                if piece_index >= info.pieces_have.len() {
+                    return Err(DisconnectReason::ProtocolViolation("have index out of range"));
+                }
+                if !info.pieces_have[piece_index] {
+                    info.pieces_have[piece_index] = true;
+                    self.state.picker.lock().await.increase_availability(piece_index as u32);
+                }
+                Self::update_peer_type(&mut info);
+            }
+
+            Message::Choke => {
+                // The peer discards all our requests when it chokes us
+                info.peer_choking = true;
+                info.peer_state = PeerState::Handshaked;
+                info.outstanding_requests.clear();
+                self.state.picker.lock().await.release_requests(self.socket_adr);
+            }
+
+            Message::Unchoke => {
+                info.peer_choking = false;
+                if info.am_interested {
+                    info.peer_state = PeerState::RequestingPiece;
+                }
+            }
+
+            Message::Interested => info.peer_interested = true,
+
+            Message::NotInterested => info.peer_interested = false,
+
+            Message::Handshake(_) => return Err(DisconnectReason::ProtocolViolation("second handshake")),
+
+            Message::Request(request) => {
+                if request.length > MAX_REQUEST_LENGTH {
+                    return Err(DisconnectReason::ProtocolViolation("request too long"));
+                }
+
+                // Requests sent while we're choking the peer are dropped, so are the ones for the
+                // blocks we don't have
+                let picker = self.state.picker.lock().await;
+                let is_valid_request =
+                    picker.have(request.index) && request.begin as u64 + request.length as u64 <= picker.piece_size(request.index) as u64;
+                let is_queueable = !info.am_choking && info.peer_requests.len() < MAX_PEER_REQUESTS;
+                if is_valid_request && is_queueable && !info.peer_requests.contains(&request) {
+                    info.peer_requests.push_back(request);
+                }
+            }
+
+            Message::Cancel(Cancel {
+                index,
+                begin,
+                length,
+            }) => info.peer_requests.retain(|request| *request != Request::new(index, begin, length)),
+
+            // The DHT node of the peer is pinged, so that it's added into our routing table once it
+            // responds
+            Message::Port(port) => {
+                let dht = self.state.dht.lock().await.clone();
+                if let Some(dht) = dht {
+                    let socket_adr = SocketAddr::new(self.socket_adr.ip(), port.listen_port());
+                    tokio::spawn(async move {
+                        dht.ping(socket_adr).await;
+                    });
+                }
+            }
+
+            Message::KeepAlive | Message::Piece(_) | Message::Extended(_) | Message::Unknown(_) => {}
+        }
+        Ok(Vec::new())
+    }
+
+    /// Our extended handshake, telling the peer about the extensions we support along with
+    /// whatever else the peer can make use of
+    async fn extended_handshake(&self) -> ExtendedHandshake {
+        let mut handshake = ExtendedHandshake {
+            m: self
+                .state
+                .extensions
+                .iter()
+                .map(|(id, extension)| (extension.name().to_string(), id))
+                .collect(),
+            v: Some(CLIENT_VERSION.to_string()),
+            p: self.state.tcp_ports.lock().await.first().copied(),
+            reqq: Some(MAX_PEER_REQUESTS),
+            yourip: Some(net::canonical(self.socket_adr).ip()),
+            ipv6: net::local_ipv6(),
+            metadata_size: None,
+        };
+        for (_, extension) in self.state.extensions.iter() {
+            extension.extend_handshake(&self.state, &mut handshake);
+        }
+        handshake
+    }
+
+    /// Handles the Extended message sent by the peer, i.e its extended handshake and the messages
+    /// of the extensions we support, which are handed over to the extension they're of
     ///
-    /// TODO : Write some stuff about INTERESTED message and its response
-    ///
-    /// Creates and sends INTERESTED message to the peer
-    pub async fn sendInterestedMessage(&mut self) {
-        // self.write_half.write_all(&Interested::build_message()).await;
+    /// An extended handshake that can't be parsed is ignored, just like the messages of the
+    /// extensions we don't support
+    async fn handle_extended(&self, extended: Extended) -> Vec<Message> {
+        if extended.id != EXTENDED_HANDSHAKE_ID {
+            return match self.state.extensions.get(extended.id) {
+                Some(extension) => extension.on_message(self, &extended.payload).await,
+                None => Vec::new(),
+            };
+        }
+
+        let Some(handshake) = ExtendedHandshake::from(&extended.payload) else {
+            return Vec::new();
+        };
+        {
+            let mut info = self.info.lock().await;
+            info.extensions = handshake.m.clone();
+            info.client = handshake.v.clone();
+            info.listen_port = handshake.p;
+            info.max_requests = handshake.reqq;
+        }
+
+        let mut outgoing = Vec::new();
+        for (_, extension) in self.state.extensions.iter() {
+            if handshake.m.contains_key(extension.name()) {
+                outgoing.extend(extension.on_handshake(self, &handshake).await);
+            }
+        }
+        outgoing
+    }
+
+    /// Ticks every extension the peer supports, see [Extension::on_tick](extension::Extension::on_tick)
+    async fn tick_extensions(&self) -> Vec<Message> {
+        let extensions = self.info.lock().await.extensions.clone();
+        let mut outgoing = Vec::new();
+        for (_, extension) in self.state.extensions.iter() {
+            if extensions.contains_key(extension.name()) {
+                outgoing.extend(extension.on_tick(self).await);
+            }
+        }
+        outgoing
+    }
+
+    /// Creates an Extended message of the extension with the given name, with the extended message
+    /// ID the peer asked for, "None" if the peer doesn't support the extension
+    async fn extension_message(&self, name: &str, payload: Vec<u8>) -> Option<Message> {
+        let id = *self.info.lock().await.extensions.get(name)?;
+        Some(Message::Extended(Extended::new(id, payload)))
+    }
+
+    /// Hands over the block sent by the peer to the [PiecePicker], and once the piece it belongs
+    /// to is complete, verifies the piece against its hash and writes it on the disk
+    async fn handle_block(&self, block: Block) -> Result<Vec<Message>, DisconnectReason> {
+        {
+            let mut info = self.info.lock().await;
+            info.outstanding_requests
+                .retain(|request| request.index != block.piece_index || request.begin != block.byte_index);
+            info.peer_state = PeerState::Downloading;
+            info.downloaded += block.raw_block.len();
+        }
+
+        let request = Request::new(block.piece_index, block.byte_index, block.raw_block.len() as u32);
+        let (result, cancels) = self.state.picker.lock().await.on_block(self.socket_adr, block);
+        if !cancels.is_empty() {
+            self.cancel_requests(&cancels, &request).await;
+        }
+
+        let blocks = match result {
+            BlockResult::PieceComplete(blocks) => blocks,
+            BlockResult::Stored | BlockResult::Unexpected => return Ok(Vec::new()),
+        };
+
+        // Hashing is done without holding the lock on the picker, the piece can't be picked by
+        // any other peer in the meantime anyway
+        let piece = Piece::from_blocks(blocks);
+        let index = piece.index();
+        let is_piece_valid = self
+            .state
+            .pieces_hash
+            .get(index as usize)
+            .is_some_and(|hash| piece.is_piece_valid(*hash));
+        if !is_piece_valid {
+            let contributors = self.state.picker.lock().await.piece_failed(index);
+            self.add_strikes(&contributors).await;
+            return Ok(Vec::new());
+        }
+
+        self.state.picker.lock().await.piece_verified(index);
+        if self.state.storage.write_piece(index, piece.data()).await.is_err() {
+            // TODO : Let the user know that the data couldn't be written
+            self.state.picker.lock().await.piece_lost(index);
+            return Ok(Vec::new());
+        }
+        self.state.bytes_complete.fetch_add(piece.data().len());
+        self.state.pieces_downloaded.fetch_add(1);
 
-        // self.receiver.recv().await
+        // This was the last piece of the torrent
+        if self.state.bytes_left() == 0 {
+            for tracker in self.state.trackers.read().await.iter().flatten() {
+                tracker.announce_completed();
+            }
+        }
+
+        // Let every peer know that we've got a new piece, including this one
+        for peer in self.state.peers.lock().await.iter() {
+            peer.send(vec![Message::Have(Have::new(index))]);
+        }
+        Ok(Vec::new())
+    }
+
+    /// Cancels the given request sent to every peer with the given socket address, it happens in
+    /// endgame when the same block was requested from multiple peers and one of them sent it
+    async fn cancel_requests(&self, socket_adrs: &[SocketAddr], request: &Request) {
+        for peer in self.state.peers.lock().await.iter() {
+            if socket_adrs.contains(&peer.socket_adr) {
+                peer.info
+                    .lock()
+                    .await
+                    .outstanding_requests
+                    .retain(|outstanding| outstanding != request);
+                peer.send(vec![Message::Cancel(Cancel::new(request))]);
+            }
+        }
+    }
+
+    /// Uploads the block of the oldest request of the peer, reading it from the disk
+    async fn serve_request(&self) -> Vec<Message> {
+        let request = match self.info.lock().await.peer_requests.pop_front() {
+            Some(request) => request,
+            None => return Vec::new(),
+        };
+
+        let data = match self.state.storage.read_block(request.index, request.begin, request.length).await {
+            Ok(data) => data,
+            Err(_) => return Vec::new(),
+        };
+
+        // We might have choked the peer while the block was being read
+        let mut info = self.info.lock().await;
+        if info.am_choking {
+            return Vec::new();
+        }
+        info.uploaded += data.len();
+        self.state.bytes_uploaded.fetch_add(data.len());
+        vec![Message::Piece(Block::new(
+            request.index,
+            request.begin,
+            BytesMut::from(data.as_slice()),
+        ))]
+    }
+
+    /// Counts a strike against every peer with the given socket address, the ones that have
+    /// sent blocks for [MAX_HASH_FAILS] pieces that failed the hash check get dropped
+    async fn add_strikes(&self, contributors: &[SocketAddr]) {
+        for peer in self.state.peers.lock().await.iter() {
+            if contributors.contains(&peer.socket_adr) {
+                let mut info = peer.info.lock().await;
+                info.hash_fails += 1;
+                if info.hash_fails >= MAX_HASH_FAILS {
+                    peer.disconnect(DisconnectReason::TooManyHashFails);
+                }
+            }
+        }
+    }
+
+    /// Updates our interest in the peer, and while the peer is unchoking us, keeps upto
+    /// [State::max_outstanding_requests] block requests outstanding with the peer, or fewer if the
+    /// peer queues up fewer
+    async fn refresh_requests(&self) -> Vec<Message> {
+        let mut info = self.info.lock().await;
+        let mut picker = self.state.picker.lock().await;
+        let mut outgoing = Self::update_interest(&mut info, &picker);
+        if info.am_interested && !info.peer_choking {
+            let max_outstanding_requests = match info.max_requests {
+                Some(max_requests) => self.state.max_outstanding_requests().min(max_requests),
+                None => self.state.max_outstanding_requests(),
+            };
+            let count = max_outstanding_requests.saturating_sub(info.outstanding_requests.len());
+            for request in picker.pick_requests(self.socket_adr, &info.pieces_have, count) {
+                info.outstanding_requests.push(request.clone());
+                outgoing.push(Message::Request(request));
+            }
+        }
+        outgoing
+    }
+
+    /// Figures out whether the peer is a Seeder or a Leecher from the pieces it has
+    fn update_peer_type(info: &mut PeerInfo) {
+        info.peer_type = if info.pieces_have.iter().all(|have| *have) {
+            PeerType::Seeder
+        } else {
+            PeerType::Leecher
+        };
+    }
+
+    /// Sends Interested as soon as the peer has some piece we don't have, and NotInterested when
+    /// it has nothing we need anymore
+    fn update_interest(info: &mut PeerInfo, picker: &PiecePicker) -> Vec<Message> {
+        let is_interesting = picker.is_interesting(&info.pieces_have);
+        if is_interesting == info.am_interested {
+            return Vec::new();
+        }
+
+        info.am_interested = is_interesting;
+        if is_interesting {
+            if !info.peer_choking {
+                info.peer_state = PeerState::RequestingPiece;
+            }
+            vec![Message::Interested]
+        } else {
+            info.peer_state = PeerState::Handshaked;
+            vec![Message::NotInterested]
+        }
     }
 }
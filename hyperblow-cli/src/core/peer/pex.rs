@@ -0,0 +1,252 @@
+/*
+ * NOTE : This file contains all the structs and methods related to Peer Exchange, i.e "ut_pex"
+ *
+ * https://www.bittorrent.org/beps/bep_0011.html
+ *
+ * Every minute each peer is told about the peers we've got connected with and the ones we've
+ * dropped since the last time, and the peers it tells us about are connected with just like the
+ * ones from the trackers. It's never registered for a private torrent, whose peers must only come
+ * from its trackers.
+ */
+
+use super::{
+    extension::Extension,
+    messages::{ExtendedHandshake, Message},
+    Peer, PeerSource, PeerState, PeerType,
+};
+use crate::core::net;
+use async_trait::async_trait;
+use serde_bencode::value::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+};
+use tokio::sync::{mpsc::UnboundedSender, Mutex};
+
+/// Name of the extension in the "m" dictionary of the extended handshake
+pub const UT_PEX: &str = "ut_pex";
+
+/// No of added and of dropped peers sent in a single message at most, the ones beyond it are sent
+/// in the next message, and the ones beyond it in a message from a peer are ignored
+const MAX_PEX_PEERS: usize = 50;
+
+/// Flag of an added peer, telling that it's a seed
+const SEED_FLAG: u8 = 0x02;
+
+/// Flag of an added peer, telling that it can be connected with, as we connected with it ourselves
+const REACHABLE_FLAG: u8 = 0x10;
+
+/// Messages of ut_pex, sent as the payload of an Extended message
+///
+/// Keys of the dictionary :
+/// added       Peers we've got connected with, in the compact form, 6 bytes each
+/// added.f     One byte of flags for each of the peers in "added"
+/// dropped     Peers we've been disconnected from, in the compact form, 6 bytes each
+/// added6      Same as "added", for the IPv6 peers, 18 bytes each
+/// added6.f    Same as "added.f", for the peers in "added6"
+/// dropped6    Same as "dropped", for the IPv6 peers, 18 bytes each
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PexMessage {
+    /// Peers we've got connected with, along with their flags
+    pub added: Vec<(SocketAddr, u8)>,
+
+    /// Peers we've been disconnected from
+    pub dropped: Vec<SocketAddr>,
+}
+
+impl PexMessage {
+    /// Parses the payload of an Extended message of ut_pex, the peers that aren't in the compact
+    /// form are left out
+    pub fn from(v: &[u8]) -> Option<Self> {
+        let Value::Dict(dict) = serde_bencode::from_bytes::<Value>(v).ok()? else {
+            return None;
+        };
+        let bytes = |key: &[u8]| match dict.get(key) {
+            Some(Value::Bytes(bytes)) => bytes.as_slice(),
+            _ => &[],
+        };
+        let peers = |key: &[u8], peer_length: usize| -> Vec<SocketAddr> {
+            bytes(key).chunks_exact(peer_length).filter_map(net::compact_peer).collect()
+        };
+
+        let mut added = Vec::new();
+        for (key, flags_key, peer_length) in [(b"added".as_ref(), b"added.f".as_ref(), 6), (b"added6", b"added6.f", 18)] {
+            let flags = bytes(flags_key);
+            for (index, socket_adr) in peers(key, peer_length).into_iter().enumerate() {
+                added.push((socket_adr, flags.get(index).copied().unwrap_or(0)));
+            }
+        }
+        let dropped = [peers(b"dropped", 6), peers(b"dropped6", 18)].concat();
+
+        Some(Self {
+            added,
+            dropped,
+        })
+    }
+
+    /// Bencodes the message into the payload of an Extended message, the IPv4 and the IPv6 peers
+    /// are put under their own keys
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (mut added, mut added_f, mut dropped) = (Vec::new(), Vec::new(), Vec::new());
+        let (mut added6, mut added6_f, mut dropped6) = (Vec::new(), Vec::new(), Vec::new());
+        for (socket_adr, flags) in &self.added {
+            match net::canonical(*socket_adr).is_ipv4() {
+                true => {
+                    added.extend(net::to_compact_peer(*socket_adr));
+                    added_f.push(*flags);
+                }
+                false => {
+                    added6.extend(net::to_compact_peer(*socket_adr));
+                    added6_f.push(*flags);
+                }
+            }
+        }
+        for socket_adr in &self.dropped {
+            match net::canonical(*socket_adr).is_ipv4() {
+                true => dropped.extend(net::to_compact_peer(*socket_adr)),
+                false => dropped6.extend(net::to_compact_peer(*socket_adr)),
+            }
+        }
+
+        let dict = HashMap::from([
+            (b"added".to_vec(), Value::Bytes(added)),
+            (b"added.f".to_vec(), Value::Bytes(added_f)),
+            (b"dropped".to_vec(), Value::Bytes(dropped)),
+            (b"added6".to_vec(), Value::Bytes(added6)),
+            (b"added6.f".to_vec(), Value::Bytes(added6_f)),
+            (b"dropped6".to_vec(), Value::Bytes(dropped6)),
+        ]);
+        serde_bencode::to_bytes(&Value::Dict(dict)).unwrap_or_default()
+    }
+}
+
+/// The ut_pex extension of a torrent, it lets each peer know about the other peers of the
+/// torrent, and hands the peers it's told about over to be connected with
+#[derive(Debug)]
+pub struct UtPex {
+    /// Where the peers learned from the other peers are sent, to be connected with
+    peer_sender: Arc<UnboundedSender<Peer>>,
+
+    /// The peers each peer knows about from us, by the socket address of the peer
+    sent: Mutex<HashMap<SocketAddr, HashSet<SocketAddr>>>,
+}
+
+#[async_trait]
+impl Extension for UtPex {
+    fn name(&self) -> &'static str {
+        UT_PEX
+    }
+
+    /// Lets the peer know about all the peers we're connected with
+    async fn on_handshake(&self, peer: &Peer, _handshake: &ExtendedHandshake) -> Vec<Message> {
+        self.exchange(peer).await
+    }
+
+    /// Hands the peers added by the peer over to be connected with, the dropped ones are left as
+    /// they are, as we might still be connected with them
+    async fn on_message(&self, peer: &Peer, payload: &[u8]) -> Vec<Message> {
+        let Some(message) = PexMessage::from(payload) else {
+            return Vec::new();
+        };
+        for (socket_adr, _) in message.added.into_iter().take(MAX_PEX_PEERS) {
+            if socket_adr != peer.socket_adr {
+                let mut added = Peer::new(socket_adr, peer.state.clone());
+                added.set_source(PeerSource::Pex);
+                let _ = self.peer_sender.send(added);
+            }
+        }
+        Vec::new()
+    }
+
+    /// Lets the peer know about the peers we've got connected with and the ones we've dropped,
+    /// since the last time
+    async fn on_tick(&self, peer: &Peer) -> Vec<Message> {
+        self.exchange(peer).await
+    }
+
+    async fn on_disconnect(&self, peer: &Peer) {
+        self.sent.lock().await.remove(&peer.socket_adr);
+    }
+}
+
+impl UtPex {
+    /// Creates the ut_pex extension of a torrent, the peers learned from the other peers are sent
+    /// through the given sender, to be connected with
+    pub fn new(peer_sender: Arc<UnboundedSender<Peer>>) -> Self {
+        Self {
+            peer_sender,
+            sent: Mutex::default(),
+        }
+    }
+
+    /// Creates the message with the peers added and dropped since the last message sent to the
+    /// peer, there's no message if nothing has changed
+    async fn exchange(&self, peer: &Peer) -> Vec<Message> {
+        let connected = Self::connected_peers(peer).await;
+
+        let mut sent = self.sent.lock().await;
+        let known = sent.entry(peer.socket_adr).or_default();
+        let added: Vec<(SocketAddr, u8)> = connected
+            .iter()
+            .filter(|(socket_adr, _)| !known.contains(*socket_adr))
+            .take(MAX_PEX_PEERS)
+            .map(|(socket_adr, flags)| (*socket_adr, *flags))
+            .collect();
+        let dropped: Vec<SocketAddr> = known
+            .iter()
+            .filter(|socket_adr| !connected.contains_key(socket_adr))
+            .take(MAX_PEX_PEERS)
+            .copied()
+            .collect();
+        if added.is_empty() && dropped.is_empty() {
+            return Vec::new();
+        }
+
+        known.extend(added.iter().map(|(socket_adr, _)| *socket_adr));
+        known.retain(|socket_adr| !dropped.contains(socket_adr));
+        let message = PexMessage {
+            added,
+            dropped,
+        };
+        drop(sent);
+        peer.extension_message(UT_PEX, message.to_bytes()).await.into_iter().collect()
+    }
+
+    /// The peers of the torrent we're exchanging messages with, other than the given peer, by the
+    /// address they can be connected with, along with their flags
+    ///
+    /// A peer that connected to us is only in there if it has told us the port it listens on
+    async fn connected_peers(peer: &Peer) -> HashMap<SocketAddr, u8> {
+        let mut connected = HashMap::new();
+        for other in peer.state.peers.lock().await.iter() {
+            if other.socket_adr == peer.socket_adr {
+                continue;
+            }
+            let info = other.info.lock().await;
+            if !matches!(
+                info.peer_state,
+                PeerState::Handshaked | PeerState::RequestingPiece | PeerState::Downloading
+            ) {
+                continue;
+            }
+
+            let mut flags = 0;
+            if matches!(info.peer_type, PeerType::Seeder) {
+                flags |= SEED_FLAG;
+            }
+            let socket_adr = match other.source {
+                PeerSource::Incoming => match info.listen_port {
+                    Some(port) => SocketAddr::new(other.socket_adr.ip(), port),
+                    None => continue,
+                },
+                _ => {
+                    flags |= REACHABLE_FLAG;
+                    other.socket_adr
+                }
+            };
+            connected.insert(socket_adr, flags);
+        }
+        connected
+    }
+}
@@ -0,0 +1,591 @@
+use super::messages::{Block, Request};
+use rand::{seq::SliceRandom, thread_rng};
+use std::{collections::HashMap, net::SocketAddr};
+
+/// Size of the block we request from the peers, 16 KiB is what almost every client uses and a
+/// lot of them drop the connection if a bigger block is requested
+pub const BLOCK_LENGTH: u32 = 16384;
+
+/// No of pieces picked at random before switching to rarest first, the rarest pieces are usually
+/// the slowest ones to download, so a new download first gets a few complete pieces as quickly as
+/// possible to have something to trade with the peers
+pub const RANDOM_FIRST_PIECES: usize = 4;
+
+/// State of a single block of a piece that's being downloaded
+#[derive(Debug, Clone, PartialEq)]
+enum BlockState {
+    /// Block hasn't been requested from any peer
+    Missing,
+
+    /// Block has been requested from the peers with the given socket addresses, it's only ever
+    /// requested from more than one peer in endgame
+    Requested(Vec<SocketAddr>),
+
+    /// Block has been received
+    Received(Block),
+}
+
+/// A piece, some of whose blocks have been requested or received
+#[derive(Debug)]
+struct PartialPiece {
+    /// State of each block of the piece, by the zero based index of the block
+    blocks: Vec<BlockState>,
+
+    /// Socket address of all the peers that sent us atleast one block of this piece, if the piece
+    /// fails the hash check, then all of them get a strike
+    contributors: Vec<SocketAddr>,
+}
+
+/// What happened to a block we received from a peer
+#[derive(Debug)]
+pub enum BlockResult {
+    /// We never requested the block, or we've already received it from some other peer
+    Unexpected,
+
+    /// Block was kept, but there are still other blocks of the piece left to be received
+    Stored,
+
+    /// It was the last block of the piece, all the blocks of the piece are given back in order,
+    /// they're still to be put together and verified against the hash of the piece
+    ///
+    /// NOTE : The piece isn't hashed here, as the picker is locked by every peer of the torrent
+    PieceComplete(Vec<Block>),
+}
+
+/// Decides which blocks are to be requested from which peer and puts the received blocks back
+/// together into pieces
+///
+/// Every piece is split into blocks of [BLOCK_LENGTH], except the last block of a piece and the
+/// blocks of the last piece which can be shorter. A piece that's already being downloaded is
+/// always finished first, after that the first [RANDOM_FIRST_PIECES] pieces are picked at random
+/// and then the pieces that the least no of peers have are picked first i.e rarest first.
+/// Pieces that aren't wanted, because they only fall under the files that aren't to be
+/// downloaded, are never picked.
+///
+/// Once every remaining block has been requested, the download enters endgame, where the blocks
+/// that are still outstanding get requested from every other peer that has them as well, so that
+/// the last few pieces don't stall on a single slow peer. As soon as one copy of such a block
+/// arrives, the requests made to the other peers are to be cancelled.
+#[derive(Debug)]
+pub struct PiecePicker {
+    /// Size of each piece in bytes, except the last piece
+    piece_length: u64,
+
+    /// Size of the entire torrent in bytes
+    total_length: u64,
+
+    /// Whether we have the verified piece at the zero based index or not
+    have: Vec<bool>,
+
+    /// Pieces that are being downloaded, by their zero based index
+    partial: HashMap<u32, PartialPiece>,
+
+    /// No of connected peers that have the piece at the zero based index
+    availability: Vec<u32>,
+
+    /// Whether the piece at the zero based index is to be downloaded or not
+    wanted: Vec<bool>,
+}
+
+impl PiecePicker {
+    /// Creates a PiecePicker for a torrent where we don't have any piece yet
+    pub fn new(piece_length: u64, total_length: u64, pieces_count: usize) -> Self {
+        Self {
+            piece_length,
+            total_length,
+            have: vec![false; pieces_count],
+            partial: HashMap::new(),
+            availability: vec![0; pieces_count],
+            wanted: vec![true; pieces_count],
+        }
+    }
+
+    /// Total no of pieces of the torrent
+    pub fn pieces_count(&self) -> usize {
+        self.have.len()
+    }
+
+    /// Whether we have the verified piece at the given index or not
+    pub fn have(&self, index: u32) -> bool {
+        self.have.get(index as usize).copied().unwrap_or(false)
+    }
+
+    /// Whether we have the verified piece or not, for each piece by its index
+    pub fn have_pieces(&self) -> &[bool] {
+        &self.have
+    }
+
+    /// Whether every wanted piece of the torrent has been downloaded and verified
+    pub fn is_complete(&self) -> bool {
+        self.have.iter().zip(self.wanted.iter()).all(|(have, wanted)| *have || !*wanted)
+    }
+
+    /// Sets whether each piece, by its index, is to be downloaded or not
+    pub fn set_wanted(&mut self, wanted: Vec<bool>) {
+        if wanted.len() == self.wanted.len() {
+            self.wanted = wanted;
+        }
+    }
+
+    /// No of connected peers that have the piece, for each piece by its index
+    pub fn availability(&self) -> &[u32] {
+        &self.availability
+    }
+
+    /// Counts the pieces of a peer, it's called when the peer sends its Bitfield
+    pub fn add_availability(&mut self, peer_pieces: &[bool]) {
+        for (count, peer_has) in self.availability.iter_mut().zip(peer_pieces.iter()) {
+            if *peer_has {
+                *count += 1;
+            }
+        }
+    }
+
+    /// Counts a single piece of a peer, it's called when the peer sends a Have
+    pub fn increase_availability(&mut self, index: u32) {
+        if let Some(count) = self.availability.get_mut(index as usize) {
+            *count += 1;
+        }
+    }
+
+    /// Stops counting the pieces of a peer, it's called when the peer disconnects
+    pub fn remove_availability(&mut self, peer_pieces: &[bool]) {
+        for (count, peer_has) in self.availability.iter_mut().zip(peer_pieces.iter()) {
+            if *peer_has {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Size of the piece at the given index in bytes, the last piece is usually shorter than the
+    /// others
+    pub fn piece_size(&self, index: u32) -> u32 {
+        let begin = index as u64 * self.piece_length;
+        let end = (begin + self.piece_length).min(self.total_length);
+        end.saturating_sub(begin) as u32
+    }
+
+    /// No of blocks the piece at the given index is split into
+    fn blocks_count(&self, index: u32) -> usize {
+        self.piece_size(index).div_ceil(BLOCK_LENGTH) as usize
+    }
+
+    /// Creates a Request for the block at the given block index of the piece
+    fn request_for(&self, index: u32, block_index: usize) -> Request {
+        let begin = block_index as u32 * BLOCK_LENGTH;
+        let length = BLOCK_LENGTH.min(self.piece_size(index) - begin);
+        Request::new(index, begin, length)
+    }
+
+    /// Whether the peer, having the given pieces, has any wanted piece we still need
+    pub fn is_interesting(&self, peer_pieces: &[bool]) -> bool {
+        peer_pieces
+            .iter()
+            .zip(self.have.iter())
+            .zip(self.wanted.iter())
+            .any(|((peer_has, have), wanted)| *peer_has && !*have && *wanted)
+    }
+
+    /// Whether the download is in endgame, i.e every block of every wanted piece that we don't
+    /// have yet has already been requested
+    pub fn is_endgame(&self) -> bool {
+        let is_every_piece_started =
+            (0..self.pieces_count()).all(|i| self.have[i] || !self.wanted[i] || self.partial.contains_key(&(i as u32)));
+        let is_every_block_requested = self
+            .partial
+            .values()
+            .all(|partial_piece| !partial_piece.blocks.contains(&BlockState::Missing));
+        is_every_piece_started && is_every_block_requested
+    }
+
+    /// Picks upto "count" blocks to be requested from the peer with the given socket address,
+    /// which has the given pieces, and marks them as requested by that peer
+    pub fn pick_requests(&mut self, peer: SocketAddr, peer_pieces: &[bool], count: usize) -> Vec<Request> {
+        let mut requests = Vec::new();
+        if count == 0 {
+            return requests;
+        }
+
+        // Finish the pieces that are already being downloaded first, so that we get complete
+        // pieces as soon as possible, rather than lots of half downloaded pieces
+        let mut partial_indices: Vec<u32> = self.partial.keys().copied().collect();
+        partial_indices.sort_unstable();
+        for index in partial_indices {
+            if peer_pieces.get(index as usize) == Some(&true) {
+                self.pick_from_piece(index, peer, count, &mut requests);
+                if requests.len() == count {
+                    return requests;
+                }
+            }
+        }
+
+        // Then start downloading new pieces
+        for index in self.new_pieces(peer_pieces) {
+            self.pick_from_piece(index, peer, count, &mut requests);
+            if requests.len() == count {
+                return requests;
+            }
+        }
+
+        if self.is_endgame() {
+            self.pick_endgame(peer, peer_pieces, count, &mut requests);
+        }
+        requests
+    }
+
+    /// Picks the blocks that have already been requested from other peers, but not from this one,
+    /// until there are "count" requests
+    fn pick_endgame(&mut self, peer: SocketAddr, peer_pieces: &[bool], count: usize, requests: &mut Vec<Request>) {
+        let mut partial_indices: Vec<u32> = self.partial.keys().copied().collect();
+        partial_indices.sort_unstable();
+        for index in partial_indices {
+            if peer_pieces.get(index as usize) != Some(&true) {
+                continue;
+            }
+
+            let mut picked_blocks = Vec::new();
+            if let Some(partial_piece) = self.partial.get_mut(&index) {
+                for (block_index, block_state) in partial_piece.blocks.iter_mut().enumerate() {
+                    if requests.len() + picked_blocks.len() == count {
+                        break;
+                    }
+                    if let BlockState::Requested(ref mut requested_from) = block_state {
+                        if !requested_from.contains(&peer) {
+                            requested_from.push(peer);
+                            picked_blocks.push(block_index);
+                        }
+                    }
+                }
+            }
+
+            for block_index in picked_blocks {
+                requests.push(self.request_for(index, block_index));
+            }
+            if requests.len() == count {
+                return;
+            }
+        }
+    }
+
+    /// Gives the index of the wanted pieces the peer has, that we haven't started downloading
+    /// yet, in the order they're to be picked
+    fn new_pieces(&self, peer_pieces: &[bool]) -> Vec<u32> {
+        let mut pieces: Vec<u32> = (0..self.pieces_count() as u32)
+            .filter(|index| {
+                let i = *index as usize;
+                !self.have[i] && self.wanted[i] && !self.partial.contains_key(index) && peer_pieces.get(i) == Some(&true)
+            })
+            .collect();
+
+        // Shuffling first, so that the pieces that are equally rare get picked at random, rather
+        // than every client picking the same piece
+        pieces.shuffle(&mut thread_rng());
+        let pieces_verified = self.have.iter().filter(|have| **have).count();
+        if pieces_verified >= RANDOM_FIRST_PIECES {
+            pieces.sort_by_key(|index| self.availability[*index as usize]);
+        }
+        pieces
+    }
+
+    /// Picks the missing blocks of the piece at the given index, until there are "count" requests
+    fn pick_from_piece(&mut self, index: u32, peer: SocketAddr, count: usize, requests: &mut Vec<Request>) {
+        let blocks_count = self.blocks_count(index);
+        let mut picked_blocks = Vec::new();
+        {
+            let partial_piece = self.partial.entry(index).or_insert_with(|| PartialPiece {
+                blocks: vec![BlockState::Missing; blocks_count],
+                contributors: Vec::new(),
+            });
+            for (block_index, block_state) in partial_piece.blocks.iter_mut().enumerate() {
+                if requests.len() + picked_blocks.len() == count {
+                    break;
+                }
+                if *block_state == BlockState::Missing {
+                    *block_state = BlockState::Requested(vec![peer]);
+                    picked_blocks.push(block_index);
+                }
+            }
+        }
+
+        for block_index in picked_blocks {
+            requests.push(self.request_for(index, block_index));
+        }
+    }
+
+    /// Keeps the block sent by the peer with the given socket address, and once all the blocks
+    /// of the piece are received, gives back the entire piece to be verified
+    ///
+    /// Along with it, gives back the socket address of the other peers the block was requested
+    /// from, whose requests are to be cancelled
+    pub fn on_block(&mut self, peer: SocketAddr, block: Block) -> (BlockResult, Vec<SocketAddr>) {
+        let index = block.piece_index;
+        if self.have(index) || !block.byte_index.is_multiple_of(BLOCK_LENGTH) {
+            return (BlockResult::Unexpected, Vec::new());
+        }
+
+        let block_index = (block.byte_index / BLOCK_LENGTH) as usize;
+        let expected_request = match block_index < self.blocks_count(index) {
+            true => self.request_for(index, block_index),
+            false => return (BlockResult::Unexpected, Vec::new()),
+        };
+        if block.raw_block.len() != expected_request.length as usize {
+            return (BlockResult::Unexpected, Vec::new());
+        }
+
+        let partial_piece = match self.partial.get_mut(&index) {
+            Some(partial_piece) => partial_piece,
+            None => return (BlockResult::Unexpected, Vec::new()),
+        };
+
+        // A block that was requested from some other peer is kept as well, the bytes are already
+        // here, so there's no reason to throw them away
+        let requested_from = match partial_piece.blocks[block_index] {
+            BlockState::Received(_) => return (BlockResult::Unexpected, Vec::new()),
+            BlockState::Missing => Vec::new(),
+            BlockState::Requested(ref requested_from) => requested_from.clone(),
+        };
+        partial_piece.blocks[block_index] = BlockState::Received(block);
+        let cancels: Vec<SocketAddr> = requested_from.into_iter().filter(|socket_adr| *socket_adr != peer).collect();
+        if !partial_piece.contributors.contains(&peer) {
+            partial_piece.contributors.push(peer);
+        }
+
+        let is_piece_complete = partial_piece
+            .blocks
+            .iter()
+            .all(|block_state| matches!(block_state, BlockState::Received(_)));
+        if !is_piece_complete {
+            return (BlockResult::Stored, cancels);
+        }
+
+        // The entry is kept until the piece is verified or fails, so that no other peer starts
+        // downloading it in the meantime
+        let blocks = partial_piece
+            .blocks
+            .iter()
+            .filter_map(|block_state| match block_state {
+                BlockState::Received(ref block) => Some(block.clone()),
+                _ => None,
+            })
+            .collect();
+        (BlockResult::PieceComplete(blocks), cancels)
+    }
+
+    /// Marks the piece at the given index as verified, after its hash matched
+    pub fn piece_verified(&mut self, index: u32) {
+        self.partial.remove(&index);
+        if let Some(have) = self.have.get_mut(index as usize) {
+            *have = true;
+        }
+    }
+
+    /// Puts the piece at the given index back in the queue, after it failed the hash check, and
+    /// gives back the socket address of all the peers that sent us its blocks
+    pub fn piece_failed(&mut self, index: u32) -> Vec<SocketAddr> {
+        match self.partial.remove(&index) {
+            Some(partial_piece) => partial_piece.contributors,
+            None => Vec::new(),
+        }
+    }
+
+    /// Marks a piece we had as missing again, e.g when it couldn't be written to the disk
+    pub fn piece_lost(&mut self, index: u32) {
+        self.partial.remove(&index);
+        if let Some(have) = self.have.get_mut(index as usize) {
+            *have = false;
+        }
+    }
+
+    /// Puts all the blocks requested from the peer with the given socket address back in the
+    /// queue, it's called when the peer chokes us or disconnects, as the blocks won't be coming
+    pub fn release_requests(&mut self, peer: SocketAddr) {
+        for partial_piece in self.partial.values_mut() {
+            for block_state in partial_piece.blocks.iter_mut() {
+                if let BlockState::Requested(ref mut requested_from) = block_state {
+                    requested_from.retain(|socket_adr| *socket_adr != peer);
+                    if requested_from.is_empty() {
+                        *block_state = BlockState::Missing;
+                    }
+                }
+            }
+        }
+
+        // A piece with nothing received and nothing requested can be picked as a new piece again
+        self.partial
+            .retain(|_, partial_piece| partial_piece.blocks.iter().any(|block_state| *block_state != BlockState::Missing));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use std::collections::HashSet;
+
+    const BLOCK: u64 = BLOCK_LENGTH as u64;
+
+    fn adr(port: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, 1], port))
+    }
+
+    fn block(request: &Request) -> Block {
+        Block {
+            piece_index: request.index,
+            byte_index: request.begin,
+            raw_block: BytesMut::from(&vec![1; request.length as usize][..]),
+        }
+    }
+
+    /// A picker of the given no of pieces of a single block each
+    fn picker(pieces_count: usize) -> PiecePicker {
+        PiecePicker::new(BLOCK, pieces_count as u64 * BLOCK, pieces_count)
+    }
+
+    #[test]
+    fn pieces_are_split_into_blocks_with_a_short_last_block() {
+        // 3 pieces of 2 full blocks and a block of 100 bytes, the last piece being a single block
+        let piece_length = 2 * BLOCK + 100;
+        let mut picker = PiecePicker::new(piece_length, 2 * piece_length + 5000, 3);
+        let mut requests = picker.pick_requests(adr(1), &[true; 3], 100);
+        requests.sort_by_key(|request| (request.index, request.begin));
+
+        let expected = vec![
+            Request::new(0, 0, BLOCK_LENGTH),
+            Request::new(0, BLOCK_LENGTH, BLOCK_LENGTH),
+            Request::new(0, 2 * BLOCK_LENGTH, 100),
+            Request::new(1, 0, BLOCK_LENGTH),
+            Request::new(1, BLOCK_LENGTH, BLOCK_LENGTH),
+            Request::new(1, 2 * BLOCK_LENGTH, 100),
+            Request::new(2, 0, 5000),
+        ];
+        assert_eq!(requests, expected);
+        assert_eq!(picker.piece_size(2), 5000);
+    }
+
+    #[test]
+    fn pieces_are_picked_at_random_first() {
+        let mut first_picks = HashSet::new();
+        for _ in 0..20 {
+            let mut picker = picker(10);
+            picker.add_availability(&[false, true, true, true, true, true, true, true, true, true]);
+            picker.add_availability(&[true; 10]);
+            first_picks.insert(picker.pick_requests(adr(1), &[true; 10], 1)[0].index);
+        }
+        // Piece 0 is the rarest, yet it isn't the only one ever picked
+        assert!(first_picks.len() > 1);
+    }
+
+    #[test]
+    fn pieces_are_picked_rarest_first_after_random_first_pieces() {
+        let mut picker = picker(10);
+        for index in 0..RANDOM_FIRST_PIECES as u32 {
+            picker.piece_verified(index);
+        }
+        // The availability of piece 9 is 1, of piece 8 is 2 and so on
+        for peers_count in 1..=10 {
+            let peer_pieces: Vec<bool> = (0..10).map(|index| index + peers_count <= 10).collect();
+            picker.add_availability(&peer_pieces);
+        }
+
+        let picked: Vec<u32> = picker
+            .pick_requests(adr(1), &[true; 10], 3)
+            .iter()
+            .map(|request| request.index)
+            .collect();
+        assert_eq!(picked, vec![9, 8, 7]);
+    }
+
+    #[test]
+    fn pieces_in_progress_are_finished_first() {
+        let mut picker = PiecePicker::new(4 * BLOCK, 8 * BLOCK, 2);
+        let first = picker.pick_requests(adr(1), &[true; 2], 1);
+        let second = picker.pick_requests(adr(2), &[true; 2], 3);
+        assert!(second.iter().all(|request| request.index == first[0].index));
+    }
+
+    #[test]
+    fn unwanted_pieces_are_never_picked() {
+        let mut picker = picker(4);
+        picker.set_wanted(vec![false, true, false, false]);
+        assert!(!picker.is_interesting(&[true, false, true, true]));
+        assert!(picker.is_interesting(&[false, true, false, false]));
+
+        let requests = picker.pick_requests(adr(1), &[true; 4], 10);
+        assert_eq!(requests, vec![Request::new(1, 0, BLOCK_LENGTH)]);
+        assert!(!picker.is_complete());
+        picker.piece_verified(1);
+        assert!(picker.is_complete());
+    }
+
+    #[test]
+    fn endgame_requests_the_outstanding_blocks_from_the_other_peers() {
+        let mut picker = PiecePicker::new(2 * BLOCK, 2 * BLOCK, 1);
+        let requests = picker.pick_requests(adr(1), &[true], 10);
+        assert_eq!(requests.len(), 2);
+        assert!(picker.is_endgame());
+
+        // The blocks requested from the first peer are requested from the second one as well, but
+        // never twice from the same peer
+        assert_eq!(picker.pick_requests(adr(2), &[true], 10), requests);
+        assert!(picker.pick_requests(adr(2), &[true], 10).is_empty());
+        assert!(picker.pick_requests(adr(3), &[false], 10).is_empty());
+
+        let (result, cancels) = picker.on_block(adr(1), block(&requests[0]));
+        assert!(matches!(result, BlockResult::Stored));
+        assert_eq!(cancels, vec![adr(2)]);
+        assert!(matches!(picker.on_block(adr(2), block(&requests[0])).0, BlockResult::Unexpected));
+
+        let (result, cancels) = picker.on_block(adr(2), block(&requests[1]));
+        assert!(matches!(result, BlockResult::PieceComplete(ref blocks) if blocks.len() == 2));
+        assert_eq!(cancels, vec![adr(1)]);
+    }
+
+    #[test]
+    fn unexpected_blocks_are_refused() {
+        let mut picker = PiecePicker::new(2 * BLOCK, 2 * BLOCK, 1);
+        let request = Request::new(0, 0, BLOCK_LENGTH);
+        // Nothing has been requested yet
+        assert!(matches!(picker.on_block(adr(1), block(&request)).0, BlockResult::Unexpected));
+
+        picker.pick_requests(adr(1), &[true], 10);
+        for request in [
+            Request::new(0, 1, BLOCK_LENGTH),
+            Request::new(0, 0, 10),
+            Request::new(0, 2 * BLOCK_LENGTH, BLOCK_LENGTH),
+        ] {
+            assert!(matches!(picker.on_block(adr(1), block(&request)).0, BlockResult::Unexpected));
+        }
+    }
+
+    #[test]
+    fn failed_piece_is_requeued_and_gives_back_its_contributors() {
+        let mut picker = PiecePicker::new(2 * BLOCK, 2 * BLOCK, 1);
+        let first = picker.pick_requests(adr(1), &[true], 1);
+        let second = picker.pick_requests(adr(2), &[true], 1);
+        picker.on_block(adr(1), block(&first[0]));
+        assert!(matches!(
+            picker.on_block(adr(2), block(&second[0])).0,
+            BlockResult::PieceComplete(_)
+        ));
+
+        // The piece isn't picked while it's being verified
+        assert!(picker.pick_requests(adr(3), &[true], 10).is_empty());
+        assert_eq!(picker.piece_failed(0), vec![adr(1), adr(2)]);
+        assert_eq!(picker.pick_requests(adr(3), &[true], 10).len(), 2);
+        assert!(!picker.have(0));
+    }
+
+    #[test]
+    fn released_requests_are_picked_again() {
+        let mut picker = picker(2);
+        let requests = picker.pick_requests(adr(1), &[true; 2], 10);
+        assert!(picker
+            .pick_requests(adr(2), &[true; 2], 10)
+            .iter()
+            .all(|request| requests.contains(request)));
+
+        picker.release_requests(adr(1));
+        picker.release_requests(adr(2));
+        assert_eq!(picker.pick_requests(adr(3), &[true; 2], 10).len(), 2);
+    }
+}
@@ -0,0 +1,189 @@
+use serde_bencode::value::Value;
+use std::{collections::HashMap, path::PathBuf};
+use thiserror::Error;
+use tokio::{fs, io};
+
+/// Directory inside of the download directory, where the resume data of every torrent is kept
+pub const RESUME_DIRECTORY: &str = ".hyperblow";
+
+#[derive(Error, Debug)]
+pub enum ResumeError {
+    #[error("Io - error : {0:?}")]
+    Io(#[from] io::Error),
+
+    #[error("InvalidBencode - error : {0:?}")]
+    InvalidBencode(#[from] serde_bencode::Error),
+
+    #[error("InvalidData - reason : {reason:?}")]
+    InvalidData { reason: &'static str },
+}
+
+/// The changes made to a torrent while it runs, which are kept on the disk as a bencoded
+/// dictionary so that they're still there the next time the torrent is added
+///
+/// Keys of the dictionary :
+/// trackers    List of tiers, each one being a list of tracker URLs, left out until the
+///             trackers of the ".torrent" file are edited
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ResumeData {
+    /// URLs of the trackers tier by tier, "None" if the ones of the ".torrent" file are used
+    pub trackers: Option<Vec<Vec<String>>>,
+}
+
+impl ResumeData {
+    /// Path of the resume data of the torrent with the given info hash, saved inside of the given
+    /// download directory
+    ///
+    /// <directory>/.hyperblow/<info hash in hex>.resume
+    pub fn path(directory: &String, info_hash: &[u8]) -> PathBuf {
+        let info_hash: String = info_hash.iter().map(|byte| format!("{byte:02x}")).collect();
+        PathBuf::from(directory).join(RESUME_DIRECTORY).join(format!("{info_hash}.resume"))
+    }
+
+    /// Reads the resume data from the given path, there's no resume data if the file doesn't
+    /// exist, i.e the torrent is added for the first time
+    pub async fn load(path: &PathBuf) -> Result<Self, ResumeError> {
+        match fs::read(path).await {
+            Ok(v) => Self::from(&v),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the resume data to the given path, a temporary file is written first and then
+    /// renamed, so that the resume data isn't left half written
+    pub async fn save(&self, path: &PathBuf) -> Result<(), ResumeError> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).await?;
+        }
+        let temp_path = path.with_extension("resume.tmp");
+        fs::write(&temp_path, self.to_bytes()?).await?;
+        fs::rename(&temp_path, path).await?;
+        Ok(())
+    }
+
+    /// Creates a ResumeData from the given bencoded dictionary
+    pub fn from(v: &[u8]) -> Result<Self, ResumeError> {
+        let dict = match serde_bencode::from_bytes::<Value>(v)? {
+            Value::Dict(dict) => dict,
+            _ => {
+                return Err(ResumeError::InvalidData {
+                    reason: "resume data is not a dictionary",
+                })
+            }
+        };
+
+        // Anything in the "trackers" that isn't of the expected type makes the resume data invalid,
+        // rather than some of the trackers being dropped
+        let trackers = match dict.get(b"trackers".as_ref()) {
+            Some(Value::List(tiers)) => {
+                let mut tracker_s = Vec::new();
+                for tier in tiers {
+                    let Value::List(urls) = tier else {
+                        return Err(ResumeError::InvalidData {
+                            reason: "tier is not a list",
+                        });
+                    };
+                    let mut _urls = Vec::new();
+                    for url in urls {
+                        let Value::Bytes(bytes) = url else {
+                            return Err(ResumeError::InvalidData {
+                                reason: "tracker is not a string",
+                            });
+                        };
+                        _urls.push(String::from_utf8_lossy(bytes).to_string());
+                    }
+                    tracker_s.push(_urls);
+                }
+                Some(tracker_s)
+            }
+            Some(_) => {
+                return Err(ResumeError::InvalidData {
+                    reason: "trackers is not a list",
+                })
+            }
+            None => None,
+        };
+
+        Ok(ResumeData {
+            trackers,
+        })
+    }
+
+    /// Bencodes the resume data into a dictionary
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ResumeError> {
+        let mut dict = HashMap::new();
+        if let Some(ref trackers) = self.trackers {
+            let tiers = trackers
+                .iter()
+                .map(|tier| Value::List(tier.iter().map(|url| Value::Bytes(url.clone().into_bytes())).collect()))
+                .collect();
+            dict.insert(b"trackers".to_vec(), Value::List(tiers));
+        }
+        Ok(serde_bencode::to_bytes(&Value::Dict(dict))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn resume() -> ResumeData {
+        ResumeData {
+            trackers: Some(vec![
+                vec!["udp://tracker.example.org:6969/announce".to_string()],
+                vec![
+                    "http://a.example.org/announce".to_string(),
+                    "https://b.example.org/announce".to_string(),
+                ],
+            ]),
+        }
+    }
+
+    #[test]
+    fn path_is_inside_of_the_download_directory() {
+        let path = ResumeData::path(&"downloads".to_string(), &[0xab, 0x01]);
+        assert_eq!(path, PathBuf::from("downloads/.hyperblow/ab01.resume"));
+    }
+
+    #[test]
+    fn resume_data_round_trip() {
+        let bytes = resume().to_bytes().unwrap();
+        assert_eq!(ResumeData::from(&bytes).unwrap(), resume());
+
+        let bytes = ResumeData::default().to_bytes().unwrap();
+        assert_eq!(bytes, b"de");
+        assert_eq!(ResumeData::from(&bytes).unwrap(), ResumeData::default());
+    }
+
+    #[tokio::test]
+    async fn saved_resume_data_is_loaded_back() {
+        let directory = temp_dir().join(format!("hyperblow-resume-{}", std::process::id()));
+        let path = ResumeData::path(&directory.to_string_lossy().to_string(), &[1; 20]);
+
+        // There's no resume data before the torrent has ever been saved
+        assert_eq!(ResumeData::load(&path).await.unwrap(), ResumeData::default());
+        resume().save(&path).await.unwrap();
+        assert_eq!(ResumeData::load(&path).await.unwrap(), resume());
+        let _ = fs::remove_dir_all(&directory).await;
+    }
+
+    #[test]
+    fn invalid_resume_data_is_refused() {
+        for (bytes, expected_reason) in [
+            (b"l8:trackerse".as_ref(), "resume data is not a dictionary"),
+            (b"d8:trackers3:urle", "trackers is not a list"),
+            (b"d8:trackersl3:urlee", "tier is not a list"),
+            (b"d8:trackersll3:urli1eeee", "tracker is not a string"),
+        ] {
+            match ResumeData::from(bytes) {
+                Err(ResumeError::InvalidData {
+                    reason,
+                }) => assert_eq!(reason, expected_reason),
+                result => panic!("{result:?}"),
+            }
+        }
+        assert!(matches!(ResumeData::from(b"d8:trackers"), Err(ResumeError::InvalidBencode(_))));
+    }
+}
@@ -1,12 +1,26 @@
 #![feature(concat_idents)]
 
-use crate::core::{peer::Peer, tracker::Tracker, File};
+use crate::core::{
+    dht::{routing_table::RoutingTable, Dht},
+    lsd::Lsd,
+    peer::{extension::Extensions, picker::PiecePicker, Peer, PeerState},
+    storage::Storage,
+    tracker::{udp_service::UdpTrackerService, Tracker},
+    File,
+};
 use crossbeam::atomic::AtomicCell;
 use hyperblow::parser::torrent_parser::FileMeta;
 use paste::paste;
+use reqwest::Url;
+use sha1::{Digest, Sha1};
 
-use std::{cell::Cell, sync::Arc};
-use tokio::sync::{Mutex, RwLock};
+use std::{cell::Cell, collections::HashMap, sync::Arc};
+use tokio::sync::{Mutex, Notify, RwLock};
+
+/// Bytes left sent to the trackers while the size of the torrent isn't known, i.e before the
+/// "info" field of a torrent added through a magnet URI is received, so that it doesn't look like
+/// we're seeding
+const UNKNOWN_BYTES_LEFT: usize = 16 * 1024;
 
 /// Used to generate getter and setter for Cell<T> types
 /// Eg.
@@ -37,12 +51,15 @@ macro_rules! cell_get_set {
     };
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DownState {
     /// It means the torrent is currently downloading
     Downloading,
     /// It means the download of the torrent is currenlty stopped
     Stopped,
+    /// It means the "info" field of a torrent added through a magnet URI is being fetched from the
+    /// peers, nothing gets downloaded until it's received
+    FetchingMetadata,
     /// It means the state is unknown, it might be requesting data from some tracker or doing
     /// something else, but not downloading the data of the torrent and not in a paused state
     Unknown,
@@ -55,7 +72,7 @@ pub enum DownState {
 pub struct State {
     pub meta_info: FileMeta,
 
-    pub d_state: DownState,
+    pub d_state: AtomicCell<DownState>,
 
     /// The entire file tree of the torrent files to be downloaded
     pub file_tree: Option<Arc<Mutex<File>>>,
@@ -64,7 +81,8 @@ pub struct State {
     pub trackers: Arc<RwLock<Vec<Vec<Arc<Tracker>>>>>,
 
     /// A list of UDP ports being used by this torrent being downloaded
-    /// The port at index 0, is the port used for UDP Trackers and it always exists
+    /// The port at index 0, is the port used for UDP Trackers, shared by all the torrents of the
+    /// engine, once the torrent has been run
     pub udp_ports: Arc<Mutex<Vec<u16>>>,
 
     /// A list of TCP ports being used by this torrent being downloaded
@@ -73,11 +91,15 @@ pub struct State {
     /// Info hash of the torrent
     pub info_hash: Vec<u8>,
 
+    /// Peer ID of this client, sent to the trackers in the announce and to the peers in the
+    /// Handshake
+    pub peer_id: [u8; 20],
+
     /// Stores the hash of each piece by its exact index extracted out of bencode encoded ".torrent" file
     pub pieces_hash: Vec<[u8; 20]>,
 
     /// All the peers of the current session
-    pub peers: Arc<Mutex<Vec<Peer>>>,
+    pub peers: Arc<Mutex<Vec<Arc<Peer>>>>,
 
     /// Total session time that torrent has been active in seconds
     pub uptime: AtomicCell<usize>,
@@ -87,6 +109,45 @@ pub struct State {
 
     // Total downloaded pieces
     pub pieces_downloaded: AtomicCell<usize>,
+
+    /// Total bytes uploaded to the peers
+    pub bytes_uploaded: AtomicCell<usize>,
+
+    /// Rate at which we're uploading to the peers in "bytes/second"
+    pub upload_speed: AtomicCell<usize>,
+
+    /// Decides which blocks are requested from which peer, shared by all the peers of the session
+    pub picker: Mutex<PiecePicker>,
+
+    /// Writes the verified pieces on the disk
+    pub storage: Storage,
+
+    /// No of block requests kept outstanding with each peer at a time
+    pub max_outstanding_requests: AtomicCell<usize>,
+
+    /// No of peers unchoked at a time, including the optimistic unchoke
+    pub upload_slots: AtomicCell<usize>,
+
+    /// No of peers this torrent can be connected with at a time
+    pub max_connections: AtomicCell<usize>,
+
+    /// Announce to every tracker of every tier at once, rather than to one tracker at a time as
+    /// BEP12 asks for, it takes effect the next time the trackers are started
+    pub announce_to_all: AtomicCell<bool>,
+
+    /// The metadata built from the "info" field received from the peers, for a torrent added
+    /// through a magnet URI, it's taken by [State::metadata]
+    pub metadata: Mutex<Option<FileMeta>>,
+
+    /// Notified once the "info" field has been received, see [State::set_metadata]
+    pub metadata_received: Notify,
+
+    /// The extensions of the Extension Protocol the torrent supports
+    pub extensions: Extensions,
+
+    /// The DHT node of the engine, the peers are told about its port and the DHT nodes of the
+    /// peers are added into it, it's "None" until the torrent is run and for a private torrent
+    pub dht: Mutex<Option<Arc<Dht>>>,
 }
 
 impl State {
@@ -100,9 +161,288 @@ impl State {
         // Code to resume the download
     }
 
+    /// Adds the peer into the session, unless a peer with the same socket address is already in
+    /// the session and its connection hasn't been closed, gives back whether it was added or not
+    pub async fn add_peer(&self, peer: Arc<Peer>) -> bool {
+        let mut peers = self.peers.lock().await;
+        if let Some(index) = peers.iter().position(|existing_peer| existing_peer.socket_adr == peer.socket_adr) {
+            if peers[index].peer_state().await != PeerState::Disconnected {
+                return false;
+            }
+            peers.remove(index);
+        }
+        peers.push(peer);
+        true
+    }
+
+    /// Removes the peer from the session once its connection has been closed, so that the
+    /// disconnected peers don't pile up
+    pub async fn remove_peer(&self, peer: &Peer) {
+        self.peers
+            .lock()
+            .await
+            .retain(|existing_peer| !std::ptr::eq(Arc::as_ptr(existing_peer), peer));
+    }
+
+    /// No of peers of the session whose connection hasn't been closed yet, including the ones
+    /// still trying to connect
+    pub async fn connections_count(&self) -> usize {
+        let mut connections_count = 0;
+        for peer in self.peers.lock().await.iter() {
+            if peer.peer_state().await != PeerState::Disconnected {
+                connections_count += 1;
+            }
+        }
+        connections_count
+    }
+
+    /// Finds out the pieces that fall under the files that are to be downloaded, according to
+    /// [File::should_download] of the file tree, and lets the picker know about them
+    ///
+    /// It must be called again whenever [File::should_download] of any file gets changed
+    pub async fn update_wanted_pieces(&self) {
+        let piece_length = self.meta_info.info.piece_length.unwrap_or(0) as u64;
+        let files: Vec<(Vec<String>, u64)> = match self.meta_info.info.files {
+            Some(ref files) => files.iter().map(|file| (file.path.clone(), file.length as u64)).collect(),
+            None => vec![(Vec::new(), self.meta_info.getTotalLength() as u64)],
+        };
+
+        let mut wanted = vec![false; self.pieces_hash.len()];
+        let mut offset = 0;
+        for (path, length) in files {
+            let should_download = match self.file_tree {
+                Some(ref file_tree) => file_tree.lock().await.should_download_path(&path).await,
+                None => true,
+            };
+
+            // A piece is wanted if even a single byte of it falls under a wanted file
+            if should_download && length > 0 && piece_length > 0 {
+                let first_piece = offset / piece_length;
+                let last_piece = (offset + length - 1) / piece_length;
+                for index in first_piece..=last_piece {
+                    if let Some(wanted) = wanted.get_mut(index as usize) {
+                        *wanted = true;
+                    }
+                }
+            }
+            offset += length;
+        }
+
+        self.picker.lock().await.set_wanted(wanted);
+    }
+
+    /// Total bytes that are yet to be downloaded, out of the entire torrent
+    pub fn bytes_left(&self) -> usize {
+        let total_length = self.meta_info.getTotalLength() as usize;
+        if !self.has_metadata() && total_length == 0 {
+            return UNKNOWN_BYTES_LEFT;
+        }
+        total_length.saturating_sub(self.bytes_complete())
+    }
+
+    /// Whether the "info" field of the torrent is there, it isn't for a torrent added through a
+    /// magnet URI until it's received from the peers
+    pub fn has_metadata(&self) -> bool {
+        self.meta_info.hasInfo()
+    }
+
+    /// Hands over the "info" field received from the peers, it's only taken if its SHA1 hash is
+    /// the info hash of the torrent and it can be parsed, gives back whether it was taken
+    ///
+    /// The trackers of the torrent are kept in the metadata built from it
+    pub async fn set_metadata(&self, raw_info: Vec<u8>) -> bool {
+        if self.has_metadata() || Sha1::digest(&raw_info).as_slice() != self.info_hash.as_slice() {
+            return false;
+        }
+        let announce_list = self.meta_info.announce_list.clone().unwrap_or_default();
+        match FileMeta::fromRawInfo(raw_info, announce_list) {
+            Ok(meta_info) => {
+                *self.metadata.lock().await = Some(meta_info);
+                self.metadata_received.notify_one();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Waits for the "info" field to be received from the peers, see [State::set_metadata], and
+    /// gives back the metadata built from it
+    pub async fn metadata(&self) -> FileMeta {
+        loop {
+            if let Some(meta_info) = self.metadata.lock().await.take() {
+                return meta_info;
+            }
+            self.metadata_received.notified().await;
+        }
+    }
+
     cell_get_set!(uptime: usize);
 
     cell_get_set!(bytes_complete: usize);
 
     cell_get_set!(pieces_downloaded: usize);
+
+    cell_get_set!(bytes_uploaded: usize);
+
+    cell_get_set!(d_state: DownState);
+
+    cell_get_set!(upload_speed: usize);
+
+    cell_get_set!(max_outstanding_requests: usize);
+
+    cell_get_set!(upload_slots: usize);
+
+    cell_get_set!(max_connections: usize);
+
+    cell_get_set!(announce_to_all: bool);
+}
+
+/// A thread shareable state of the entire engine, shared by all the torrents of the engine
+#[derive(Debug)]
+pub struct EngineState {
+    /// No of peers unchoked at a time across all the torrents
+    pub upload_slots: AtomicCell<usize>,
+
+    /// No of upload slots being used by each torrent, by its info hash
+    upload_slots_used: Mutex<HashMap<Vec<u8>, usize>>,
+
+    /// No of peers all the torrents together can be connected with at a time
+    pub max_connections: AtomicCell<usize>,
+
+    /// TCP port on which the peers can connect to us, it's 0 until the listener has been bound
+    pub listen_port: AtomicCell<u16>,
+
+    /// Directory the torrents are downloaded into, the resume data of the torrents and the routing
+    /// table of the DHT are kept inside of it as well, see
+    /// [RESUME_DIRECTORY](crate::core::resume::RESUME_DIRECTORY)
+    pub download_directory: String,
+
+    /// State of all the running torrents, by their info hash
+    torrents: Mutex<HashMap<Vec<u8>, Arc<State>>>,
+
+    /// Sends the requests of the UDP trackers of all the torrents and receives their responses
+    pub udp_trackers: UdpTrackerService,
+
+    /// The DHT node all the torrents look up their peers through
+    pub dht: Arc<Dht>,
+
+    /// Finds the peers of all the torrents on the local network
+    pub lsd: Lsd,
+}
+
+impl EngineState {
+    pub fn new(upload_slots: usize, max_connections: usize, download_directory: String) -> Self {
+        Self {
+            upload_slots: AtomicCell::new(upload_slots),
+            upload_slots_used: Mutex::new(HashMap::new()),
+            max_connections: AtomicCell::new(max_connections),
+            listen_port: AtomicCell::new(0),
+            torrents: Mutex::new(HashMap::new()),
+            udp_trackers: UdpTrackerService::default(),
+            dht: Arc::new(Dht::new(Some(RoutingTable::path(&download_directory)))),
+            lsd: Lsd::default(),
+            download_directory,
+        }
+    }
+
+    /// Makes the torrent reachable by the peers connecting to us, and lets it know about the port
+    /// we're listening on, so that it can be announced to the trackers, the port the UDP trackers
+    /// are reached through and the DHT node, unless the torrent is private
+    pub async fn register_torrent(&self, state: Arc<State>) {
+        let udp_port = self.udp_trackers.port();
+        if udp_port != 0 {
+            let mut udp_ports = state.udp_ports.lock().await;
+            if !udp_ports.contains(&udp_port) {
+                udp_ports.insert(0, udp_port);
+            }
+        }
+
+        let listen_port = self.listen_port();
+        if listen_port != 0 {
+            let mut tcp_ports = state.tcp_ports.lock().await;
+            if !tcp_ports.contains(&listen_port) {
+                tcp_ports.insert(0, listen_port);
+            }
+        }
+
+        if self.dht.port() != 0 && !state.meta_info.isPrivate() {
+            *state.dht.lock().await = Some(self.dht.clone());
+        }
+        self.torrents.lock().await.insert(state.info_hash.clone(), state);
+    }
+
+    /// Makes the torrent with the given info hash unreachable by the peers connecting to us
+    pub async fn unregister_torrent(&self, info_hash: &[u8]) {
+        self.torrents.lock().await.remove(info_hash);
+    }
+
+    /// Gives the state of the running torrent with the given info hash
+    pub async fn torrent(&self, info_hash: &[u8]) -> Option<Arc<State>> {
+        self.torrents.lock().await.get(info_hash).cloned()
+    }
+
+    /// Gives the state of all the running torrents
+    pub async fn torrents(&self) -> Vec<Arc<State>> {
+        self.torrents.lock().await.values().cloned().collect()
+    }
+
+    /// Gives the state of all the running torrents that have a tracker with the given address
+    pub async fn torrents_with_tracker(&self, address: &Url) -> Vec<Arc<State>> {
+        let torrents: Vec<Arc<State>> = self.torrents.lock().await.values().cloned().collect();
+        let mut torrents_with_tracker = Vec::new();
+        for torrent in torrents {
+            let has_tracker = torrent
+                .trackers
+                .read()
+                .await
+                .iter()
+                .flatten()
+                .any(|tracker| tracker.address == *address);
+            if has_tracker {
+                torrents_with_tracker.push(torrent);
+            }
+        }
+        torrents_with_tracker
+    }
+
+    /// Whether the torrent can be connected with one more peer, without going over either its own
+    /// connection limit or the connection limit of the entire engine
+    pub async fn can_connect(&self, state: &State) -> bool {
+        if state.connections_count().await >= state.max_connections() {
+            return false;
+        }
+
+        let torrents: Vec<Arc<State>> = self.torrents.lock().await.values().cloned().collect();
+        let mut connections_count = 0;
+        for torrent in torrents {
+            connections_count += torrent.connections_count().await;
+        }
+        connections_count < self.max_connections()
+    }
+
+    /// Gives the torrent with the given info hash as many of the upload slots it wants as there
+    /// are left, after the ones used by the other torrents, the slots used by the torrent
+    /// previously are given up first
+    pub async fn claim_upload_slots(&self, info_hash: &[u8], wanted: usize) -> usize {
+        let mut upload_slots_used = self.upload_slots_used.lock().await;
+        let used_by_others: usize = upload_slots_used
+            .iter()
+            .filter(|(hash, _)| hash.as_slice() != info_hash)
+            .map(|(_, used)| *used)
+            .sum();
+        let claimed = wanted.min(self.upload_slots().saturating_sub(used_by_others));
+        upload_slots_used.insert(info_hash.to_vec(), claimed);
+        claimed
+    }
+
+    /// Gives up all the upload slots used by the torrent with the given info hash
+    pub async fn release_upload_slots(&self, info_hash: &[u8]) {
+        self.upload_slots_used.lock().await.remove(info_hash);
+    }
+
+    cell_get_set!(upload_slots: usize);
+
+    cell_get_set!(max_connections: usize);
+
+    cell_get_set!(listen_port: u16);
 }
@@ -0,0 +1,199 @@
+use hyperblow::parser::torrent_parser::FileMeta;
+use std::{
+    io::SeekFrom,
+    path::{Component, Path, PathBuf},
+};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::{self, AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+
+/// Joins the given part of a path taken from the torrent onto the given path, keeping only the
+/// normal components of it, i.e a "..", a ".", a root or a prefix such as "C:" is dropped, so
+/// that a malicious torrent can't write outside of the download directory
+fn safe_join(path: PathBuf, part: &str) -> PathBuf {
+    Path::new(part).components().fold(path, |path, component| match component {
+        Component::Normal(component) => path.join(component),
+        _ => path,
+    })
+}
+
+/// A file of the torrent as it's laid out on the disk
+#[derive(Debug)]
+struct StorageFile {
+    /// Path of the file, including the download directory
+    path: PathBuf,
+
+    /// Offset of the first byte of the file, from the first byte of the entire torrent
+    offset: u64,
+
+    /// Size of the file in bytes
+    length: u64,
+}
+
+/// Maps the pieces of the torrent onto the files of the torrent and writes them on the disk
+///
+/// All the files of a torrent are treated as one long stream of bytes, in the order they're
+/// listed in the ".torrent" file, and a piece is just a slice of that stream. So a single piece
+/// can end up in a part of one file or span across multiple files.
+#[derive(Debug)]
+pub struct Storage {
+    /// Files of the torrent, in the order of the ".torrent" file
+    files: Vec<StorageFile>,
+
+    /// Size of each piece in bytes, except the last piece
+    piece_length: u64,
+}
+
+impl Storage {
+    /// Creates a Storage that saves the files of the torrent inside of the given directory
+    ///
+    /// Single file mode : <directory>/<name>
+    /// Multiple file mode : <directory>/<name>/<path>
+    ///
+    /// The name and the path come from the torrent, so anything in them that could lead outside
+    /// of the directory is dropped, see [safe_join]
+    pub fn new(meta: &FileMeta, directory: &String) -> Self {
+        let name = meta.info.name.clone().unwrap_or_default();
+        let root = safe_join(PathBuf::from(directory), &name);
+
+        let mut files = Vec::new();
+        match meta.info.files {
+            Some(ref torrent_files) => {
+                let mut offset = 0;
+                for file in torrent_files {
+                    let path = file.path.iter().fold(root.clone(), |path, component| safe_join(path, component));
+                    let length = file.length as u64;
+                    files.push(StorageFile {
+                        path,
+                        offset,
+                        length,
+                    });
+                    offset += length;
+                }
+            }
+            None => files.push(StorageFile {
+                path: root,
+                offset: 0,
+                length: meta.info.length.unwrap_or(0) as u64,
+            }),
+        }
+
+        Self {
+            files,
+            piece_length: meta.info.piece_length.unwrap_or(0) as u64,
+        }
+    }
+
+    /// Writes the data of the verified piece at the given index into all the files it falls
+    /// under, creating the files and their directories if they don't exist yet
+    pub async fn write_piece(&self, index: u32, data: &[u8]) -> io::Result<()> {
+        let piece_begin = index as u64 * self.piece_length;
+        let piece_end = piece_begin + data.len() as u64;
+
+        for file in self.files.iter() {
+            let file_end = file.offset + file.length;
+            if file_end <= piece_begin || file.offset >= piece_end {
+                continue;
+            }
+
+            // The part of the piece that falls under this file
+            let begin = piece_begin.max(file.offset);
+            let end = piece_end.min(file_end);
+            let bytes = &data[(begin - piece_begin) as usize..(end - piece_begin) as usize];
+
+            if let Some(parent) = file.path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            let mut handle = OpenOptions::new().write(true).create(true).truncate(false).open(&file.path).await?;
+            handle.seek(SeekFrom::Start(begin - file.offset)).await?;
+            handle.write_all(bytes).await?;
+            handle.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Reads "length" bytes of the piece at the given index, starting at the byte offset "begin"
+    /// within the piece, from all the files it falls under
+    pub async fn read_block(&self, index: u32, begin: u32, length: u32) -> io::Result<Vec<u8>> {
+        let block_begin = index as u64 * self.piece_length + begin as u64;
+        let block_end = block_begin + length as u64;
+        let mut data = vec![0_u8; length as usize];
+
+        for file in self.files.iter() {
+            let file_end = file.offset + file.length;
+            if file_end <= block_begin || file.offset >= block_end {
+                continue;
+            }
+
+            // The part of the block that falls under this file
+            let begin = block_begin.max(file.offset);
+            let end = block_end.min(file_end);
+            let bytes = &mut data[(begin - block_begin) as usize..(end - block_begin) as usize];
+
+            let mut handle = OpenOptions::new().read(true).open(&file.path).await?;
+            handle.seek(SeekFrom::Start(begin - file.offset)).await?;
+            handle.read_exact(bytes).await?;
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bencoded string
+    fn string(v: &str) -> String {
+        format!("{}:{v}", v.len())
+    }
+
+    /// Metadata of a torrent with the files at the given paths, 1 byte each
+    fn meta(name: &str, paths: &[&[&str]]) -> FileMeta {
+        let files: String = paths
+            .iter()
+            .map(|path| {
+                let path: String = path.iter().map(|component| string(component)).collect();
+                format!("d6:lengthi1e4:pathl{path}ee")
+            })
+            .collect();
+        let torrent = format!(
+            "d8:announce{}4:infod5:filesl{files}e4:name{}12:piece lengthi16384e6:pieces20:{}ee",
+            string("udp://tracker.example.org:6969"),
+            string(name),
+            "0".repeat(20)
+        );
+        FileMeta::fromRawTorrentFile(torrent.into_bytes()).unwrap()
+    }
+
+    #[test]
+    fn files_stay_inside_of_the_directory() {
+        let meta = meta(
+            "../../name",
+            &[
+                &["..", "..", "etc", "passwd"],
+                &["/etc", "shadow"],
+                &["a/../../b"],
+                &[".", "c", ".."],
+                &["dir", "file"],
+            ],
+        );
+        let storage = Storage::new(&meta, &"downloads".to_owned());
+
+        let paths: Vec<PathBuf> = storage.files.iter().map(|file| file.path.clone()).collect();
+        let root = PathBuf::from("downloads").join("name");
+        assert_eq!(
+            paths,
+            vec![
+                root.join("etc").join("passwd"),
+                root.join("etc").join("shadow"),
+                root.join("a").join("b"),
+                root.join("c"),
+                root.join("dir").join("file"),
+            ]
+        );
+        for path in paths {
+            assert!(path.components().all(|component| matches!(component, Component::Normal(_))));
+        }
+    }
+}
@@ -10,11 +10,23 @@
 // TODO : Create the DataStructure in such a way that it could resume the download later on as well
 // TODO : Return error on error generated rather than this Option<T> on TorrentFile::new()
 #![allow(unused_must_use)]
-use super::peer::Peer;
+use super::peer::{
+    choker::{Choker, DEFAULT_UPLOAD_SLOTS},
+    extension::Extensions,
+    listener::DEFAULT_MAX_CONNECTIONS,
+    metadata::UtMetadata,
+    pex::UtPex,
+    picker::PiecePicker,
+    DisconnectReason, Peer, PeerSource, DEFAULT_MAX_OUTSTANDING_REQUESTS,
+};
 use crate::{
     core::{
-        state::{DownState, State},
-        tracker::Tracker,
+        dht::routing_table::{NodeId, K},
+        generate_peer_id,
+        resume::{ResumeData, ResumeError},
+        state::{DownState, EngineState, State},
+        storage::Storage,
+        tracker::{Tracker, TrackerState},
         File,
     },
     ACell, ArcMutex, ArcRwLock,
@@ -22,24 +34,61 @@ use crate::{
 use crossbeam::atomic::AtomicCell;
 use futures::future::{join, join_all};
 use hyperblow::parser::torrent_parser::FileMeta;
-use std::{cell::Cell, sync::Arc};
+use rand::{seq::SliceRandom, thread_rng};
+use reqwest::Url;
+use std::{cell::Cell, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use thiserror::Error;
 use tokio::{
     join,
-    net::UdpSocket,
     sync::{
         mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
-        Mutex, RwLock,
+        Mutex, Notify, RwLock,
     },
     task::JoinHandle,
+    time::{interval_at, sleep, Instant},
 };
+use tokio_util::sync::CancellationToken;
 
 const TRANS_ID: i32 = 10;
 
+/// Time given to a tracker to be announced to, before the next tracker is tried, when the
+/// trackers are announced to tier by tier
+const TRACKER_RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often the tracker being announced to is checked for having responded or failed
+const TRACKER_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time waited before trying every tier again, once every tracker of every tier has failed
+const TIERS_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time between the lookups of the torrent on the DHT, each one announcing us to the nodes
+const DHT_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Time waited before looking up the torrent on the DHT again, while the DHT has too few nodes
+/// for the lookup to get anywhere, e.g while it's still bootstrapping
+const DHT_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Debug)]
 pub enum TError {
     NoTrackerResolved,
 }
 
+#[derive(Error, Debug)]
+pub enum TrackerEditError {
+    #[error("InvalidUrl - reason : {reason:?}")]
+    InvalidUrl { reason: String },
+
+    #[error("AlreadyAdded - url : {url:?}")]
+    AlreadyAdded { url: String },
+
+    #[error("NotFound - url : {url:?}")]
+    NotFound { url: String },
+
+    /// The edit was made, but it couldn't be saved to the resume data
+    #[error("Resume - error : {0:?}")]
+    Resume(#[from] ResumeError),
+}
+
 #[derive(Debug)]
 pub struct TorrentFile {
     /// Path of the torrent file
@@ -57,6 +106,9 @@ pub struct TorrentFile {
     /// here we are making changes in the state field continuosly
     pub state: Arc<State>,
 
+    /// State shared by all the torrents of the engine
+    engine_state: Arc<EngineState>,
+
     /// Trackers get the socket address of the peers.
     /// Let's say there are 20 trackers, we are communicating to,
     /// and when all of them have this UnboundedSender, then we can simply
@@ -64,61 +116,128 @@ pub struct TorrentFile {
     /// peers we can invoke run() method of the peer and
     /// store in the peers field of [Peers]
     peers_channel: (Arc<UnboundedSender<Peer>>, Arc<Mutex<UnboundedReceiver<Peer>>>),
+
+    /// Cancelled to stop running the torrent for good, i.e when it's removed
+    cancel: CancellationToken,
+
+    /// Cancelled to stop announcing to the trackers tier by tier, a new one is created every time
+    /// the trackers are started
+    trackers_cancel: Mutex<CancellationToken>,
+
+    /// Path of the resume data of the torrent, see [ResumeData::path]
+    resume_path: PathBuf,
+
+    /// The changes made to the torrent that are kept on the disk, such as the edited trackers
+    resume: Mutex<ResumeData>,
+
+    /// The trackers of the torrent this one has taken over, whose sessions the trackers of this
+    /// torrent carry on with once they're created, see [TorrentFile::take_over]
+    handed_over_trackers: Mutex<Vec<Arc<Tracker>>>,
 }
 
 struct Peers {
     peers: Vec<u32>,
 }
 
-/// TODO: Implement DHT(Distributed Hash Table) as well
 impl TorrentFile {
     /// It will try to parse the given the path of the torrent file and create a new data structure
     /// from the Torrent file
-    pub async fn new(path: &String) -> Option<Self> {
+    pub async fn new(path: &String, engine_state: Arc<EngineState>) -> Option<Self> {
         match FileMeta::fromTorrentFile(&path) {
             Ok(meta_info) => {
                 let info_hash = meta_info.generateInfoHash();
-                let pieces_hash = meta_info.getPiecesHash();
-                let pieces_count = pieces_hash.len();
-                let d_state = DownState::Unknown;
-                let file_tree = Some(Self::generateFileTree(&meta_info).await);
-                let trackers = ArcRwLock!(Vec::new());
-                let udp_ports = ArcMutex!(Vec::new());
-                let tcp_ports = ArcMutex!(Vec::new());
-                let peers = ArcMutex!(Vec::new());
-                let bytes_complete = ACell!(3000000000);
-                let pieces_downloaded = ACell!(120);
-                let uptime = ACell!(0);
-
-                let peers_channel = unbounded_channel::<Peer>();
-                let peers_channel = (Arc::new(peers_channel.0), ArcMutex!(peers_channel.1));
-
-                let state = Arc::new(State {
-                    pieces_downloaded,
-                    bytes_complete,
-                    meta_info,
-                    d_state,
-                    file_tree,
-                    trackers,
-                    udp_ports,
-                    tcp_ports,
-                    info_hash,
-                    pieces_hash,
-                    peers,
-                    uptime,
-                });
-
-                Some(Self {
-                    path: path.to_string(),
-                    pieces_count,
-                    state,
-                    peers_channel,
-                })
+                Some(Self::fromFileMeta(meta_info, info_hash, path, engine_state).await)
             }
             _ => None,
         }
     }
 
+    /// Creates a new data structure from the given metadata of the torrent, which may not have the
+    /// "info" field yet if the torrent was added through a magnet URI, see [FileMeta::hasInfo]
+    ///
+    /// path : The path of the torrent file, or the magnet URI the torrent was added through
+    pub async fn fromFileMeta(meta_info: FileMeta, info_hash: Vec<u8>, path: &String, engine_state: Arc<EngineState>) -> Self {
+        let peer_id = generate_peer_id();
+        let pieces_hash = meta_info.getPiecesHash();
+        let pieces_count = pieces_hash.len();
+        let d_state = ACell!(DownState::Unknown);
+        let file_tree = Some(Self::generateFileTree(&meta_info).await);
+        let trackers = ArcRwLock!(Vec::new());
+        let udp_ports = ArcMutex!(Vec::new());
+        let tcp_ports = ArcMutex!(Vec::new());
+        let peers = ArcMutex!(Vec::new());
+        let bytes_complete = ACell!(0);
+        let pieces_downloaded = ACell!(0);
+        let bytes_uploaded = ACell!(0);
+        let upload_speed = ACell!(0);
+        let uptime = ACell!(0);
+        let piece_length = meta_info.info.piece_length.unwrap_or(0) as u64;
+        let total_length = meta_info.getTotalLength() as u64;
+        let picker = Mutex::new(PiecePicker::new(piece_length, total_length, pieces_count));
+        let storage = Storage::new(&meta_info, &engine_state.download_directory);
+        let resume_path = ResumeData::path(&engine_state.download_directory, &info_hash);
+        // A resume data that can't be read is no different than having none at all
+        let resume = Mutex::new(ResumeData::load(&resume_path).await.unwrap_or_default());
+        let max_outstanding_requests = ACell!(DEFAULT_MAX_OUTSTANDING_REQUESTS);
+        let upload_slots = ACell!(DEFAULT_UPLOAD_SLOTS);
+        let max_connections = ACell!(DEFAULT_MAX_CONNECTIONS);
+        let announce_to_all = ACell!(false);
+        let metadata = Mutex::default();
+        let metadata_received = Notify::new();
+        let dht = Mutex::default();
+
+        let peers_channel = unbounded_channel::<Peer>();
+        let peers_channel = (Arc::new(peers_channel.0), ArcMutex!(peers_channel.1));
+
+        let mut extensions = Extensions::default();
+        extensions.register(Arc::new(UtMetadata::default()));
+        // The peers of a private torrent must only come from its trackers
+        if !meta_info.isPrivate() {
+            extensions.register(Arc::new(UtPex::new(peers_channel.0.clone())));
+        }
+
+        let state = Arc::new(State {
+            pieces_downloaded,
+            bytes_complete,
+            bytes_uploaded,
+            upload_speed,
+            meta_info,
+            d_state,
+            file_tree,
+            trackers,
+            udp_ports,
+            tcp_ports,
+            info_hash,
+            peer_id,
+            pieces_hash,
+            peers,
+            uptime,
+            picker,
+            storage,
+            max_outstanding_requests,
+            upload_slots,
+            max_connections,
+            announce_to_all,
+            metadata,
+            metadata_received,
+            extensions,
+            dht,
+        });
+
+        Self {
+            path: path.to_string(),
+            pieces_count,
+            state,
+            engine_state,
+            peers_channel,
+            cancel: CancellationToken::new(),
+            trackers_cancel: Mutex::default(),
+            resume_path,
+            resume,
+            handed_over_trackers: Mutex::default(),
+        }
+    }
+
     //// NOTE : This function is assumed to be called once in the download session
     ///// Creates objects of [Tracker] by extracting out all the Trackers from "announce" and "announce-list" field
     ///// and then resolves their address through DNS lookup
@@ -176,127 +295,429 @@ impl TorrentFile {
         File::new(meta, &".".to_owned()).await.unwrap()
     }
 
-    async fn getUDPSocket(&self) -> Arc<UdpSocket> {
-        // TODO : Currently this function exhaustively checks for each port and tries to
-        // give one of the ports incrementing from 6881
-        let mut port = 6881;
-        // TODO: Get a list of all the ports used by the entire application as well,
-        // i.e store a global use  of entire sockets somewhere in a global state
-        //
-        // Gets a port that is not used by the application
-        loop {
-            let adr = format!("0.0.0.0:{port}");
-            match UdpSocket::bind(adr).await {
-                Ok(socket) => {
-                    let mut udp_ports = self.state.udp_ports.lock().await;
-                    udp_ports.push(port);
-                    return Arc::new(socket);
-                }
-                Err(e) => {
-                    //println!("{:?}", e.to_string());
-                    port = port + 1;
-                }
-            }
-        }
-    }
-
-    // Running of trackers is divided into two sub tasks
-    // 1. Sending trackers requests
-    // 2. Receiving trackers response
-    //
-    // One is, sending Trackers requests and other is
-    // If there are 'n' no of trackers, then
-    // In the first async task of 'req', it spawns 'n' no of tasks within itself, and these each task make a
-    // request and for every response that comes in the socket, its handled by second async
-    // task of 'res
-    async fn runTrackers(&self, socket: Arc<UdpSocket>) {
-        // Step 1 : Generate "Tracker" instance from all the tracker's URL in "announce" or
-        // "announce_list" field of FileMeta and spawm a tokio task internally to call each tracker's run method
+    /// Generates a [Tracker] instance from all the tracker's URL in "announce" or "announce_list"
+    /// field of FileMeta, or from the ones in the resume data if they were edited, and starts
+    /// announcing to them
+    ///
+    /// The requests and responses of the UDP trackers go through the
+    /// [UdpTrackerService](crate::core::tracker::udp_service::UdpTrackerService) of the engine
+    async fn runTrackers(&self) {
         let trackers: Vec<Vec<Arc<Tracker>>> = {
             let mut tracker_s = Vec::default();
-            if let Some(ref announce_list_s) = self.state.meta_info.announce_list {
-                for announce_list in announce_list_s {
-                    let mut _trackers = Vec::new();
-                    for announce_url in announce_list {
-                        if let Ok(tracker) = Tracker::new(announce_url, self.state.clone(), self.peers_channel.0.clone()) {
-                            let tracker = Arc::new(tracker);
-                            let tracker_cloned = tracker.clone();
-                            tokio::spawn(async move {
-                                tracker_cloned.resolveTracker().await;
-                            });
-                            _trackers.push(tracker);
-                        }
+            for announce_list in self.tracker_urls().await {
+                let mut _trackers = Vec::new();
+                for announce_url in announce_list.iter() {
+                    if let Ok(tracker) = self.newTracker(announce_url) {
+                        _trackers.push(Arc::new(tracker));
                     }
-                    tracker_s.push(_trackers);
                 }
-            } else {
-                let ref announce_url = self.state.meta_info.announce;
-                if let Ok(tracker) = Tracker::new(announce_url, self.state.clone(), self.peers_channel.0.clone()) {
-                    tracker_s.push(vec![Arc::new(tracker)])
+                // BEP12 asks for the trackers of each tier to be tried in a random order
+                _trackers.shuffle(&mut thread_rng());
+                if !_trackers.is_empty() {
+                    tracker_s.push(_trackers);
                 }
             }
             tracker_s
         };
+        for handed_over in self.handed_over_trackers.lock().await.drain(..) {
+            if let Some(tracker) = trackers.iter().flatten().find(|t| t.address == handed_over.address) {
+                tracker.take_over(&handed_over).await;
+            }
+        }
         *self.state.trackers.write().await = trackers;
+        self.spawnTrackers().await;
+    }
+
+    /// URLs of the trackers tier by tier, the ones saved in the resume data if the trackers were
+    /// edited, otherwise the ones of the "announce_list" field of FileMeta, or of the "announce"
+    /// field if there's no "announce_list" as BEP12 asks for
+    async fn tracker_urls(&self) -> Vec<Vec<String>> {
+        if let Some(ref trackers) = self.resume.lock().await.trackers {
+            return trackers.clone();
+        }
+        match self.state.meta_info.announce_list {
+            Some(ref announce_list_s) => announce_list_s.clone(),
+            None => vec![vec![self.state.meta_info.announce.clone()]],
+        }
+    }
+
+    /// Creates a [Tracker] of this torrent from the given URL
+    fn newTracker(&self, announce_url: &String) -> Result<Tracker, Box<dyn std::error::Error>> {
+        Tracker::new(
+            announce_url,
+            self.state.clone(),
+            self.engine_state.clone(),
+            self.peers_channel.0.clone(),
+        )
+    }
+
+    /// Adds a tracker with the given URL at the end of the given tier, a new tier is created after
+    /// the last one if the tier doesn't exist yet
+    ///
+    /// The tracker is announced to right away if every tracker is announced to at once, otherwise
+    /// it's tried when its turn in the tier comes. The trackers are saved to the resume data.
+    pub async fn add_tracker(&self, announce_url: &String, tier_index: usize) -> Result<(), TrackerEditError> {
+        let tracker = Arc::new(self.newTracker(announce_url).map_err(|e| TrackerEditError::InvalidUrl {
+            reason: e.to_string(),
+        })?);
+
+        {
+            let mut trackers = self.state.trackers.write().await;
+            if trackers.iter().flatten().any(|t| t.address == tracker.address) {
+                return Err(TrackerEditError::AlreadyAdded {
+                    url: tracker.address.to_string(),
+                });
+            }
+            match trackers.get_mut(tier_index) {
+                Some(tier) => tier.push(tracker.clone()),
+                None => trackers.push(vec![tracker.clone()]),
+            }
+        }
+
+        if self.state.d_state() != DownState::Stopped && self.state.announce_to_all() {
+            tokio::spawn(async move {
+                tracker.run().await;
+            });
+        }
+        self.saveTrackers().await
+    }
+
+    /// Removes the tracker with the given URL, along with its tier if it was the only tracker of
+    /// the tier, and lets the tracker know that we're no longer downloading or seeding the torrent
+    ///
+    /// The trackers are saved to the resume data.
+    pub async fn remove_tracker(&self, announce_url: &Url) -> Result<(), TrackerEditError> {
+        let tracker = {
+            let mut trackers = self.state.trackers.write().await;
+            let position = trackers.iter().enumerate().find_map(|(tier_index, tier)| {
+                let position = tier.iter().position(|t| t.address == *announce_url)?;
+                Some((tier_index, position))
+            });
+            let Some((tier_index, position)) = position else {
+                return Err(TrackerEditError::NotFound {
+                    url: announce_url.to_string(),
+                });
+            };
+            let tracker = trackers[tier_index].remove(position);
+            if trackers[tier_index].is_empty() {
+                trackers.remove(tier_index);
+            }
+            tracker
+        };
+
+        // Waiting for the "stopped" announce would hold up the caller for no reason
+        tokio::spawn(async move {
+            tracker.stop().await;
+        });
+        self.saveTrackers().await
+    }
+
+    /// Announces to the tracker with the given URL right away, see [Tracker::reannounce]
+    pub async fn reannounce(&self, announce_url: &Url) -> Result<(), TrackerEditError> {
+        self.tracker(announce_url).await?.reannounce();
+        Ok(())
+    }
+
+    /// Scrapes the tracker with the given URL right away, gives back whether the numbers were
+    /// received, see [Tracker::scrape_now]
+    pub async fn scrape(&self, announce_url: &Url) -> Result<bool, TrackerEditError> {
+        Ok(self.tracker(announce_url).await?.scrape_now().await)
+    }
+
+    /// The tracker of this torrent with the given URL
+    async fn tracker(&self, announce_url: &Url) -> Result<Arc<Tracker>, TrackerEditError> {
+        let trackers = self.state.trackers.read().await;
+        match trackers.iter().flatten().find(|t| t.address == *announce_url) {
+            Some(tracker) => Ok(tracker.clone()),
+            None => Err(TrackerEditError::NotFound {
+                url: announce_url.to_string(),
+            }),
+        }
+    }
 
-        // Step 2 : Recv by listening on the UDP socket and then find out for whom the message came for and give
-        // back to that specific tracker the response messsage
+    /// Saves the URLs of the trackers, tier by tier, to the resume data
+    async fn saveTrackers(&self) -> Result<(), TrackerEditError> {
+        let trackers = self
+            .state
+            .trackers
+            .read()
+            .await
+            .iter()
+            .map(|tier| tier.iter().map(|tracker| tracker.address.to_string()).collect())
+            .collect();
+
+        let mut resume = self.resume.lock().await;
+        resume.trackers = Some(trackers);
+        resume.save(&self.resume_path).await?;
+        Ok(())
+    }
+
+    /// Spawns a tokio task to announce to the trackers, either to all of them at once or tier by
+    /// tier, according to [State::announce_to_all], the task runs until the torrent is stopped
+    async fn spawnTrackers(&self) {
+        let cancel = CancellationToken::new();
+        *self.trackers_cancel.lock().await = cancel.clone();
+
+        let state = self.state.clone();
+        if state.announce_to_all() {
+            for tracker in state.trackers.read().await.iter().flatten() {
+                let tracker = tracker.clone();
+                tokio::spawn(async move {
+                    tracker.run().await;
+                });
+            }
+        } else {
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = Self::runTiers(state) => {}
+                    _ = cancel.cancelled() => {}
+                }
+            });
+        }
+    }
+
+    /// Announces to a single tracker at a time as BEP12 asks for, the trackers of a tier are
+    /// tried in order and the next tier is only tried when every tracker of the current tier has
+    /// failed. A tracker that gets announced to is moved to the front of its tier and kept on being
+    /// announced to, until it fails.
+    ///
+    /// Once every tracker of every tier has failed, they're all tried again after
+    /// [TIERS_RETRY_INTERVAL]
+    async fn runTiers(state: Arc<State>) {
         loop {
-            // A buffer of 4KiB capacity
-            let mut buf = [0; 4096];
-            match socket.recv_from(&mut buf).await {
-                Ok((len, ref s_addrs)) => {
-                    // NOTE : I could've stored all trackers in the top scope of this
-                    // receive_trackers_response() function, so that i don't have to await. But, the
-                    // problem is of BEP12, where i have to constantly arrange the Trackers, this
-                    // changes the index in the self.state.trackers field
-                    let trackers = self.state.trackers.read().await;
-                    for trackers in trackers.iter() {
-                        for tracker in trackers {
-                            if tracker.isEqualTo(s_addrs) {
-                                if let Some((ref sd, _)) = tracker.udp_channel {
-                                    if !sd.is_closed() {
-                                        let mut buf = buf.to_vec();
-                                        buf.truncate(len);
-                                        sd.send(buf); // TODO : send() return Result<T>, might need to make use of Err ?. Figure out
-                                    }
-                                }
-                            }
-                        }
-                    }
+            let tiers_count = state.trackers.read().await.len();
+            for tier_index in 0..tiers_count {
+                let mut position = 0;
+                loop {
+                    let tracker = match state.trackers.read().await.get(tier_index).and_then(|tier| tier.get(position)) {
+                        Some(tracker) => tracker.clone(),
+                        None => break,
+                    };
+
+                    // The tracker that got announced to sits at the front of the tier now, so the
+                    // one after it is tried next
+                    position = if Self::runActiveTracker(&state, tier_index, &tracker).await {
+                        1
+                    } else {
+                        position + 1
+                    };
                 }
-                _ => {
-                    // Error on receiving from the UDP Socket
+            }
+            sleep(TIERS_RETRY_INTERVAL).await;
+        }
+    }
+
+    /// Runs the given tracker of the given tier until it fails, or until it doesn't respond within
+    /// [TRACKER_RESPONSE_TIMEOUT] of being started, gives back whether it was announced to
+    async fn runActiveTracker(state: &State, tier_index: usize, tracker: &Arc<Tracker>) -> bool {
+        let started_at = Instant::now();
+        let mut announced = false;
+
+        let run = tracker.run();
+        tokio::pin!(run);
+        let mut check = interval_at(started_at + TRACKER_CHECK_INTERVAL, TRACKER_CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = &mut run => return announced,
+                _ = check.tick() => {}
+            }
+
+            if tracker.has_failed() {
+                return announced;
+            }
+
+            if !announced && tracker.has_announced() {
+                announced = true;
+                let mut trackers = state.trackers.write().await;
+                if let Some(tier) = trackers.get_mut(tier_index) {
+                    if let Some(position) = tier.iter().position(|t| Arc::ptr_eq(t, tracker)) {
+                        let tracker = tier.remove(position);
+                        tier.insert(0, tracker);
+                    }
                 }
             }
+
+            if !announced && started_at.elapsed() >= TRACKER_RESPONSE_TIMEOUT {
+                tracker.set_tracker_state(TrackerState::Idle);
+                return false;
+            }
         }
     }
 
+    /// Receives the peers collected by the trackers and runs a session with each one of them,
+    /// a peer that's already in the session is skipped, unless its connection was closed
+    ///
+    /// The peers received while the connection limits have been reached are dropped, the trackers
+    /// will hand them over again on the next announce anyway
     pub async fn runDownload(&self) {
         let ref peers_rcv = self.peers_channel.1;
         let mut peers_rcv = peers_rcv.lock().await;
         while let Some(peer) = peers_rcv.recv().await {
-            //println!("--------------------------------------------",);
-            //println!("{:?}", peer.socket_adr);
-            //println!("--------------------------------------------",);
+            let peer = Arc::new(peer);
+            if self.state.d_state() == DownState::Stopped
+                || !self.engine_state.can_connect(&self.state).await
+                || !self.state.add_peer(peer.clone()).await
+            {
+                continue;
+            }
+
+            tokio::spawn(async move {
+                peer.run().await;
+            });
+        }
+    }
+
+    /// Looks up the peers of the torrent on the DHT of the engine every [DHT_ANNOUNCE_INTERVAL],
+    /// announcing us to the nodes closest to the torrent, and runs a session with each peer found
+    ///
+    /// The nodes in the "nodes" field of the torrent are bootstrapped from first. It doesn't run
+    /// for a private torrent, whose peers must only come from its trackers.
+    async fn runDht(&self) {
+        let Ok(info_hash) = NodeId::try_from(self.state.info_hash.as_slice()) else {
+            return;
+        };
+        if self.state.meta_info.isPrivate() {
+            return;
+        }
+        let dht = &self.engine_state.dht;
+        if let Some(ref nodes) = self.state.meta_info.nodes {
+            dht.add_nodes(nodes).await;
+        }
+
+        loop {
+            if self.state.d_state() != DownState::Stopped {
+                for socket_adr in dht.announce(info_hash, self.engine_state.listen_port()).await {
+                    let mut peer = Peer::new(socket_adr, self.state.clone());
+                    peer.set_source(PeerSource::Dht);
+                    self.peers_channel.0.send(peer);
+                }
+            }
+
+            if dht.nodes_count().await < K {
+                sleep(DHT_RETRY_INTERVAL).await;
+            } else {
+                sleep(DHT_ANNOUNCE_INTERVAL).await;
+            }
         }
-        // TODO: Run in a loop, but never return anything
+    }
+
+    /// Chokes and unchokes the peers of the session, for as long as the session runs
+    async fn runChoker(&self) {
+        let mut choker = Choker::new(self.state.clone(), self.engine_state.clone());
+        choker.run().await;
     }
 
     /// TODO : Add examples for the rust docs
     /// Starts to download the torrent, it will keep on mutating the "state" field as it
-    /// makes progress, and if the torrent needs to be pause or started, one can use the method on
-    /// that State instance
+    /// makes progress, and if the torrent needs to be paused or resumed, one can use the
+    /// [TorrentFile::stop] and [TorrentFile::start] methods
     ///
     /// NOTE : While using this method, one must clone and keep a Arc pointer of "state" field,
     /// so that they can use it later on to display the UI or the data changed
     pub async fn run(&self) {
-        // A UDP socket for all the Trackers to send requests and receive responses
-        let trackers_udp_socket = self.getUDPSocket().await;
+        self.state.set_d_state(self.running_state());
+        self.engine_state.register_torrent(self.state.clone()).await;
+        self.state.update_wanted_pieces().await;
 
-        let run_trackers = self.runTrackers(trackers_udp_socket.clone());
+        let run_trackers = self.runTrackers();
         let run_download = self.runDownload();
+        let run_choker = self.runChoker();
+        let run_dht = self.runDht();
+
+        tokio::select! {
+            _ = async { join!(run_trackers, run_download, run_choker, run_dht) } => {}
+            _ = self.cancel.cancelled() => {}
+        }
+    }
+
+    /// Stops downloading and seeding the torrent, the trackers are let known with a "stopped"
+    /// announce, every peer is disconnected and no peer is connected with until it's started again
+    pub async fn stop(&self) {
+        if self.state.d_state() == DownState::Stopped {
+            return;
+        }
+        let trackers = self.halt().await;
+        join_all(trackers.iter().map(|tracker| tracker.stop())).await;
+    }
+
+    /// Marks the torrent as stopped, disconnects every peer and stops announcing to the trackers
+    /// tier by tier, gives back the trackers, which are still to be stopped
+    async fn halt(&self) -> Vec<Arc<Tracker>> {
+        self.state.set_d_state(DownState::Stopped);
+        self.engine_state.unregister_torrent(&self.state.info_hash).await;
+        self.engine_state.release_upload_slots(&self.state.info_hash).await;
+
+        for peer in self.state.peers.lock().await.iter() {
+            peer.disconnect(DisconnectReason::Requested);
+        }
+
+        self.trackers_cancel.lock().await.cancel();
+        self.state.trackers.read().await.iter().flatten().cloned().collect()
+    }
+
+    /// Carries on with the sessions of the given torrent, which is of the same info hash but is
+    /// yet to have the "info" field, and stops running that torrent for good
+    ///
+    /// The trackers of this torrent carry on with the sessions of the ones with the same URL, so
+    /// they aren't told that the torrent was stopped and started again. The peers of that torrent
+    /// are connected with again, the ones that connected to us on the port they're listening on.
+    pub async fn take_over(&self, torrent: &TorrentFile) {
+        let mut peers = Vec::new();
+        for peer in torrent.state.peers.lock().await.iter() {
+            let socket_adr = match peer.source {
+                // The port a peer connected to us from isn't the one it listens on
+                PeerSource::Incoming => match peer.info.lock().await.listen_port {
+                    Some(port) => SocketAddr::new(peer.socket_adr.ip(), port),
+                    None => continue,
+                },
+                _ => peer.socket_adr,
+            };
+            peers.push((socket_adr, peer.source));
+        }
+
+        let trackers = torrent.halt().await;
+        join_all(trackers.iter().map(|tracker| tracker.halt())).await;
+        torrent.cancel.cancel();
+        *self.handed_over_trackers.lock().await = trackers;
+
+        for (socket_adr, source) in peers {
+            let mut peer = Peer::new(socket_adr, self.state.clone());
+            peer.set_source(source);
+            self.add_peer(peer);
+        }
+    }
+
+    /// Starts the torrent again after it was stopped, the trackers are announced to with a
+    /// "started" event
+    pub async fn start(&self) {
+        if self.state.d_state() != DownState::Stopped {
+            return;
+        }
+        self.state.set_d_state(self.running_state());
+        self.engine_state.register_torrent(self.state.clone()).await;
+
+        self.spawnTrackers().await;
+    }
+
+    /// The state of the torrent while it runs, the "info" field is fetched first if the torrent
+    /// was added through a magnet URI
+    fn running_state(&self) -> DownState {
+        if self.state.has_metadata() {
+            DownState::Downloading
+        } else {
+            DownState::FetchingMetadata
+        }
+    }
+
+    /// Runs a session with the given peer, just like the ones collected by the trackers, see
+    /// [TorrentFile::runDownload]
+    pub fn add_peer(&self, peer: Peer) {
+        let _ = self.peers_channel.0.send(peer);
+    }
 
-        join!(run_trackers, run_download);
+    /// Stops the torrent for good, the session isn't run anymore after this
+    pub async fn remove(&self) {
+        self.stop().await;
+        self.cancel.cancel();
     }
 }
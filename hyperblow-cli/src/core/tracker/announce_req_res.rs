@@ -1,6 +1,7 @@
+use crate::core::net::compact_peer;
 use byteorder::{BigEndian, ReadBytesExt};
 use bytes::{BufMut, BytesMut};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::net::SocketAddr;
 
 /// Struct to handle "Announce" request message
 /// Used to create a "98 byte" buffer to make "Announce Request"
@@ -22,6 +23,18 @@ use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 /// 92      32-bit integer  num_want        The maximum number of peers you want in the reply. Use -1 for default.
 /// 96      16-bit integer  port            The port you're listening on.
 /// 98
+///
+/// The request is followed by the options of BEP41, if there's any URLData to be sent
+/// Reference : http://www.bittorrent.org/beps/bep_0041.html
+///
+/// Option Bytes Structure:
+/// Offset  Size    Name    Value
+/// 0       8-bit integer   type            0: EndOfOptions; 1: NOP; 2: URLData
+/// 1       8-bit integer   length          Length of the data, only for URLData
+/// 2       N-byte string   data            Upto 255 bytes of the path and query of the tracker URL
+///
+/// A path and query longer than 255 bytes is split into consecutive URLData options, which the
+/// tracker concatenates back
 #[derive(Debug, Clone)]
 pub struct AnnounceRequest {
     connection_id: Option<i64>,
@@ -37,8 +50,15 @@ pub struct AnnounceRequest {
     key: Option<i32>,
     num_want: i32,
     port: Option<i16>,
+    url_data: Vec<u8>,
 }
 
+/// Option type that marks the end of the BEP41 options
+const OPTION_END_OF_OPTIONS: u8 = 0x0;
+
+/// Option type that carries a part of the path and query of the tracker URL
+const OPTION_URL_DATA: u8 = 0x2;
+
 impl AnnounceRequest {
     // Creates an empty Announce instance
     pub fn new() -> Self {
@@ -56,16 +76,17 @@ impl AnnounceRequest {
             downloaded: None,
             left: None,
             uploaded: None,
-            event: Some(1),
+            event: Some(0),
             ip_address: 0,
             key: None,
             num_want: -1,
             port: None,
+            url_data: Vec::new(),
         }
     }
 
-    // Consumes the Announce instance and gives you a Buffer of 98 bytes that you
-    // can use to make Announce Request in UDP
+    // Consumes the Announce instance and gives you a Buffer of 98 bytes, followed by the URLData
+    // options if any, that you can use to make Announce Request in UDP
     pub fn serialize_to_bytes(&self) -> Option<BytesMut> {
         let mut bytes = BytesMut::with_capacity(98);
         bytes.put_i64(self.connection_id?);
@@ -81,6 +102,14 @@ impl AnnounceRequest {
         bytes.put_i32(self.key?);
         bytes.put_i32(self.num_want);
         bytes.put_i16(self.port?);
+        if !self.url_data.is_empty() {
+            for url_data in self.url_data.chunks(255) {
+                bytes.put_u8(OPTION_URL_DATA);
+                bytes.put_u8(url_data.len() as u8);
+                bytes.put_slice(url_data);
+            }
+            bytes.put_u8(OPTION_END_OF_OPTIONS);
+        }
         Some(bytes)
     }
 
@@ -112,9 +141,23 @@ impl AnnounceRequest {
         self.port = Some(v);
     }
 
+    pub fn set_event(&mut self, v: i32) {
+        self.event = Some(v);
+    }
+
     pub fn set_key(&mut self, v: i32) {
         self.key = Some(v);
     }
+
+    pub fn set_peer_id(&mut self, v: [u8; 20]) {
+        self.peer_id = Some(v);
+    }
+
+    /// Sets the path and query of the tracker URL, e.g "/announce?passkey=abc", to be sent as the
+    /// URLData option
+    pub fn set_url_data(&mut self, v: &[u8]) {
+        self.url_data = v.to_vec();
+    }
 }
 
 /// IPv4 announce response:
@@ -129,6 +172,18 @@ impl AnnounceRequest {
 /// 24 + 6 * n  16-bit integer  TCP port
 /// 20 + 6 * Ns
 ///
+/// IPv6 announce response, received when the request was sent over IPv6:
+///
+/// Offet      Size            Name            Value
+/// 0           32-bit integer  action          1 // announce
+/// 4           32-bit integer  transaction_id
+/// 8           32-bit integer  interval
+/// 12          32-bit integer  leechers
+/// 16          32-bit integer  seeders
+/// 20 + 18 * n 128-bit integer IP address
+/// 36 + 18 * n 16-bit integer  TCP port
+/// 20 + 18 * N
+///
 /// Struct to handle the response received by sending "Announce" request
 #[derive(Debug, Clone)]
 pub struct AnnounceResponse {
@@ -141,11 +196,12 @@ pub struct AnnounceResponse {
 }
 
 impl AnnounceResponse {
-    /// Creates a AnnounceResponse from the given buffer
+    /// Creates a AnnounceResponse from the given buffer, "ipv6" tells whether the response was
+    /// received over IPv6, in which case the peers are IPv6 peers of 18 bytes each
     ///
     /// The error produced here are the IO errors from parsing the given buffer bytes into
     /// respective types
-    pub fn from(v: &[u8]) -> Result<Self, std::io::Error> {
+    pub fn from(v: &[u8], ipv6: bool) -> Result<Self, std::io::Error> {
         let mut action_bytes = &v[0..=3];
         let mut transaction_id_bytes = &v[4..=7];
         let mut interval_bytes = &v[8..=11];
@@ -158,20 +214,9 @@ impl AnnounceResponse {
         let leechers = ReadBytesExt::read_i32::<BigEndian>(&mut leechers_bytes)?;
         let seeders = ReadBytesExt::read_i32::<BigEndian>(&mut seeder_bytes)?;
 
-        // Range where all the (IP:PORT) are situated
-        let x = 20..v.len();
-
-        let mut peersAddresses = vec![];
-        for i in x.step_by(6) {
-            let port_bytes = vec![v[i + 4], v[i + 5]];
-            let mut port_bytes = &port_bytes[..];
-            let port = ReadBytesExt::read_i16::<BigEndian>(&mut port_bytes)?;
-            let socket_adr = SocketAddr::new(
-                IpAddr::V4(Ipv4Addr::new(v[i], v[i + 1], v[i + 2], v[i + 3])),
-                port as u16,
-            );
-            peersAddresses.push(socket_adr);
-        }
+        // All the (IP:PORT) are situated after the first 20 bytes
+        let peer_length = if ipv6 { 18 } else { 6 };
+        let peersAddresses = v[20..].chunks_exact(peer_length).filter_map(compact_peer).collect();
 
         Ok(AnnounceResponse {
             action,
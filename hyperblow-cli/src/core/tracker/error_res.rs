@@ -1,9 +1,8 @@
 use byteorder::{BigEndian, ReadBytesExt};
-use std::error;
 
-/// Struct to handle response message from "Connect" request to the UDP Tracker
-/// Used to create an instance of AnnounceRequest
-/// Connect Response Bytes Structure from the UDP Tracker Protocol :
+/// Struct to handle the response message the UDP Tracker replies with, instead of the expected
+/// response, when it refuses a request
+/// Error Response Bytes Structure from the UDP Tracker Protocol :
 ///
 /// Offset  Size            Name            Value
 /// 0       32-bit integer  action          3 // error
@@ -17,14 +16,16 @@ pub struct ErrorResponse {
 }
 
 impl ErrorResponse {
-    pub fn from(v: &[u8]) -> Result<ErrorResponse, Box<dyn error::Error>> {
-        let mut action_bytes = &v[0..=3];
-        let mut transaction_id_bytes = &v[4..=7];
-        let message_bytes = v[8..(v.len())].to_vec();
-
-        let action = ReadBytesExt::read_i32::<BigEndian>(&mut action_bytes).unwrap();
-        let transaction_id = ReadBytesExt::read_i32::<BigEndian>(&mut transaction_id_bytes).unwrap();
-        let message = String::from_utf8(message_bytes)?;
+    /// Creates an ErrorResponse from the given buffer, the message isn't always valid UTF-8 so
+    /// the invalid bytes are replaced
+    ///
+    /// The error produced here are the IO errors from parsing the given buffer bytes into
+    /// respective types
+    pub fn from(v: &[u8]) -> Result<ErrorResponse, std::io::Error> {
+        let mut bytes = v;
+        let action = ReadBytesExt::read_i32::<BigEndian>(&mut bytes)?;
+        let transaction_id = ReadBytesExt::read_i32::<BigEndian>(&mut bytes)?;
+        let message = String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string();
 
         Ok(Self {
             action,
@@ -0,0 +1,378 @@
+use crate::core::{net::compact_peer, percEncode};
+use reqwest::Url;
+use serde_bencode::value::Value;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HttpTrackerError {
+    #[error("Request - error : {0:?}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("InvalidBencode - error : {0:?}")]
+    InvalidBencode(#[from] serde_bencode::Error),
+
+    #[error("IncompleteRequest")]
+    IncompleteRequest,
+
+    #[error("InvalidResponse - reason : {reason:?}")]
+    InvalidResponse { reason: &'static str },
+}
+
+/// Struct to handle "Announce" request made to a HTTP or HTTPS tracker
+/// Used to create the query string appended to the announce URL of the tracker
+/// Reference : http://www.bittorrent.org/beps/bep_0003.html#trackers
+///
+/// Query parameters :
+/// info_hash   20-byte SHA1 hash of the info dict, percent encoded
+/// peer_id     20-byte Peer ID of ours, percent encoded
+/// port        The port we're listening on
+/// uploaded    The number of bytes uploaded so far
+/// downloaded  The number of bytes downloaded so far
+/// left        The number of bytes left to download until we're finished
+/// compact     1 to ask for the peer list in the compact form of BEP23
+/// event       started, completed or stopped, left out for the regular announces
+/// key         A random key, letting the tracker identify us if our IP changes
+/// trackerid   The "tracker id" the tracker sent in a previous response, if any
+#[derive(Debug, Clone)]
+pub struct HttpAnnounceRequest {
+    info_hash: Option<Vec<u8>>,
+    peer_id: Option<[u8; 20]>,
+    port: Option<u16>,
+    uploaded: Option<u64>,
+    downloaded: Option<u64>,
+    left: Option<u64>,
+    compact: bool,
+    event: Option<&'static str>,
+    key: Option<u32>,
+    tracker_id: Option<Vec<u8>>,
+}
+
+impl HttpAnnounceRequest {
+    // Creates an empty HttpAnnounceRequest instance
+    pub fn new() -> Self {
+        HttpAnnounceRequest {
+            info_hash: None,
+            peer_id: None,
+            port: None,
+            uploaded: None,
+            downloaded: None,
+            left: None,
+            compact: true,
+            event: None,
+            key: None,
+            tracker_id: None,
+        }
+    }
+
+    /// Gives you the URL to make the Announce Request to, by appending the query parameters to
+    /// the announce URL of the tracker
+    ///
+    /// The announce URL can already have a query of its own (private trackers usually put the
+    /// passkey there), in which case the parameters are appended after it
+    pub fn to_url(&self, address: &Url) -> Option<String> {
+        let mut url = address.to_string();
+        url.push(if address.query().is_some() { '&' } else { '?' });
+        url.push_str(&format!(
+            "info_hash={}&peer_id={}&port={}&uploaded={}&downloaded={}&left={}&compact={}",
+            percEncode(self.info_hash.clone()?),
+            percEncode(self.peer_id?.to_vec()),
+            self.port?,
+            self.uploaded?,
+            self.downloaded?,
+            self.left?,
+            self.compact as u8,
+        ));
+        if let Some(event) = self.event {
+            url.push_str(&format!("&event={event}"));
+        }
+        if let Some(key) = self.key {
+            url.push_str(&format!("&key={key:08X}"));
+        }
+        if let Some(ref tracker_id) = self.tracker_id {
+            url.push_str(&format!("&trackerid={}", percEncode(tracker_id.clone())));
+        }
+        Some(url)
+    }
+
+    pub fn set_info_hash(&mut self, v: &[u8]) {
+        self.info_hash = Some(v.to_vec());
+    }
+
+    pub fn set_peer_id(&mut self, v: [u8; 20]) {
+        self.peer_id = Some(v);
+    }
+
+    pub fn set_port(&mut self, v: u16) {
+        self.port = Some(v);
+    }
+
+    pub fn set_uploaded(&mut self, v: u64) {
+        self.uploaded = Some(v);
+    }
+
+    pub fn set_downloaded(&mut self, v: u64) {
+        self.downloaded = Some(v);
+    }
+
+    pub fn set_left(&mut self, v: u64) {
+        self.left = Some(v);
+    }
+
+    pub fn set_event(&mut self, v: &'static str) {
+        self.event = Some(v);
+    }
+
+    pub fn set_key(&mut self, v: u32) {
+        self.key = Some(v);
+    }
+
+    pub fn set_tracker_id(&mut self, v: Vec<u8>) {
+        self.tracker_id = Some(v);
+    }
+}
+
+/// Struct to handle the bencoded dictionary received by sending "Announce" request to a HTTP or
+/// HTTPS tracker
+///
+/// The peers can either be a single string of 6 bytes per peer (compact form, BEP23) or a list
+/// of dictionaries with the "peer id", "ip" and "port" keys (BEP3). Trackers that don't support
+/// the compact form reply with the list of dictionaries, even if we asked for the compact one.
+/// The IPv6 peers come separately in "peers6", as a single string of 18 bytes per peer (BEP7).
+#[derive(Debug, Clone)]
+pub struct HttpAnnounceResponse {
+    /// If present, the announce failed and none of the other fields are present
+    pub failure_reason: Option<String>,
+
+    /// The announce went through, but the tracker has something to say
+    pub warning_message: Option<String>,
+
+    /// Seconds to wait before making the next regular announce
+    pub interval: u64,
+
+    /// Seconds that must pass before announcing again, even when the announce is forced
+    pub min_interval: Option<u64>,
+
+    /// Must be sent back to the tracker on the next announces, once it's received
+    pub tracker_id: Option<Vec<u8>>,
+
+    /// No of peers with the entire torrent i.e seeders
+    pub complete: Option<i64>,
+
+    /// No of peers still downloading the torrent i.e leechers
+    pub incomplete: Option<i64>,
+
+    /// Socket address of each peer, along with its Peer ID if the tracker told us about it
+    pub peers: Vec<(SocketAddr, Option<Vec<u8>>)>,
+}
+
+impl HttpAnnounceResponse {
+    /// Creates a HttpAnnounceResponse from the given bencoded body of the response
+    pub fn from(v: &[u8]) -> Result<Self, HttpTrackerError> {
+        let dict = match serde_bencode::from_bytes::<Value>(v)? {
+            Value::Dict(dict) => dict,
+            _ => {
+                return Err(HttpTrackerError::InvalidResponse {
+                    reason: "response is not a dictionary",
+                })
+            }
+        };
+
+        let string = |key: &[u8]| match dict.get(key) {
+            Some(Value::Bytes(bytes)) => Some(String::from_utf8_lossy(bytes).to_string()),
+            _ => None,
+        };
+        let int = |key: &[u8]| match dict.get(key) {
+            Some(Value::Int(int)) => Some(*int),
+            _ => None,
+        };
+
+        let failure_reason = string(b"failure reason");
+        let interval = match int(b"interval") {
+            Some(interval) if interval >= 0 => interval as u64,
+            _ if failure_reason.is_some() => 0,
+            _ => {
+                return Err(HttpTrackerError::InvalidResponse {
+                    reason: "interval is missing",
+                })
+            }
+        };
+
+        let tracker_id = match dict.get(b"tracker id".as_ref()) {
+            Some(Value::Bytes(bytes)) => Some(bytes.clone()),
+            _ => None,
+        };
+
+        let mut peers = match dict.get(b"peers".as_ref()) {
+            Some(Value::Bytes(bytes)) => Self::compact_peers(bytes, 6),
+            Some(Value::List(list)) => list.iter().filter_map(Self::dictionary_peer).collect(),
+            _ => Vec::new(),
+        };
+        if let Some(Value::Bytes(bytes)) = dict.get(b"peers6".as_ref()) {
+            peers.extend(Self::compact_peers(bytes, 18));
+        }
+
+        Ok(HttpAnnounceResponse {
+            failure_reason,
+            warning_message: string(b"warning message"),
+            interval,
+            min_interval: int(b"min interval")
+                .filter(|min_interval| *min_interval >= 0)
+                .map(|min_interval| min_interval as u64),
+            tracker_id,
+            complete: int(b"complete"),
+            incomplete: int(b"incomplete"),
+            peers,
+        })
+    }
+
+    /// Parses the peers in the compact form, where each peer takes "peer_length" bytes, i.e 6
+    /// bytes for the IPv4 peers and 18 bytes for the IPv6 peers
+    fn compact_peers(v: &[u8], peer_length: usize) -> Vec<(SocketAddr, Option<Vec<u8>>)> {
+        v.chunks_exact(peer_length)
+            .filter_map(compact_peer)
+            .map(|socket_adr| (socket_adr, None))
+            .collect()
+    }
+
+    /// Parses a single peer in the dictionary form, the "ip" can either be an IP address or a
+    /// DNS name, the latter isn't supported and such a peer is skipped
+    fn dictionary_peer(v: &Value) -> Option<(SocketAddr, Option<Vec<u8>>)> {
+        let dict: &HashMap<Vec<u8>, Value> = match v {
+            Value::Dict(dict) => dict,
+            _ => return None,
+        };
+
+        let ip = match dict.get(b"ip".as_ref()) {
+            Some(Value::Bytes(bytes)) => String::from_utf8_lossy(bytes).parse::<IpAddr>().ok()?,
+            _ => return None,
+        };
+        let port = match dict.get(b"port".as_ref()) {
+            Some(Value::Int(port)) => u16::try_from(*port).ok()?,
+            _ => return None,
+        };
+        let peer_id = match dict.get(b"peer id".as_ref()) {
+            Some(Value::Bytes(bytes)) if bytes.len() == 20 => Some(bytes.clone()),
+            _ => None,
+        };
+
+        Some((SocketAddr::new(ip, port), peer_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> HttpAnnounceRequest {
+        let mut request = HttpAnnounceRequest::new();
+        request.set_info_hash(&[
+            0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf1, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x12, 0x34, 0x56, 0x78, 0x9a,
+        ]);
+        request.set_peer_id(*b"-HB0001-a b~c.d_e-f0");
+        request.set_port(6881);
+        request.set_uploaded(10);
+        request.set_downloaded(20);
+        request.set_left(30);
+        request
+    }
+
+    #[test]
+    fn info_hash_and_peer_id_are_percent_encoded() {
+        let address = Url::parse("http://tracker.example.org/announce").unwrap();
+        let url = request().to_url(&address).unwrap();
+        assert_eq!(
+            url,
+            "http://tracker.example.org/announce?info_hash=%124Vx%9A%BC%DE%F1%23Eg%89%AB%CD%EF%124Vx%9A\
+             &peer_id=%2DHB0001%2Da%20b%7Ec%2Ed%5Fe%2Df0&port=6881&uploaded=10&downloaded=20&left=30&compact=1"
+        );
+    }
+
+    #[test]
+    fn optional_parameters_follow_the_query_of_the_announce_url() {
+        let address = Url::parse("https://tracker.example.org/announce?passkey=abc").unwrap();
+        let mut request = request();
+        request.set_event("started");
+        request.set_key(0xbeef);
+        request.set_tracker_id(b"id 1".to_vec());
+        let url = request.to_url(&address).unwrap();
+        assert!(url.starts_with("https://tracker.example.org/announce?passkey=abc&info_hash="));
+        assert!(url.ends_with("&compact=1&event=started&key=0000BEEF&trackerid=id%201"));
+
+        assert_eq!(HttpAnnounceRequest::new().to_url(&address), None);
+    }
+
+    #[test]
+    fn compact_peers() {
+        let mut body = b"d8:completei5e10:incompletei3e8:intervali1800e5:peers12:".to_vec();
+        body.extend_from_slice(&[10, 0, 0, 1, 0x1a, 0xe1, 192, 168, 1, 2, 0xc8, 0xd5]);
+        body.extend_from_slice(b"6:peers618:");
+        body.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0x1a, 0xe1]);
+        body.push(b'e');
+
+        let response = HttpAnnounceResponse::from(&body).unwrap();
+        assert_eq!(response.interval, 1800);
+        assert_eq!((response.complete, response.incomplete), (Some(5), Some(3)));
+        let peers: Vec<(SocketAddr, Option<Vec<u8>>)> = ["10.0.0.1:6881", "192.168.1.2:51413", "[2001:db8::1]:6881"]
+            .iter()
+            .map(|socket_adr| (socket_adr.parse().unwrap(), None))
+            .collect();
+        assert_eq!(response.peers, peers);
+    }
+
+    #[test]
+    fn dictionary_peers() {
+        let body = b"d8:intervali900e5:peersl\
+            d2:ip8:10.0.0.17:peer id20:-XX0001-0123456789ab4:porti6881ee\
+            d2:ip11:2001:db8::24:porti51413ee\
+            d2:ip16:peer.example.org4:porti6881ee\
+            d2:ip8:10.0.0.34:porti70000ee\
+            ee";
+
+        let response = HttpAnnounceResponse::from(body).unwrap();
+        let peers = vec![
+            ("10.0.0.1:6881".parse().unwrap(), Some(b"-XX0001-0123456789ab".to_vec())),
+            ("[2001:db8::2]:51413".parse().unwrap(), None),
+        ];
+        assert_eq!(response.peers, peers);
+    }
+
+    #[test]
+    fn failure_reason_without_interval() {
+        let response = HttpAnnounceResponse::from(b"d14:failure reason17:torrent not founde").unwrap();
+        assert_eq!(response.failure_reason.as_deref(), Some("torrent not found"));
+        assert_eq!(response.interval, 0);
+        assert!(response.peers.is_empty());
+
+        assert!(matches!(
+            HttpAnnounceResponse::from(b"d5:peers0:e"),
+            Err(HttpTrackerError::InvalidResponse { .. })
+        ));
+        assert!(matches!(
+            HttpAnnounceResponse::from(b"li1ee"),
+            Err(HttpTrackerError::InvalidResponse { .. })
+        ));
+        assert!(matches!(
+            HttpAnnounceResponse::from(b"d8:interval"),
+            Err(HttpTrackerError::InvalidBencode(_))
+        ));
+    }
+
+    #[test]
+    fn warning_message_min_interval_and_tracker_id() {
+        let body = b"d8:intervali1800e12:min intervali60e10:tracker id4:id 15:peers0:\
+            15:warning message13:slow tracker!e";
+
+        let response = HttpAnnounceResponse::from(body).unwrap();
+        assert_eq!(response.failure_reason, None);
+        assert_eq!(response.warning_message.as_deref(), Some("slow tracker!"));
+        assert_eq!(response.min_interval, Some(60));
+        assert_eq!(response.tracker_id, Some(b"id 1".to_vec()));
+
+        let response = HttpAnnounceResponse::from(b"d8:intervali1800e12:min intervali-1ee").unwrap();
+        assert_eq!((response.min_interval, response.tracker_id), (None, None));
+    }
+}
@@ -1,18 +1,26 @@
-// TODO: Implement DHT
-
 mod announce_req_res;
 mod connect_req_res;
 mod error_res;
+mod http_announce_req_res;
+mod scrape_req_res;
+mod udp_connection;
+pub mod udp_service;
 
 use self::{
     announce_req_res::{AnnounceRequest, AnnounceResponse},
     connect_req_res::{ConnectRequest, ConnectResponse},
+    error_res::ErrorResponse,
+    http_announce_req_res::{HttpAnnounceRequest, HttpAnnounceResponse, HttpTrackerError},
+    scrape_req_res::{ScrapeRequest, ScrapeResponse, ScrapeStats},
+    udp_connection::{is_response_to, retransmit},
 };
 use crate::{
-    core::{peer::Peer, state::State},
+    core::{
+        peer::Peer,
+        state::{EngineState, State},
+    },
     ACell, ArcMutex,
 };
-use byteorder::{BigEndian, ReadBytesExt};
 use crossbeam::atomic::AtomicCell;
 use rand::{thread_rng, Rng};
 use reqwest::Url;
@@ -21,33 +29,103 @@ use std::{
     fmt::{write, Display},
     io,
     net::SocketAddr,
-    sync::Arc,
+    sync::{Arc, RwLock as StdRwLock},
     time::{Duration, Instant},
 };
 use strum_macros::Display;
 use tokio::{
-    net::UdpSocket,
-    sync::{
-        mpsc,
-        mpsc::{UnboundedReceiver, UnboundedSender},
-        Mutex,
-    },
+    sync::{mpsc::UnboundedSender, Mutex, Notify},
     time::{sleep, timeout},
 };
+use tokio_util::sync::CancellationToken;
+
+/// Time given to a HTTP or HTTPS tracker to respond to an announce
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time waited before announcing again after a HTTP or HTTPS tracker couldn't be reached, or
+/// after any tracker refused the announce, it's doubled on every consecutive failure, upto
+/// [MAX_RETRY_BACKOFF] times
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+const MAX_RETRY_BACKOFF: u32 = 5;
+
+/// The trackers aren't announced to more often than this, whatever interval they ask for
+const MIN_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time given to the tracker to respond to the "stopped" announce, it isn't retried
+const STOPPED_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time waited before resolving the DNS of the tracker again, after it couldn't be resolved
+const DNS_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time between each scrape of a UDP tracker, the torrents sharing the tracker are scraped together
+pub const SCRAPE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Time given to a UDP tracker to respond to the ConnectRequest and the ScrapeRequest made while
+/// scraping
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Time waited before announcing again after the announce failed "n" times in a row
+fn retry_interval(n: u32) -> Duration {
+    RETRY_INTERVAL * 2_u32.pow(n.min(MAX_RETRY_BACKOFF))
+}
 
 ///Type of protocol used to connect to the tracker
 #[derive(PartialEq, Debug, Clone)]
 pub enum TrackerProtocol {
     UDP,
+
+    /// HTTP or HTTPS tracker, i.e over TCP
     TCP,
 }
 
+/// The event sent along with an announce, letting the tracker know where the torrent is in its
+/// lifecycle
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum AnnounceEvent {
+    /// A regular announce, made every interval the tracker asks for
+    None,
+
+    /// The entire torrent has just been downloaded, it isn't sent if the torrent was already
+    /// complete when it was started
+    Completed,
+
+    /// The first announce after the torrent was started
+    Started,
+
+    /// The torrent is no longer being downloaded or seeded, it's the last announce
+    Stopped,
+}
+
+impl AnnounceEvent {
+    /// Value of the "event" field of the UDP AnnounceRequest
+    pub fn udp_value(&self) -> i32 {
+        match *self {
+            Self::None => 0,
+            Self::Completed => 1,
+            Self::Started => 2,
+            Self::Stopped => 3,
+        }
+    }
+
+    /// Value of the "event" query parameter of the HTTP AnnounceRequest, which is left out for a
+    /// regular announce
+    pub fn http_value(&self) -> Option<&'static str> {
+        match *self {
+            Self::None => None,
+            Self::Completed => Some("completed"),
+            Self::Started => Some("started"),
+            Self::Stopped => Some("stopped"),
+        }
+    }
+}
+
 /// List of all the states that a **UDP or TCP** Tracker can be in
 ///
 /// **TCP and UDP** - Tracker state for both UDP and TCP based tracker
 /// **TCP** - Tracker state for only TCP based tracker
 /// **UDP** - Tracker state for only UDP based tracker
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum TrackerState {
     /// Default state of the Tracker, where **NO** action is performed on the tracker
     /// For : **TCP and UDP** Tracker
@@ -73,11 +151,31 @@ pub enum TrackerState {
     /// For : **UDP** Tracker
     WaitingForConnectResponse,
 
-    /// ConnectResponse was received and AnnounceRequest was sent to the tracker, for which
-    /// we are now waiting to get a response
-    /// For : **UDP** Tracker
+    /// AnnounceRequest was sent to the tracker, for which we are now waiting to get a response,
+    /// for a UDP tracker it's sent once the ConnectResponse is received
+    /// For : **TCP and UDP** Tracker
     WaitingForAnnounceResponse,
 
+    /// AnnounceResponse was received and the tracker will be announced to again at
+    /// **next_announce**
+    /// For : **TCP and UDP** Tracker
+    Announced { next_announce: Instant },
+
+    /// The tracker couldn't be reached, it shall be announced to again at **retry_time**
+    /// For : **TCP and UDP** Tracker
+    AnnounceFailed { retry_time: Instant },
+
+    /// The tracker refused the announce with the given **message**, i.e the "failure reason" of
+    /// a HTTP tracker or the ErrorResponse of a UDP tracker, it shall be announced to again at
+    /// **retry_time**
+    /// For : **TCP and UDP** Tracker
+    Error { message: String, retry_time: Instant },
+
+    /// AnnounceResponse was received along with the "warning message" of the tracker and the
+    /// tracker will be announced to again at **next_announce**
+    /// For : **TCP** Tracker
+    Warning { message: String, next_announce: Instant },
+
     /// A ScrapeRequest was sent to the tracker, for which we are now watiing to get
     /// a response
     /// For : **UDP** Tracker
@@ -102,6 +200,36 @@ impl Display for TrackerState {
                 "DNSUnresolved ({:?}/30 sec)",
                 Instant::now().duration_since(retry_time.clone()).as_secs()
             ),
+            Self::Announced {
+                ref next_announce,
+            } => write!(
+                f,
+                "Announced (next in {} sec)",
+                next_announce.saturating_duration_since(Instant::now()).as_secs()
+            ),
+            Self::AnnounceFailed {
+                ref retry_time,
+            } => write!(
+                f,
+                "Announce Failed (retry in {} sec)",
+                retry_time.saturating_duration_since(Instant::now()).as_secs()
+            ),
+            Self::Error {
+                ref message,
+                ref retry_time,
+            } => write!(
+                f,
+                "Error : {message} (retry in {} sec)",
+                retry_time.saturating_duration_since(Instant::now()).as_secs()
+            ),
+            Self::Warning {
+                ref message,
+                ref next_announce,
+            } => write!(
+                f,
+                "Warning : {message} (next in {} sec)",
+                next_announce.saturating_duration_since(Instant::now()).as_secs()
+            ),
         }
     }
 }
@@ -109,7 +237,8 @@ impl Display for TrackerState {
 /// A tracker in BitTorrent is simply, a "URL", that uses certian request and response technique in
 /// order to get information about peers
 ///
-///One way to avoid using Trackers is using DHT(Distributed Hash Table).
+/// The peers of a torrent are found without any tracker through the DHT of the engine, see
+/// [Dht](crate::core::dht::Dht)
 #[derive(Debug)]
 pub struct Tracker {
     /// The state of the torrent file
@@ -133,8 +262,6 @@ pub struct Tracker {
     /// from other trackers.
     pub peer_sender: Arc<UnboundedSender<Peer>>,
 
-    pub udp_channel: Option<(UnboundedSender<Vec<u8>>, Arc<Mutex<UnboundedReceiver<Vec<u8>>>>)>,
-
     /// Data to make connect request
     pub connect_request: Arc<Mutex<TrackerRequest>>,
 
@@ -147,14 +274,42 @@ pub struct Tracker {
     /// Data received from announce request as response
     pub announce_response: Arc<Mutex<TrackerResponse>>,
 
-    /// Data to make scrape request
-    pub scrape_request: Arc<Mutex<TrackerRequest>>,
-
     /// Data received from scrape request as response
     pub scrape_response: Arc<Mutex<TrackerResponse>>,
 
-    // TODO : Store UDP Socket here in the struct
-    pub tracker_state: AtomicCell<TrackerState>,
+    /// The "tracker id" received from a HTTP or HTTPS tracker, which is sent back to it on every
+    /// announce after that
+    pub tracker_id: Arc<Mutex<Option<Vec<u8>>>>,
+
+    /// State shared by all the torrents of the engine, used to scrape the torrents sharing this
+    /// tracker together and to reach the UDP trackers through [udp_service::UdpTrackerService]
+    pub engine_state: Arc<EngineState>,
+
+    /// The numbers received on the latest scrape of the torrent, "None" until it's scraped
+    pub scrape_stats: AtomicCell<Option<ScrapeStats>>,
+
+    /// The time the torrent was last scraped, may it be by this tracker or by the same tracker of
+    /// another torrent
+    pub last_scrape: AtomicCell<Option<Instant>>,
+
+    /// The event to be sent on the next announce, it's "started" until the tracker has been
+    /// announced to
+    pub event: AtomicCell<AnnounceEvent>,
+
+    /// Makes the tracker announce right away, rather than waiting for the interval
+    announce_now: Notify,
+
+    /// Cancelled to stop the tracker from running, a new one is created every time it's run
+    cancel: Mutex<CancellationToken>,
+
+    /// What the tracker is currently doing, or why it failed
+    ///
+    /// NOTE : It's a std RwLock, as the state is read by the UI outside of the async context
+    tracker_state: StdRwLock<TrackerState>,
+
+    /// Whether the tracker carries on from the same tracker of another torrent, so that its next
+    /// run isn't a new session, see [Tracker::take_over]
+    carried_over: AtomicCell<bool>,
 }
 
 impl Tracker {
@@ -162,6 +317,7 @@ impl Tracker {
     pub fn new(
         address: &String,
         torrent_state: Arc<State>,
+        engine_state: Arc<EngineState>,
         peer_sender: Arc<UnboundedSender<Peer>>,
     ) -> Result<Tracker, Box<dyn std::error::Error>> {
         let address = Url::parse(address)?;
@@ -170,34 +326,35 @@ impl Tracker {
         let connect_response = ArcMutex!(TrackerResponse::None);
         let announce_request = ArcMutex!(TrackerRequest::None);
         let announce_response = ArcMutex!(TrackerResponse::None);
-        let scrape_request = ArcMutex!(TrackerRequest::None);
         let scrape_response = ArcMutex!(TrackerResponse::None);
 
-        // TODO: Find out the protocol, if its TCP or UDP for the tracker
-        let protocol = TrackerProtocol::UDP;
-        let (sd, rv) = mpsc::unbounded_channel::<Vec<u8>>();
-
-        let udp_channel = match protocol {
-            TrackerProtocol::UDP => Some((sd, ArcMutex!(rv))),
-            TrackerProtocol::TCP => None,
+        let protocol = match address.scheme() {
+            "udp" => TrackerProtocol::UDP,
+            "http" | "https" => TrackerProtocol::TCP,
+            scheme => return Err(format!("Unsupported tracker protocol : {scheme}").into()),
         };
-
-        let tracker_state = ACell!(TrackerState::Idle);
+        let tracker_state = StdRwLock::new(TrackerState::Idle);
 
         Ok(Tracker {
             torrent_state,
             address,
             socketAddrs: Arc::default(),
             protocol,
-            udp_channel,
             connect_request,
             connect_response,
             announce_request,
             announce_response,
-            scrape_request,
             scrape_response,
+            tracker_id: Arc::default(),
+            engine_state,
+            scrape_stats: ACell!(None),
+            last_scrape: ACell!(None),
             peer_sender,
+            event: ACell!(AnnounceEvent::Started),
+            announce_now: Notify::new(),
+            cancel: Mutex::default(),
             tracker_state,
+            carried_over: ACell!(false),
         })
     }
 
@@ -206,177 +363,444 @@ impl Tracker {
     /// tracker's DNS, that's what this method does, it resolves the DNS of the tracker
     pub async fn resolveTracker(&self) {
         let resolveDNS = || async {
-            if let Ok(addrs) = self.address.socket_addrs(|| None) {
-                *self.socketAddrs.lock().await = addrs;
-                true
-            } else {
-                false
+            match self.address.socket_addrs(|| None) {
+                Ok(addrs) if !addrs.is_empty() => {
+                    *self.socketAddrs.lock().await = addrs;
+                    true
+                }
+                _ => false,
             }
         };
 
-        self.tracker_state.store(TrackerState::DNSResolving);
+        self.set_tracker_state(TrackerState::DNSResolving);
         if resolveDNS().await {
-            self.tracker_state.store(TrackerState::DNSResolved);
+            self.set_tracker_state(TrackerState::DNSResolved);
         } else {
-            self.tracker_state.store(TrackerState::DNSUnresolved {
+            self.set_tracker_state(TrackerState::DNSUnresolved {
                 retry_time: Instant::now(),
             });
         }
     }
 
-    // Compares given socket address to the trackers list of socket addresses,
-    // if it matches any one of it, then we can say that the given socket address belongs
-    // to the tracker i.e the socket address is equal to the Tracker
-    pub fn isEqualTo(&self, sAdr1: &SocketAddr) -> bool {
-        // TODO: Make it functional after brekage
-        //if let Some(ref sAdresses) = self.socketAddrs {
-        //for sAdr2 in sAdresses {
-        //return *sAdr1 == *sAdr2;
-        //}
-        //}
-        false
+    /// Starts running the tracker, according to its protocol, until it's stopped
+    pub async fn run(&self) {
+        let cancel = CancellationToken::new();
+        *self.cancel.lock().await = cancel.clone();
+
+        // Every run is a new session of the torrent, as far as the tracker is concerned
+        if !self.carried_over.swap(false) {
+            self.event.store(AnnounceEvent::Started);
+        }
+
+        let run_protocol = async {
+            match self.protocol {
+                TrackerProtocol::UDP => {
+                    loop {
+                        self.resolveTracker().await;
+                        if self.tracker_state() == TrackerState::DNSResolved {
+                            break;
+                        }
+                        sleep(DNS_RETRY_INTERVAL).await;
+                    }
+                    self.run_me().await
+                }
+                TrackerProtocol::TCP => self.run_http().await,
+            }
+        };
+
+        tokio::select! {
+            _ = run_protocol => {}
+            _ = cancel.cancelled() => {}
+        }
     }
 
-    pub async fn run(&self, socket: Arc<UdpSocket>) {
-        //self.resolveTracker()
+    /// Stops running the tracker and lets it know that we're no longer downloading or seeding
+    /// the torrent, unless it was never announced to
+    pub async fn stop(&self) {
+        self.halt().await;
+
+        if self.event.load() != AnnounceEvent::Started {
+            let _ = timeout(STOPPED_TIMEOUT, async {
+                match self.protocol {
+                    TrackerProtocol::UDP => {
+                        self.announce(AnnounceEvent::Stopped).await;
+                    }
+                    TrackerProtocol::TCP => {
+                        if let Ok(client) = reqwest::Client::builder().timeout(STOPPED_TIMEOUT).build() {
+                            let _ = self.send_http_announce_request(&client, AnnounceEvent::Stopped).await;
+                        }
+                    }
+                }
+            })
+            .await;
+        }
+        self.set_tracker_state(TrackerState::Idle);
     }
 
-    /// Starts running the tracker
-    /// socket => Socket through which the tracker will send UDP request and receive UDP response
-    pub async fn run_me(&self, socket: Arc<UdpSocket>) {
-        // A timeout duration for all types of responses
-        let timeout_duration = |n: u64| Duration::from_secs(15 + 2 ^ n);
+    /// Stops running the tracker without letting it know, it's used when the torrent carries on
+    /// with another tracker, see [Tracker::take_over]
+    pub async fn halt(&self) {
+        self.cancel.lock().await.cancel();
+    }
 
-        let mut no_of_times_connect_request_timeout = 0;
+    /// Carries on the session of the torrent with the given tracker, which has the same URL, so
+    /// that this tracker isn't announced to with a "started" event again, nor with the "tracker id"
+    /// or the numbers of the scrape lost
+    ///
+    /// It's used when a torrent added through a magnet URI has received its "info" field, and is
+    /// run again as another [TorrentFile](crate::core::TorrentFile) with the same trackers
+    pub async fn take_over(&self, tracker: &Tracker) {
+        self.event.store(tracker.event.load());
+        self.carried_over.store(true);
+        *self.tracker_id.lock().await = tracker.tracker_id.lock().await.clone();
+        self.scrape_stats.store(tracker.scrape_stats.load());
+        self.last_scrape.store(tracker.last_scrape.load());
+    }
+
+    /// Lets the tracker know right away that the entire torrent has been downloaded, unless the
+    /// tracker hasn't even been told that the torrent was started
+    pub fn announce_completed(&self) {
+        if self.event.compare_exchange(AnnounceEvent::None, AnnounceEvent::Completed).is_ok() {
+            self.announce_now.notify_one();
+        }
+    }
+
+    /// Announces to the tracker right away, rather than waiting for the interval it asked for or
+    /// for the retry after a failure, it takes effect once the tracker is running
+    pub fn reannounce(&self) {
+        self.announce_now.notify_one();
+    }
+
+    /// Scrapes the tracker right away, outside of [SCRAPE_INTERVAL], gives back whether the
+    /// numbers were received
+    ///
+    /// Only the UDP trackers are scraped, so it's always "false" for a HTTP or HTTPS tracker
+    pub async fn scrape_now(&self) -> bool {
+        if self.protocol != TrackerProtocol::UDP {
+            return false;
+        }
+        // The state the tracker was in before the scrape is shown again after it
+        let tracker_state = self.tracker_state();
+        if self.socketAddrs.lock().await.is_empty() {
+            self.resolveTracker().await;
+        }
+        let scraped = self.scrape().await.is_some();
+        self.restore_tracker_state(tracker_state);
+        scraped
+    }
+
+    /// Shows the given state again after a scrape, unless an announce has changed the state since,
+    /// i.e the state is still one the scrape has gone through
+    fn restore_tracker_state(&self, tracker_state: TrackerState) {
+        let mut state = match self.tracker_state.write() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if matches!(
+            *state,
+            TrackerState::DNSResolving
+                | TrackerState::DNSResolved
+                | TrackerState::DNSUnresolved { .. }
+                | TrackerState::WaitingForConnectResponse
+                | TrackerState::WaitingForScrapeResponse
+        ) {
+            *state = tracker_state;
+        }
+    }
+
+    /// Marks the event sent on the announce that just went through as sent, unless some other
+    /// event has come up in the meantime
+    fn event_sent(&self, event: AnnounceEvent) {
+        let _ = self.event.compare_exchange(event, AnnounceEvent::None);
+    }
+
+    /// The path and query of the announce URL, sent along with the UDP announces so that the
+    /// trackers that authenticate through a passkey in the URL can tell who we are (BEP41)
+    fn url_data(&self) -> String {
+        let mut url_data = self.address.path().to_string();
+        if let Some(query) = self.address.query() {
+            url_data.push('?');
+            url_data.push_str(query);
+        }
+        url_data
+    }
+
+    /// What the tracker is currently doing, or why it failed
+    pub fn tracker_state(&self) -> TrackerState {
+        match self.tracker_state.read() {
+            Ok(tracker_state) => tracker_state.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub fn set_tracker_state(&self, tracker_state: TrackerState) {
+        match self.tracker_state.write() {
+            Ok(mut state) => *state = tracker_state,
+            Err(poisoned) => *poisoned.into_inner() = tracker_state,
+        }
+    }
 
+    /// Whether the tracker has been announced to since it was last run
+    pub fn has_announced(&self) -> bool {
+        self.event.load() != AnnounceEvent::Started
+    }
+
+    /// Whether the last attempt to announce to the tracker failed, may it be because its DNS
+    /// couldn't be resolved, the tracker couldn't be reached or it refused the announce
+    pub fn has_failed(&self) -> bool {
+        matches!(
+            self.tracker_state(),
+            TrackerState::AnnounceFailed { .. } | TrackerState::Error { .. } | TrackerState::DNSUnresolved { .. }
+        )
+    }
+
+    /// Announces to the HTTP or HTTPS tracker forever, waiting for the interval the tracker asks
+    /// for between each announce, or backing off when the announce fails
+    async fn run_http(&self) {
+        let client = match reqwest::Client::builder().timeout(HTTP_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(_) => return,
+        };
+
+        let mut no_of_times_announce_failed = 0;
         loop {
-            let sendConReq = self.sendConnectRequest(socket.clone());
-            match timeout(timeout_duration(no_of_times_connect_request_timeout), sendConReq).await {
-                Ok(v) => match v {
-                    // TODO : Replace with get_connect_response();
-                    Ok(_) => match self.getResponse().await {
-                        Some(res) => {
-                            // Save the received ConnectResponse in self.connect_response
-                            {
-                                let mut connect_response = self.connect_response.lock().await;
-                                *connect_response = res;
-                            }
-
-                            // According to BEP-15, client can use a Connection ID until one minute after it has received it.
-                            //
-                            // This means we can't use the connection_id stored inside of ConnectResponse after 1 minute
-                            let connection_id_timeout_duration = Duration::from_secs(60);
-                            let duration_since_connect_response = Instant::now();
-
-                            'announce: loop {
-                                let now = Instant::now();
-                                let now = now.duration_since(duration_since_connect_response);
-
-                                let mut no_of_times_announce_request_timeout = 0;
-                                if now <= connection_id_timeout_duration {
-                                    match timeout(
-                                        timeout_duration(no_of_times_announce_request_timeout),
-                                        self.send_announce_request(socket.clone()),
-                                    )
-                                    .await
-                                    {
-                                        Ok(vv) => {
-                                            match vv {
-                                                // TODO : Replace with get_announce_response();
-                                                Ok(_) => match self.getResponse().await {
-                                                    Some(res) => {
-                                                        if let TrackerResponse::AnnounceResponse(ref ar) = res {
-                                                            //println!("The interval is {}", ar.interval);
-                                                            let sleep_duration = Duration::from_secs(ar.interval as u64);
-                                                            {
-                                                                for peer_socket_adr in ar.peersAddresses.clone() {
-                                                                    let peer = Peer::new(peer_socket_adr, self.torrent_state.clone());
-                                                                    self.peer_sender.send(peer);
-                                                                }
-                                                                let mut announce_response = self.announce_response.lock().await;
-                                                                *announce_response = res;
-                                                            }
-                                                            sleep(sleep_duration).await;
-                                                            break 'announce;
-                                                        }
-
-                                                        // Save the received AnnounceResponse in self.announannounce_response
-                                                    }
-                                                    None => {
-                                                        //println!("GOT ERROR");
-                                                    }
-                                                },
-                                                Err(e) => {
-                                                    // Error while sending AnnounceRequest, probably some kind of socket issue
-                                                    //println!("CONNECT REQUEST SOCKET ISSUE {:?}", e.to_string());
-                                                    sleep(Duration::from_secs(1000)).await;
-                                                    // TODO : Replace this with some actual solution rather than sleeping
-                                                }
-                                            }
-                                        }
-                                        Err(_) => {
-                                            if no_of_times_announce_request_timeout <= 8 {
-                                                no_of_times_announce_request_timeout += 1;
-                                            }
-                                            // Announce Request timeout error
-                                        }
-                                    }
-                                }
-                            }
+            self.set_tracker_state(TrackerState::WaitingForAnnounceResponse);
+
+            let event = self.event.load();
+            let response = self.send_http_announce_request(&client, event).await;
+            let retry_interval = retry_interval(no_of_times_announce_failed);
+            let sleep_duration = match response {
+                Ok(ref res) if res.failure_reason.is_none() => {
+                    self.event_sent(event);
+                    no_of_times_announce_failed = 0;
+                    for (peer_socket_adr, peer_id) in res.peers.clone() {
+                        let mut peer = Peer::new(peer_socket_adr, self.torrent_state.clone());
+                        if let Some(peer_id) = peer_id {
+                            peer.set_expected_peer_id(peer_id);
                         }
-                        None => {}
-                    },
-                    Err(e) => {
-                        // Error while sending ConnectRequest, probably some kind of socket issue
-                        //println!("CONNECT REQUEST SOCKET ISSUE {:?}", e.to_string());
-                        sleep(Duration::from_secs(1000)).await;
-
-                        // TODO : Replace this with some actual solution rather than sleeping
+                        let _ = self.peer_sender.send(peer);
+                    }
+                    if let Some(ref tracker_id) = res.tracker_id {
+                        *self.tracker_id.lock().await = Some(tracker_id.clone());
                     }
-                },
+
+                    let interval = Duration::from_secs(res.interval.max(res.min_interval.unwrap_or(0)));
+                    let sleep_duration = interval.max(MIN_ANNOUNCE_INTERVAL);
+                    let next_announce = Instant::now() + sleep_duration;
+                    self.set_tracker_state(match res.warning_message {
+                        Some(ref message) => TrackerState::Warning {
+                            message: message.clone(),
+                            next_announce,
+                        },
+                        None => TrackerState::Announced {
+                            next_announce,
+                        },
+                    });
+                    sleep_duration
+                }
+                Ok(ref res) => {
+                    no_of_times_announce_failed += 1;
+                    self.set_tracker_state(TrackerState::Error {
+                        message: res.failure_reason.clone().unwrap_or_default(),
+                        retry_time: Instant::now() + retry_interval,
+                    });
+                    retry_interval
+                }
                 Err(_) => {
-                    //println!("GOT TIMEOUT ERROR FOR CONNECT RESPONSE");
-                    // Connect Request timeout error
-                    if no_of_times_connect_request_timeout <= 8 {
-                        no_of_times_connect_request_timeout += 1;
+                    no_of_times_announce_failed += 1;
+                    self.set_tracker_state(TrackerState::AnnounceFailed {
+                        retry_time: Instant::now() + retry_interval,
+                    });
+                    retry_interval
+                }
+            };
+
+            if let Ok(res) = response {
+                *self.announce_response.lock().await = TrackerResponse::HttpAnnounceResponse(res);
+            }
+
+            tokio::select! {
+                _ = sleep(sleep_duration) => {}
+                // There's an event the tracker must know about right away
+                _ = self.announce_now.notified() => {}
+            }
+        }
+    }
+
+    /// Makes an Announce Request to the HTTP or HTTPS tracker and parses the bencoded response
+    ///
+    /// A response with a failure reason is still an Ok(), it's up to the caller to check it
+    async fn send_http_announce_request(
+        &self,
+        client: &reqwest::Client,
+        event: AnnounceEvent,
+    ) -> Result<HttpAnnounceResponse, HttpTrackerError> {
+        let mut announce_req = HttpAnnounceRequest::new();
+        announce_req.set_info_hash(&self.torrent_state.info_hash);
+        announce_req.set_peer_id(self.torrent_state.peer_id);
+        announce_req.set_uploaded(self.torrent_state.bytes_uploaded() as u64);
+        announce_req.set_downloaded(self.torrent_state.bytes_complete() as u64);
+        announce_req.set_left(self.torrent_state.bytes_left() as u64);
+        if let Some(event) = event.http_value() {
+            announce_req.set_event(event);
+        }
+        {
+            // The port the peers can connect to us on
+            let ports = self.torrent_state.tcp_ports.lock().await;
+            announce_req.set_port(ports.first().copied().unwrap_or_default());
+        }
+        announce_req.set_key(thread_rng().gen());
+        if let Some(ref tracker_id) = *self.tracker_id.lock().await {
+            announce_req.set_tracker_id(tracker_id.clone());
+        }
+
+        let url = announce_req.to_url(&self.address).ok_or(HttpTrackerError::IncompleteRequest)?;
+        *self.announce_request.lock().await = TrackerRequest::HttpAnnounceRequest(announce_req);
+
+        let body = client.get(url).send().await?.error_for_status()?.bytes().await?;
+        HttpAnnounceResponse::from(&body)
+    }
+
+    /// Starts running the tracker, the requests are sent and the responses received through
+    /// [udp_service::UdpTrackerService] of the engine
+    ///
+    /// Announces to the tracker every interval it asks for and scrapes it in between, the
+    /// requests are retransmitted as BEP15 asks for, see [retransmit]
+    pub async fn run_me(&self) {
+        let mut no_of_times_refused = 0;
+        loop {
+            let event = self.event.load();
+            let announce_response = retransmit(|| self.announce(event)).await;
+            let next_announce = match announce_response {
+                Some(TrackerResponse::AnnounceResponse(ref ar)) => {
+                    self.event_sent(event);
+                    for peer_socket_adr in ar.peersAddresses.clone() {
+                        let peer = Peer::new(peer_socket_adr, self.torrent_state.clone());
+                        let _ = self.peer_sender.send(peer);
+                    }
+                    no_of_times_refused = 0;
+                    Instant::now() + Duration::from_secs(ar.interval.max(0) as u64).max(MIN_ANNOUNCE_INTERVAL)
+                }
+                Some(TrackerResponse::Error(ref er)) => {
+                    let retry_interval = retry_interval(no_of_times_refused);
+                    no_of_times_refused += 1;
+                    self.set_tracker_state(TrackerState::Error {
+                        message: er.message.clone(),
+                        retry_time: Instant::now() + retry_interval,
+                    });
+                    tokio::select! {
+                        _ = sleep(retry_interval) => {}
+                        _ = self.announce_now.notified() => {}
                     }
+                    continue;
                 }
+                _ => {
+                    // No response even after all the retransmissions, so start over
+                    self.set_tracker_state(TrackerState::AnnounceFailed {
+                        retry_time: Instant::now(),
+                    });
+                    continue;
+                }
+            };
+            if let Some(res) = announce_response {
+                *self.announce_response.lock().await = res;
+            }
+
+            self.set_tracker_state(TrackerState::Announced {
+                next_announce,
+            });
+            tokio::select! {
+                _ = self.scrape_until(next_announce) => {}
+                // There's an event the tracker must know about right away
+                _ = self.announce_now.notified() => {}
+            }
+        }
+    }
+
+    /// Makes a single AnnounceRequest to the tracker and waits for its response, the tracker is
+    /// connected to first if there's no connection_id that can be used
+    async fn announce(&self, event: AnnounceEvent) -> Option<TrackerResponse> {
+        let socket_adr = self.udp_socket_adr().await?;
+        let connection_id = match self.connect(socket_adr).await? {
+            Ok(connection_id) => connection_id,
+            Err(error_response) => return Some(TrackerResponse::Error(error_response)),
+        };
+        self.set_tracker_state(TrackerState::WaitingForAnnounceResponse);
+        let mut pending = self.engine_state.udp_trackers.register(socket_adr);
+        self.send_announce_request(socket_adr, pending.transaction_id, connection_id, event)
+            .await
+            .ok()?;
+
+        let d = pending.recv().await?;
+        if self.isErrorResponse(&d, pending.transaction_id) {
+            return Some(TrackerResponse::Error(ErrorResponse::from(&d).ok()?));
+        }
+        // Check whether the packet is atleast 20 bytes
+        if d.len() < 20 || !is_response_to(&d, 1, pending.transaction_id) {
+            return None;
+        }
+        let announce_response = AnnounceResponse::from(&d, socket_adr.is_ipv6()).ok()?;
+        Some(TrackerResponse::AnnounceResponse(announce_response))
+    }
+
+    /// Gives the connection_id to be used for the next request to the tracker at the given socket
+    /// address, the one received last by any torrent is used until it expires, after which a
+    /// ConnectRequest is made to get a new one
+    ///
+    /// Gives back the ErrorResponse instead, if the tracker replied with one
+    async fn connect(&self, socket_adr: SocketAddr) -> Option<Result<i64, ErrorResponse>> {
+        let udp_trackers = &self.engine_state.udp_trackers;
+        if let Some(connection_id) = udp_trackers.connection_id(&socket_adr).await {
+            return Some(Ok(connection_id));
+        }
+
+        self.set_tracker_state(TrackerState::WaitingForConnectResponse);
+        let mut pending = udp_trackers.register(socket_adr);
+        self.sendConnectRequest(socket_adr, pending.transaction_id).await.ok()?;
+
+        let d = pending.recv().await?;
+        if self.isErrorResponse(&d, pending.transaction_id) {
+            return Some(Err(ErrorResponse::from(&d).ok()?));
+        }
+        // Check whether the packet is atleast 16 bytes
+        if d.len() < 16 || !is_response_to(&d, 0, pending.transaction_id) {
+            return None;
+        }
+        let connect_response = ConnectResponse::from(&d).ok()?;
+        let connection_id = connect_response.connection_id;
+        udp_trackers.set_connection_id(socket_adr, connection_id).await;
+        *self.connect_response.lock().await = TrackerResponse::ConnectResponse(connect_response);
+        Some(Ok(connection_id))
+    }
+
+    /// The socket address the requests are sent to, i.e the first of the socket addresses of the
+    /// tracker that can be reached through [udp_service::UdpTrackerService]
+    ///
+    /// The socket addresses are in the order the DNS resolver prefers them, so the IPv6 address
+    /// of the tracker gets used on a host with IPv6 connectivity
+    async fn udp_socket_adr(&self) -> Option<SocketAddr> {
+        for socket_adr in self.socketAddrs.lock().await.iter() {
+            if self.engine_state.udp_trackers.can_reach(socket_adr).await {
+                return Some(*socket_adr);
             }
-            //println!("ENDED PRETTY QUICK");
         }
+        None
     }
 
-    /// Creates a ConnectRequest instance and tries to send it through the given UDP Socket to the
-    /// given UDP Socket Address
+    /// Creates a ConnectRequest instance with the given transaction_id and tries to send it to
+    /// the given UDP Socket Address
     ///
     /// If ConnectRequest is sent, then instance of ConnectRequest is stored in [Tracker] "connect_req" field
     ///
     /// Error :
     /// The only error is IO error passed by tokio::net::UDPSocket
     ///
-    /// Panic :
-    /// Panic might occur if there is no any Vector inside of self.socketAddrs  
-    /// Even if there is a vector, if there is no item at index 0, then a panic will occur
-    ///
-    pub async fn sendConnectRequest(&self, socket: Arc<UdpSocket>) -> Result<(), io::Error> {
-        let connect_req = ConnectRequest::new();
+    pub async fn sendConnectRequest(&self, socket_adr: SocketAddr, transaction_id: i32) -> Result<(), io::Error> {
+        let mut connect_req = ConnectRequest::new();
+        connect_req.transaction_id = transaction_id;
         let connect_req_bytes = connect_req.serializeToBytes();
-        // Assumption : Only those Trackers come upto this point whose DNS is resolved from the given URL and contains atleast one resolved socket address
-        // that's why unwrap() is called without any worries;
-        //
-        // TODO : Let's say it contains 10 socket addresses in self.socketAddrs, how to to decide
-        // which one to use
-        //
-        // NOTE : One can use the exact concept that a browser makes use of in order to decide the
-        // socket address to be used right now and in the future, as a round robin or ?. Currenlty
-        // we are deciding to use the socket at the index 0
-        let socketAddrs = {
-            let socket_addresses = self.socketAddrs.lock().await;
-            // TODO : Make choosable among other indices too
-            socket_addresses[0]
-        };
 
-        return match socket.send_to(connect_req_bytes.as_ref(), socketAddrs).await {
+        return match self.engine_state.udp_trackers.send_to(connect_req_bytes.as_ref(), socket_adr).await {
             Ok(_) => {
                 let mut con_req = self.connect_request.lock().await;
                 *con_req = TrackerRequest::ConnectRequest(connect_req);
@@ -386,35 +810,38 @@ impl Tracker {
         };
     }
 
-    pub async fn send_announce_request(&self, socket: Arc<UdpSocket>) -> Result<(), io::Error> {
+    /// Creates an AnnounceRequest instance with the given transaction_id and connection_id, and
+    /// tries to send it to the given UDP Socket Address
+    ///
+    /// If AnnounceRequest is sent, then instance of AnnounceRequest is stored in [Tracker] "announce_request" field
+    pub async fn send_announce_request(
+        &self,
+        socket_adr: SocketAddr,
+        transaction_id: i32,
+        connection_id: i64,
+        event: AnnounceEvent,
+    ) -> Result<(), io::Error> {
         let mut announce_req = AnnounceRequest::new();
+        announce_req.set_connection_id(connection_id);
+        announce_req.set_transaction_id(transaction_id);
+        announce_req.set_info_hash(&self.torrent_state.info_hash);
+        announce_req.set_peer_id(self.torrent_state.peer_id);
+        announce_req.set_downloaded(self.torrent_state.bytes_complete() as i64);
+        announce_req.set_uploaded(self.torrent_state.bytes_uploaded() as i64);
+        announce_req.set_left(self.torrent_state.bytes_left() as i64);
+        announce_req.set_event(event.udp_value());
         {
-            let connect_response = self.connect_response.lock().await;
-            if let TrackerResponse::ConnectResponse(ref c_res) = *connect_response {
-                announce_req.set_connection_id(c_res.connection_id);
-                announce_req.set_transaction_id(c_res.transaction_id);
-                announce_req.set_info_hash(&self.torrent_state.info_hash);
-                announce_req.set_downloaded(1000); // TODO : Replace with actual downloaded bytes
-                announce_req.set_uploaded(1000); // TODO : Replace with actual uploaded bytes
-                announce_req.set_left(5000); // TODO : Replace with actual left bytes
-                {
-                    let ports = self.torrent_state.udp_ports.lock().await;
-                    if let Some(port) = ports.get(0) {
-                        announce_req.set_port(*port as i16);
-                    }
-                }
-                let mut rng = thread_rng();
-                announce_req.set_key(rng.gen());
+            // The port the peers can connect to us on
+            let ports = self.torrent_state.tcp_ports.lock().await;
+            if let Some(port) = ports.first() {
+                announce_req.set_port(*port as i16);
             }
         }
+        announce_req.set_key(thread_rng().gen());
+        announce_req.set_url_data(self.url_data().as_bytes());
 
         if let Some(announce_req_bytes) = announce_req.serialize_to_bytes() {
-            let socketAddrs = {
-                let socket_addresses = self.socketAddrs.lock().await;
-                // TODO : Make choosable among other indices too
-                socket_addresses[0]
-            };
-            return match socket.send_to(&announce_req_bytes, socketAddrs).await {
+            return match self.engine_state.udp_trackers.send_to(&announce_req_bytes, socket_adr).await {
                 Ok(_) => {
                     let mut ann_req = self.announce_request.lock().await;
                     *ann_req = TrackerRequest::AnnounceRequest(announce_req);
@@ -430,6 +857,116 @@ impl Tracker {
         Ok(())
     }
 
+    /// Scrapes the tracker every [SCRAPE_INTERVAL] until the given instant, which is the time of
+    /// the next announce
+    ///
+    /// A scrape is skipped when the same tracker of another torrent has scraped this torrent
+    /// along with its own in the meantime
+    async fn scrape_until(&self, until: Instant) {
+        loop {
+            let next_scrape = match self.last_scrape.load() {
+                Some(last_scrape) => last_scrape + SCRAPE_INTERVAL,
+                None => Instant::now(),
+            };
+            if next_scrape >= until {
+                sleep(until.saturating_duration_since(Instant::now())).await;
+                return;
+            }
+
+            sleep(next_scrape.saturating_duration_since(Instant::now())).await;
+            if self
+                .last_scrape
+                .load()
+                .is_some_and(|last_scrape| last_scrape + SCRAPE_INTERVAL > Instant::now())
+            {
+                continue;
+            }
+
+            self.scrape_now().await;
+        }
+    }
+
+    /// Makes a ScrapeRequest to the tracker, using the same connection_id as the announce if it
+    /// hasn't expired yet, and hands over the numbers received to the trackers of all the
+    /// torrents scraped
+    ///
+    /// Gives back "None" if any of the requests couldn't be sent or its response wasn't received
+    /// within [SCRAPE_TIMEOUT], it isn't retransmitted as it's made again after
+    /// [SCRAPE_INTERVAL] anyway
+    async fn scrape(&self) -> Option<()> {
+        self.last_scrape.store(Some(Instant::now()));
+
+        let (scrape_req, scrape_response) = timeout(SCRAPE_TIMEOUT, async {
+            let socket_adr = self.udp_socket_adr().await?;
+            let connection_id = self.connect(socket_adr).await?.ok()?;
+            self.set_tracker_state(TrackerState::WaitingForScrapeResponse);
+            let mut pending = self.engine_state.udp_trackers.register(socket_adr);
+            let scrape_req = self
+                .send_scrape_request(socket_adr, pending.transaction_id, connection_id)
+                .await
+                .ok()?;
+
+            let d = pending.recv().await?;
+            if !is_response_to(&d, 2, pending.transaction_id) {
+                return None;
+            }
+            Some((scrape_req, ScrapeResponse::from(&d).ok()?))
+        })
+        .await
+        .ok()??;
+        self.share_scrape_stats(&scrape_req.info_hashes, &scrape_response).await;
+        *self.scrape_response.lock().await = TrackerResponse::ScrapeResponse(scrape_response);
+        Some(())
+    }
+
+    /// Creates a ScrapeRequest instance for this torrent, along with all the other torrents of
+    /// the engine that have the same tracker, and tries to send it to the given UDP Socket Address
+    ///
+    /// Gives back the ScrapeRequest that was sent, so that the numbers in the response can be
+    /// matched with the info hashes in it
+    pub async fn send_scrape_request(
+        &self,
+        socket_adr: SocketAddr,
+        transaction_id: i32,
+        connection_id: i64,
+    ) -> Result<ScrapeRequest, io::Error> {
+        let mut scrape_req = ScrapeRequest::new();
+        scrape_req.set_connection_id(connection_id);
+        scrape_req.set_transaction_id(transaction_id);
+        scrape_req.add_info_hash(&self.torrent_state.info_hash);
+        for state in self.engine_state.torrents_with_tracker(&self.address).await {
+            if state.info_hash != self.torrent_state.info_hash {
+                scrape_req.add_info_hash(&state.info_hash);
+            }
+        }
+
+        let scrape_req_bytes = scrape_req
+            .serialize_to_bytes()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "incomplete ScrapeRequest"))?;
+        self.engine_state.udp_trackers.send_to(&scrape_req_bytes, socket_adr).await?;
+        Ok(scrape_req)
+    }
+
+    /// Stores the numbers of each torrent scraped, given by the info hashes in the order they were
+    /// scraped in, into the tracker of the torrent with the same address as this one
+    async fn share_scrape_stats(&self, info_hashes: &[Vec<u8>], scrape_res: &ScrapeResponse) {
+        let now = Instant::now();
+        for (info_hash, stats) in info_hashes.iter().zip(scrape_res.stats.iter()) {
+            if *info_hash == self.torrent_state.info_hash {
+                self.scrape_stats.store(Some(*stats));
+                continue;
+            }
+
+            if let Some(state) = self.engine_state.torrent(info_hash).await {
+                let trackers = state.trackers.read().await;
+                for tracker in trackers.iter().flatten().filter(|tracker| tracker.address == self.address) {
+                    tracker.scrape_stats.store(Some(*stats));
+                    tracker.last_scrape.store(Some(now));
+                }
+            }
+        }
+    }
+
     /// It pushes the peers achieved from Announce into the "peers" field of
     /// the "state" field of [Tracker]
     ///
@@ -447,117 +984,14 @@ impl Tracker {
         }
 
         if !DOES_PEER_ALREADY_EXIST {
-            peers.push(Peer::new(peer_socket_adr, self.torrent_state.clone()));
-        }
-    }
-
-    /// It will return "None", when the channel is closed
-    pub async fn getResponse(&self) -> Option<TrackerResponse> {
-        let NONE = Some(TrackerResponse::None);
-        let (_, rx) = self.udp_channel.as_ref().unwrap();
-        let mut rx = rx.lock().await;
-
-        return match rx.recv().await {
-            Some(d) => {
-                // Check for ConnectResponse
-                if self.isConnectResponse(&d).await {
-                    //println!("GOT A CONNECT RESPONSE HERE");
-                    //println!("{:?}", self.torrents);
-
-                    return if let Ok(cr) = ConnectResponse::from(&d) {
-                        Some(TrackerResponse::ConnectResponse(cr))
-                    } else {
-                        NONE
-                    };
-                } else if self.isAnnounceResponse(&d).await {
-                    //println!("GOT ANNOUNCE RESPONSE");
-
-                    return if let Ok(ar) = AnnounceResponse::from(&d) {
-                        Some(TrackerResponse::AnnounceResponse(ar))
-                    } else {
-                        NONE
-                    };
-                } else {
-                    NONE
-                }
-            }
-            None => None,
-        };
-    }
-
-    /// Checks from the given buffer, if the given response is a ConnectResponse or not
-    pub async fn isConnectResponse(&self, d: &[u8]) -> bool {
-        let mut IS_CONNECT_RESPONSE = false;
-
-        // Check whether the packet is atleast 16 bytes
-        if d.len() >= 16 {
-            let mut action_bytes = &d[0..=3];
-            if let Ok(action) = ReadBytesExt::read_i32::<BigEndian>(&mut action_bytes) {
-                // Check whether the action is Connect i.e 0
-                if action == 0 {
-                    let mut transaction_id_bytes = &d[4..=7];
-                    if let Ok(transaction_id) = ReadBytesExt::read_i32::<BigEndian>(&mut transaction_id_bytes) {
-                        let connect_req_transaction_id = {
-                            let connect_request = self.connect_request.lock().await;
-                            if let TrackerRequest::ConnectRequest(ref connect_request) = *connect_request {
-                                connect_request.transaction_id
-                            } else {
-                                // Make sure that you store a ConnectRequest in the place of
-                                // "self.connect_request"
-                                // A random number just to make the function false in the next
-                                // step i.e comparing the connect req
-                                0
-                            }
-                        };
-                        // Check whether the transaction_id in ConnectRequest matches with the ConnectResponse or not
-                        IS_CONNECT_RESPONSE = connect_req_transaction_id == transaction_id;
-                    }
-                }
-            }
+            peers.push(Arc::new(Peer::new(peer_socket_adr, self.torrent_state.clone())));
         }
-        return IS_CONNECT_RESPONSE;
-    }
-
-    /// Checks from the given buffer, if it's a ConnectResponse or not
-    pub async fn isAnnounceResponse(&self, d: &[u8]) -> bool {
-        let mut IS_ANNOUNCE_RESPONSE = false;
-        // Check whether the packet is atleast 16 bytes
-        if d.len() >= 20 {
-            let mut action_bytes = &d[0..=3];
-            if let Ok(action) = ReadBytesExt::read_i32::<BigEndian>(&mut action_bytes) {
-                // Check whether the action is Announce i.e 1
-                if action == 1 {
-                    let mut transaction_id_bytes = &d[4..=7];
-                    if let Ok(transaction_id) = ReadBytesExt::read_i32::<BigEndian>(&mut transaction_id_bytes) {
-                        let announce_req_transaction_id = {
-                            let announce_request = self.announce_request.lock().await;
-                            if let TrackerRequest::AnnounceRequest(ref announce_request) = *announce_request {
-                                announce_request.transaction_id.unwrap()
-                            } else {
-                                // Make sure that you store a AnnounceRequest in the place of "self.announce_request"
-                                //
-                                // A random number just to make the function false in the next
-                                // step i.e comparing the connect re
-                                0
-                            }
-                        };
-                        IS_ANNOUNCE_RESPONSE = announce_req_transaction_id == transaction_id;
-                    }
-                }
-            }
-        }
-
-        return IS_ANNOUNCE_RESPONSE;
-    }
-
-    pub async fn isScrapeResponse(&self, d: &[u8]) -> bool {
-        false // Dummy
     }
 
-    /// Checks from the given buffer, if the given response is an Error or not
-    pub async fn isErrorResponse(&self, d: &[u8]) -> bool {
-        //let action = ReadBytesExt::read_i32::<BigEndian>(&mut action_bytes);
-        false //dummy
+    /// Checks from the given buffer, if it's the ErrorResponse to the request with the given
+    /// transaction_id or not
+    pub fn isErrorResponse(&self, d: &[u8], transaction_id: i32) -> bool {
+        is_response_to(d, 3, transaction_id)
     }
 }
 
@@ -565,7 +999,9 @@ impl Tracker {
 pub enum TrackerResponse {
     ConnectResponse(ConnectResponse),
     AnnounceResponse(AnnounceResponse),
-    Error,
+    HttpAnnounceResponse(HttpAnnounceResponse),
+    ScrapeResponse(ScrapeResponse),
+    Error(ErrorResponse),
     None,
 }
 
@@ -573,6 +1009,8 @@ pub enum TrackerResponse {
 pub enum TrackerRequest {
     ConnectRequest(ConnectRequest),
     AnnounceRequest(AnnounceRequest),
+    HttpAnnounceRequest(HttpAnnounceRequest),
+    ScrapeRequest(ScrapeRequest),
     None,
 }
 
@@ -0,0 +1,180 @@
+use byteorder::{BigEndian, ReadBytesExt};
+use std::{future::Future, time::Duration};
+use tokio::time::{sleep_until, timeout_at, Instant};
+
+/// A connection_id can be used for one minute after it's received, after which the tracker has
+/// to be connected to again
+pub const CONNECTION_ID_LIFETIME: Duration = Duration::from_secs(60);
+
+/// Time waited for the response of the first request, the nth retransmission waits 15·2ⁿ seconds
+const BASE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// "n" goes upto 8 i.e 3840 seconds, the request fails if it times out after that
+pub const MAX_RETRANSMISSIONS: u32 = 8;
+
+/// Time waited for the response of a request that has been retransmitted "n" times
+pub fn request_timeout(n: u32) -> Duration {
+    BASE_TIMEOUT * 2_u32.pow(n.min(MAX_RETRANSMISSIONS))
+}
+
+/// Makes the request through the given closure and retransmits it every time its response
+/// isn't received in time, as BEP15 asks for
+///
+/// The closure sends the request and waits for its response, "None" from it means the response
+/// won't come, e.g the request couldn't be sent, in which case the rest of the timeout is waited
+/// out anyway before retransmitting. Gives back "None" once the request has timed out after
+/// [MAX_RETRANSMISSIONS].
+pub async fn retransmit<T, F, Fut>(mut request: F) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<T>>, {
+    for n in 0..=MAX_RETRANSMISSIONS {
+        let deadline = Instant::now() + request_timeout(n);
+        if let Ok(Some(response)) = timeout_at(deadline, request()).await {
+            return Some(response);
+        }
+        sleep_until(deadline).await;
+    }
+    None
+}
+
+/// Checks from the given buffer, if it's the response with the given action to the request with
+/// the given transaction_id
+///
+/// Every response starts with the action and the transaction_id, both 32-bit integers
+pub fn is_response_to(d: &[u8], action: i32, transaction_id: i32) -> bool {
+    if d.len() < 8 {
+        return false;
+    }
+
+    let mut action_bytes = &d[0..=3];
+    let mut transaction_id_bytes = &d[4..=7];
+    matches!(ReadBytesExt::read_i32::<BigEndian>(&mut action_bytes), Ok(a) if a == action)
+        && matches!(ReadBytesExt::read_i32::<BigEndian>(&mut transaction_id_bytes), Ok(t) if t == transaction_id)
+}
+
+/// Keeps the connection_id received from the tracker, until it expires
+///
+/// The same connection_id is used for both the announce and the scrape, so that the tracker
+/// isn't connected to before each one of them
+#[derive(Debug, Default)]
+pub struct ConnectionIdCache {
+    connection_id: Option<(i64, Instant)>,
+}
+
+impl ConnectionIdCache {
+    /// Gives the connection_id, unless it has expired or none has been received yet
+    pub fn get(&self) -> Option<i64> {
+        match self.connection_id {
+            Some((connection_id, received_at)) if received_at.elapsed() < CONNECTION_ID_LIFETIME => Some(connection_id),
+            _ => None,
+        }
+    }
+
+    /// Keeps the connection_id that has just been received
+    pub fn set(&mut self, connection_id: i64) {
+        self.connection_id = Some((connection_id, Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{BufMut, BytesMut};
+    use std::cell::{Cell, RefCell};
+    use tokio::time;
+
+    #[test]
+    fn timeout_doubles_upto_n_8() {
+        assert_eq!(request_timeout(0), Duration::from_secs(15));
+        assert_eq!(request_timeout(1), Duration::from_secs(30));
+        assert_eq!(request_timeout(3), Duration::from_secs(120));
+        assert_eq!(request_timeout(8), Duration::from_secs(3840));
+        assert_eq!(request_timeout(9), Duration::from_secs(3840));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retransmits_on_every_timeout_until_n_8() {
+        let start = Instant::now();
+        let sent_at = RefCell::new(Vec::new());
+
+        let response: Option<()> = retransmit(|| {
+            sent_at.borrow_mut().push(start.elapsed().as_secs());
+            std::future::pending()
+        })
+        .await;
+
+        assert_eq!(response, None);
+        assert_eq!(*sent_at.borrow(), vec![0, 15, 45, 105, 225, 465, 945, 1905, 3825]);
+        assert_eq!(start.elapsed(), Duration::from_secs(15 * (2_u64.pow(9) - 1)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_retransmitting_once_responded() {
+        let start = Instant::now();
+        let attempts = Cell::new(0);
+
+        let response = retransmit(|| {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move {
+                if attempt < 3 {
+                    std::future::pending::<()>().await;
+                }
+                time::sleep(Duration::from_secs(5)).await;
+                Some(attempt)
+            }
+        })
+        .await;
+
+        assert_eq!(response, Some(3));
+        assert_eq!(start.elapsed(), Duration::from_secs(15 + 30 + 5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_out_the_timeout_when_request_fails() {
+        let start = Instant::now();
+        let attempts = Cell::new(0);
+
+        let response = retransmit(|| {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move { (attempt == 2).then_some(attempt) }
+        })
+        .await;
+
+        assert_eq!(response, Some(2));
+        assert_eq!(start.elapsed(), Duration::from_secs(15));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connection_id_expires_after_a_minute() {
+        let mut cache = ConnectionIdCache::default();
+        assert_eq!(cache.get(), None);
+
+        cache.set(0x41727101980);
+        assert_eq!(cache.get(), Some(0x41727101980));
+
+        time::advance(Duration::from_secs(59)).await;
+        assert_eq!(cache.get(), Some(0x41727101980));
+
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(cache.get(), None);
+
+        cache.set(7);
+        assert_eq!(cache.get(), Some(7));
+    }
+
+    #[test]
+    fn checks_action_and_transaction_id() {
+        let mut d = BytesMut::new();
+        d.put_i32(1);
+        d.put_i32(-42);
+        d.put_i32(1800);
+
+        assert!(is_response_to(&d, 1, -42));
+        assert!(!is_response_to(&d, 1, 42));
+        assert!(!is_response_to(&d, 0, -42));
+        assert!(!is_response_to(&d[..7], 1, -42));
+    }
+}
@@ -0,0 +1,197 @@
+use super::udp_connection::ConnectionIdCache;
+use crate::core::net;
+use crossbeam::atomic::AtomicCell;
+use rand::{thread_rng, Rng};
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex as StdMutex},
+};
+use tokio::{
+    net::UdpSocket,
+    sync::{oneshot, Mutex},
+};
+
+/// Ports tried one after another, until the socket gets bound to one of them
+const UDP_PORTS: RangeInclusive<u16> = 6881..=6999;
+
+/// Sends the requests of the UDP trackers of all the torrents of the engine through a single UDP
+/// socket, and hands each response over to the request with the same transaction_id
+///
+/// The connection_id received from a tracker is kept here as well, so that the torrents sharing
+/// the tracker don't connect to it one after another
+#[derive(Debug, Default)]
+pub struct UdpTrackerService {
+    /// The UDP socket shared by all the trackers, "None" until it has been bound
+    socket: Mutex<Option<Arc<UdpSocket>>>,
+
+    /// Port the socket is bound to, it's 0 until the socket has been bound
+    port: AtomicCell<u16>,
+
+    /// The requests waiting for their response, by their transaction_id
+    ///
+    /// NOTE : It's a std Mutex, as a request that's given up on, e.g on a timeout, is removed
+    /// from here on drop
+    pending: StdMutex<HashMap<i32, PendingRequest>>,
+
+    /// The connection_id of each tracker, by its socket address
+    connection_ids: Mutex<HashMap<SocketAddr, ConnectionIdCache>>,
+}
+
+/// A request sent to a tracker, for which we're waiting to get a response
+#[derive(Debug)]
+struct PendingRequest {
+    /// Socket address the request was sent to, a response from anywhere else is ignored
+    socket_adr: SocketAddr,
+
+    /// Where the response is handed over to
+    response: oneshot::Sender<Vec<u8>>,
+}
+
+/// The transaction_id reserved for a request along with the response that'll come for it, the
+/// transaction_id is released once it's dropped
+#[derive(Debug)]
+pub struct PendingResponse<'a> {
+    service: &'a UdpTrackerService,
+
+    /// The transaction_id the request must be made with
+    pub transaction_id: i32,
+
+    response: oneshot::Receiver<Vec<u8>>,
+}
+
+impl PendingResponse<'_> {
+    /// Waits for the response, it will return "None" if the service stopped receiving
+    pub async fn recv(&mut self) -> Option<Vec<u8>> {
+        (&mut self.response).await.ok()
+    }
+}
+
+impl Drop for PendingResponse<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.service.pending.lock() {
+            pending.remove(&self.transaction_id);
+        }
+    }
+}
+
+impl UdpTrackerService {
+    /// Binds the socket to the first free port out of [UDP_PORTS]
+    pub async fn bind(&self) -> io::Result<()> {
+        let mut last_error = io::Error::new(io::ErrorKind::AddrInUse, "no free port for the UDP trackers");
+        for port in UDP_PORTS {
+            match net::bind_udp(port) {
+                Ok(socket) => {
+                    *self.socket.lock().await = Some(Arc::new(socket));
+                    self.port.store(port);
+                    return Ok(());
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Port the socket is bound to, it's 0 until the socket has been bound
+    pub fn port(&self) -> u16 {
+        self.port.load()
+    }
+
+    /// Receives the responses of all the trackers forever, each response is handed over to the
+    /// request with the same transaction_id, as long as it came from the tracker the request was
+    /// sent to
+    pub async fn run(&self) {
+        let socket = match *self.socket.lock().await {
+            Some(ref socket) => socket.clone(),
+            None => return,
+        };
+
+        // A buffer of 4KiB capacity
+        let mut buf = [0; 4096];
+        loop {
+            let (len, socket_adr) = match socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(_) => continue,
+            };
+            if len < 8 {
+                continue;
+            }
+
+            // Every response has the transaction_id right after the action
+            let transaction_id = i32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+            let socket_adr = net::canonical(socket_adr);
+            let request = {
+                let Ok(mut pending) = self.pending.lock() else {
+                    continue;
+                };
+                match pending.get(&transaction_id) {
+                    Some(request) if request.socket_adr == socket_adr => pending.remove(&transaction_id),
+                    _ => None,
+                }
+            };
+            if let Some(request) = request {
+                let _ = request.response.send(buf[..len].to_vec());
+            }
+        }
+    }
+
+    /// Reserves a transaction_id that isn't used by any other request waiting for its response,
+    /// for a request to be sent to the tracker at the given socket address
+    pub fn register(&self, socket_adr: SocketAddr) -> PendingResponse<'_> {
+        let (sender, receiver) = oneshot::channel();
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let transaction_id = loop {
+            let transaction_id = thread_rng().gen();
+            if !pending.contains_key(&transaction_id) {
+                break transaction_id;
+            }
+        };
+        pending.insert(
+            transaction_id,
+            PendingRequest {
+                socket_adr,
+                response: sender,
+            },
+        );
+
+        PendingResponse {
+            service: self,
+            transaction_id,
+            response: receiver,
+        }
+    }
+
+    /// Sends the given bytes to the tracker at the given socket address
+    pub async fn send_to(&self, bytes: &[u8], socket_adr: SocketAddr) -> io::Result<()> {
+        let socket = match *self.socket.lock().await {
+            Some(ref socket) => socket.clone(),
+            None => return Err(io::Error::new(io::ErrorKind::NotConnected, "the UDP socket isn't bound")),
+        };
+        let socket_is_ipv6 = socket.local_addr()?.is_ipv6();
+        socket.send_to(bytes, net::reachable_from(socket_adr, socket_is_ipv6)).await?;
+        Ok(())
+    }
+
+    /// Whether the tracker at the given socket address can be reached, i.e any address through a
+    /// dual stack IPv6 socket, but only the IPv4 ones through an IPv4 socket
+    pub async fn can_reach(&self, socket_adr: &SocketAddr) -> bool {
+        match *self.socket.lock().await {
+            Some(ref socket) => matches!(socket.local_addr(), Ok(local_adr) if local_adr.is_ipv6() || socket_adr.is_ipv4()),
+            None => false,
+        }
+    }
+
+    /// Gives the connection_id of the tracker at the given socket address, unless it has expired
+    /// or none has been received yet
+    pub async fn connection_id(&self, socket_adr: &SocketAddr) -> Option<i64> {
+        self.connection_ids.lock().await.get(socket_adr)?.get()
+    }
+
+    /// Keeps the connection_id that has just been received from the tracker at the given socket
+    /// address, for all the torrents to use
+    pub async fn set_connection_id(&self, socket_adr: SocketAddr, connection_id: i64) {
+        self.connection_ids.lock().await.entry(socket_adr).or_default().set(connection_id);
+    }
+}
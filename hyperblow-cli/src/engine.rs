@@ -15,10 +15,22 @@
 //// 1. It has its own internal thread(s), runtime, to dowload the torrent.
 //// 2. The only abstraction engine is going to share is EngineHandle,
 ////    which can control core behaviours of engine such as shut it down
-use crate::core::{tracker::Tracker, TorrentFile};
+use crate::core::{
+    magnetURI::MagnetURI,
+    peer::{
+        choker::DEFAULT_ENGINE_UPLOAD_SLOTS,
+        listener::{PeerListener, DEFAULT_ENGINE_MAX_CONNECTIONS},
+        Peer,
+    },
+    state::{DownState, EngineState},
+    torrentFile::TrackerEditError,
+    tracker::{Tracker, TrackerState},
+    TorrentFile,
+};
+use reqwest::Url;
 use std::{sync::Arc, thread::JoinHandle};
 use tokio::{
-    runtime::{Builder, Runtime},
+    runtime::{Builder, Handle, Runtime},
     sync::{
         mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
         Mutex, RwLock,
@@ -27,12 +39,12 @@ use tokio::{
 
 #[derive(Debug)]
 enum Torrent {
-    //MagnetUriTorrent(i32),
+    MagnetUriTorrent(Arc<MagnetURI>),
     FileTorrent(Arc<TorrentFile>),
 }
 
 pub enum TorrentSource {
-    //MagnetURI(String),
+    MagnetURI(String),
     FilePath(String),
 }
 
@@ -40,9 +52,16 @@ pub struct Engine {
     /// Stores all the torrents that are to be downloaded
     pub torrents: Arc<Mutex<Vec<Arc<TorrentHandle>>>>,
 
+    /// State shared by all the torrents of the engine
+    state: Arc<EngineState>,
+
     /// The thread that spawns the tokio runtime, where all the torrents download is gonna take place
     engine_thread_handle: JoinHandle<()>,
 
+    /// Handle to the tokio runtime of the engine_thread, used to control the torrents from the
+    /// ui_thread
+    runtime: Handle,
+
     /// An internal sender that sends the newly spawned torrent source from the ui_thread into the engine_thread
     trnt_thread_sender: UnboundedSender<TorrentSource>,
 
@@ -51,9 +70,15 @@ pub struct Engine {
 }
 
 impl Engine {
-    /// Creates an instance of the engine
-    pub fn new() -> Arc<Self> {
+    /// Creates an instance of the engine, that downloads the torrents into the given directory
+    pub fn new(download_directory: String) -> Arc<Self> {
         let torrents = Arc::default();
+        let state = Arc::new(EngineState::new(
+            DEFAULT_ENGINE_UPLOAD_SLOTS,
+            DEFAULT_ENGINE_MAX_CONNECTIONS,
+            download_directory,
+        ));
+        let engine_state = state.clone();
 
         // Receivies the torrent source from ui_thread and sends it into the engine thread
         let (tsrc_sd, mut tsrc_rx) = unbounded_channel::<TorrentSource>();
@@ -61,14 +86,43 @@ impl Engine {
         // Receives the torrent handle from engine thread and sents it back to ui_thread
         let (thdl_sd, thdl_rx) = unbounded_channel::<Arc<TorrentHandle>>();
 
-        let engine_thread_handle = std::thread::spawn(move || {
-            let tokio_rt = Self::generate_tokio_runtime();
+        let tokio_rt = Self::generate_tokio_runtime();
+        let runtime = tokio_rt.handle().clone();
 
+        let engine_thread_handle = std::thread::spawn(move || {
             tokio_rt.block_on(async move {
+                // Starts listening for the peers before any torrent is run, so that every torrent
+                // announces the port to the trackers
+                // TODO : Let the user know when no port could be bound
+                if let Ok(listener) = PeerListener::bind(engine_state.clone()).await {
+                    tokio::spawn(async move { listener.run().await });
+                }
+
+                // A single UDP socket for the UDP trackers of all the torrents
+                // TODO : Let the user know when no port could be bound
+                if engine_state.udp_trackers.bind().await.is_ok() {
+                    let engine_state = engine_state.clone();
+                    tokio::spawn(async move { engine_state.udp_trackers.run().await });
+                }
+
+                // A single DHT node for all the torrents
+                // TODO : Let the user know when no port could be bound
+                if engine_state.dht.bind().await.is_ok() {
+                    let engine_state = engine_state.clone();
+                    tokio::spawn(async move { engine_state.dht.run().await });
+                }
+
+                // A single Local Service Discovery for all the torrents
+                // TODO : Let the user know when no multicast group could be joined
+                if engine_state.lsd.bind().await.is_ok() {
+                    let engine_state = engine_state.clone();
+                    tokio::spawn(async move { engine_state.lsd.run(&engine_state).await });
+                }
+
                 while let Some(src) = tsrc_rx.recv().await {
                     // TODO : Check if there was any error in creating the torrent handle in this
                     // engine_thread and then only run the torrent on the engine thread and send its pointer to the ui_thread
-                    let handle = TorrentHandle::new(src).await;
+                    let handle = TorrentHandle::new(src, engine_state.clone()).await;
                     let tokio_handle = handle.clone();
                     tokio::task::spawn(async move { tokio_handle.run().await });
 
@@ -80,12 +134,152 @@ impl Engine {
 
         Arc::new(Self {
             torrents,
+            state,
             engine_thread_handle,
+            runtime,
             trnt_thread_sender: tsrc_sd,
             trnt_handle_receiver: Arc::new(Mutex::new(thdl_rx)),
         })
     }
 
+    /// No of peers unchoked at a time across all the torrents
+    pub fn upload_slots(&self) -> usize {
+        self.state.upload_slots()
+    }
+
+    /// Sets the no of peers unchoked at a time across all the torrents
+    pub fn set_upload_slots(&self, upload_slots: usize) {
+        self.state.set_upload_slots(upload_slots);
+    }
+
+    /// TCP port on which the peers can connect to us, it's 0 if no port could be bound
+    pub fn listen_port(&self) -> u16 {
+        self.state.listen_port()
+    }
+
+    /// No of peers all the torrents together can be connected with at a time
+    pub fn max_connections(&self) -> usize {
+        self.state.max_connections()
+    }
+
+    /// Sets the no of peers all the torrents together can be connected with at a time
+    pub fn set_max_connections(&self, max_connections: usize) {
+        self.state.set_max_connections(max_connections);
+    }
+
+    /// The nodes the DHT bootstraps from, as "host:port"
+    pub fn dht_bootstrap_nodes(&self) -> Vec<String> {
+        self.state.dht.bootstrap_nodes()
+    }
+
+    /// Sets the nodes the DHT bootstraps from, as "host:port", they're used the next time the
+    /// DHT runs short of nodes
+    pub fn set_dht_bootstrap_nodes(&self, nodes: Vec<String>) {
+        self.state.dht.set_bootstrap_nodes(nodes);
+    }
+
+    /// Whether the torrents are announced on the local network and the peers found there are
+    /// connected with
+    pub fn lsd_enabled(&self) -> bool {
+        self.state.lsd.enabled()
+    }
+
+    /// Turns the Local Service Discovery on or off for all the torrents, it's never used for the
+    /// private torrents
+    pub fn set_lsd_enabled(&self, enabled: bool) {
+        self.state.lsd.set_enabled(enabled);
+    }
+
+    /// Pauses the torrent at the given index, see [TorrentHandle::pause]
+    ///
+    /// NOTE : It blocks until the trackers have been let known, so it must not be called from
+    /// within an async context
+    pub fn pause(&self, index: usize) {
+        if let Some(handle) = self.torrents.blocking_lock().get(index).cloned() {
+            self.runtime.block_on(handle.pause());
+        }
+    }
+
+    /// Resumes the paused torrent at the given index, see [TorrentHandle::resume]
+    ///
+    /// NOTE : It must not be called from within an async context
+    pub fn resume(&self, index: usize) {
+        if let Some(handle) = self.torrents.blocking_lock().get(index).cloned() {
+            self.runtime.block_on(handle.resume());
+        }
+    }
+
+    /// Stops the torrent at the given index for good and removes it from the engine
+    ///
+    /// NOTE : It blocks until the trackers have been let known, so it must not be called from
+    /// within an async context
+    pub fn remove(&self, index: usize) {
+        let handle = {
+            let mut torrents = self.torrents.blocking_lock();
+            if index >= torrents.len() {
+                return;
+            }
+            torrents.remove(index)
+        };
+        self.runtime.block_on(handle.remove());
+    }
+
+    /// Adds a tracker to the given tier of the torrent at the given index, see
+    /// [TorrentHandle::add_tracker], nothing is done if there's no torrent at the index
+    ///
+    /// NOTE : It must not be called from within an async context
+    pub fn add_tracker(&self, index: usize, announce_url: &String, tier_index: usize) -> Result<(), TrackerEditError> {
+        match self.torrents.blocking_lock().get(index).cloned() {
+            Some(handle) => self.runtime.block_on(handle.add_tracker(announce_url, tier_index)),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes a tracker of the torrent at the given index, see [TorrentHandle::remove_tracker],
+    /// nothing is done if there's no torrent at the index
+    ///
+    /// NOTE : It must not be called from within an async context
+    pub fn remove_tracker(&self, index: usize, announce_url: &Url) -> Result<(), TrackerEditError> {
+        match self.torrents.blocking_lock().get(index).cloned() {
+            Some(handle) => self.runtime.block_on(handle.remove_tracker(announce_url)),
+            None => Ok(()),
+        }
+    }
+
+    /// Announces to a tracker of the torrent at the given index right away, see
+    /// [TorrentHandle::reannounce]
+    ///
+    /// NOTE : It must not be called from within an async context
+    pub fn reannounce(&self, index: usize, announce_url: &Url) {
+        if let Some(handle) = self.torrents.blocking_lock().get(index).cloned() {
+            self.runtime.block_on(handle.reannounce(announce_url));
+        }
+    }
+
+    /// Scrapes a tracker of the torrent at the given index right away, see
+    /// [TorrentHandle::scrape], it doesn't wait for the scrape to finish as the numbers received
+    /// end up in the tracker anyway
+    pub fn scrape(&self, index: usize, announce_url: &Url) {
+        if let Some(handle) = self.torrents.blocking_lock().get(index).cloned() {
+            let announce_url = announce_url.clone();
+            self.runtime.spawn(async move { handle.scrape(&announce_url).await });
+        }
+    }
+
+    /// Stops all the torrents, it's to be called before the engine is dropped so that the
+    /// trackers are let known that we're gone, the routing table of the DHT is saved as well
+    ///
+    /// NOTE : It blocks until the trackers have been let known, so it must not be called from
+    /// within an async context
+    pub fn shutdown(&self) {
+        let handles: Vec<Arc<TorrentHandle>> = self.torrents.blocking_lock().clone();
+        let state = self.state.clone();
+        self.runtime.block_on(async move {
+            futures::future::join_all(handles.iter().map(|handle| handle.pause())).await;
+            let _ = state.dht.save().await;
+        });
+    }
+
     /// Creates a tokio runtime on thread its called
     fn generate_tokio_runtime() -> Runtime {
         Builder::new_multi_thread().enable_all().build().unwrap()
@@ -119,26 +313,75 @@ pub struct TorrentHandle {
 
 impl TorrentHandle {
     /// Consumes the torrent source, may it be a Path or a MagnetURI,
-    pub async fn new(src: TorrentSource) -> Arc<TorrentHandle> {
+    pub async fn new(src: TorrentSource, engine_state: Arc<EngineState>) -> Arc<TorrentHandle> {
         return match src {
             TorrentSource::FilePath(ref path) => {
-                let torrent = TorrentFile::new(path).await.unwrap();
+                let torrent = TorrentFile::new(path, engine_state).await.unwrap();
                 Arc::new(Self {
                     inner: Torrent::FileTorrent(Arc::new(torrent)),
                 })
             }
+            TorrentSource::MagnetURI(ref uri) => {
+                let torrent = MagnetURI::new(uri, engine_state).await.unwrap();
+                Arc::new(Self {
+                    inner: Torrent::MagnetUriTorrent(Arc::new(torrent)),
+                })
+            }
         };
     }
 
     pub async fn run(&self) {
         match self.inner {
-            //Torrent::MagnetUriTorrent(ref m_torrent) => {}
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => {
+                magnet_trnt.run().await;
+            }
             Torrent::FileTorrent(ref file_trnt) => {
                 file_trnt.run().await;
             }
         }
     }
 
+    /// Stops downloading and seeding the torrent, until it's resumed
+    pub async fn pause(&self) {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.stop().await,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.stop().await,
+        }
+    }
+
+    /// Resumes the paused torrent
+    pub async fn resume(&self) {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.start().await,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.start().await,
+        }
+    }
+
+    /// Stops the torrent for good
+    pub async fn remove(&self) {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.remove().await,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.remove().await,
+        }
+    }
+
+    /// Whether the torrent has been paused or not
+    pub fn is_paused(&self) -> bool {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.d_state() == DownState::Stopped,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.d_state() == DownState::Stopped,
+        }
+    }
+
+    /// Whether the "info" field of the torrent, added through a magnet URI, is yet to be received
+    /// from the peers, nothing gets downloaded until then
+    pub fn is_fetching_metadata(&self) -> bool {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => !file_trnt.state.has_metadata(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => !magnet_trnt.torrent().state.has_metadata(),
+        }
+    }
+
     /// Gets the name of the torrent blockingly
     pub fn pause_resume(&self) -> String {
         return match self.inner {
@@ -159,6 +402,14 @@ impl TorrentHandle {
                     String::from("Name Not Found!")
                 }
             }
+            // The display name of the magnet URI is there until the "info" field is received
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => {
+                if let Some(ref name) = magnet_trnt.torrent().state.meta_info.info.name.clone() {
+                    name.clone()
+                } else {
+                    String::from("Name Not Found!")
+                }
+            }
         };
     }
 
@@ -166,26 +417,23 @@ impl TorrentHandle {
     pub fn bytes_complete(&self) -> usize {
         return match self.inner {
             Torrent::FileTorrent(ref file_trnt) => file_trnt.state.bytes_complete(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.bytes_complete(),
         };
     }
 
     /// Gives total size of entire torrent in "bytes"
     pub fn bytes_total(&self) -> usize {
         return match self.inner {
-            Torrent::FileTorrent(ref file_trnt) => {
-                if let Some(size) = file_trnt.state.meta_info.info.length {
-                    size as usize
-                } else {
-                    0 as usize
-                }
-            }
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.meta_info.getTotalLength() as usize,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.meta_info.getTotalLength() as usize,
         };
     }
 
     /// Gives total no of pieces
     pub fn pieces_total(&self) -> usize {
         return match self.inner {
-            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.meta_info.info.pieces.len(),
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.pieces_hash.len(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.pieces_hash.len(),
         };
     }
 
@@ -193,6 +441,7 @@ impl TorrentHandle {
     pub fn pieces_downloaded(&self) -> usize {
         return match self.inner {
             Torrent::FileTorrent(ref file_trnt) => file_trnt.state.pieces_downloaded(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.pieces_downloaded(),
         };
     }
 
@@ -206,9 +455,83 @@ impl TorrentHandle {
                     0
                 }
             }
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => {
+                if let Some(size) = magnet_trnt.torrent().state.meta_info.info.piece_length {
+                    size as usize
+                } else {
+                    0
+                }
+            }
         };
     }
 
+    /// No of block requests kept outstanding with each peer at a time
+    pub fn max_outstanding_requests(&self) -> usize {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.max_outstanding_requests(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.max_outstanding_requests(),
+        }
+    }
+
+    /// Sets the no of block requests kept outstanding with each peer at a time
+    pub fn set_max_outstanding_requests(&self, count: usize) {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.set_max_outstanding_requests(count),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.set_max_outstanding_requests(count),
+        }
+    }
+
+    /// No of peers of this torrent unchoked at a time
+    pub fn upload_slots(&self) -> usize {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.upload_slots(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.upload_slots(),
+        }
+    }
+
+    /// Sets the no of peers of this torrent unchoked at a time, the engine wide limit still applies
+    pub fn set_upload_slots(&self, upload_slots: usize) {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.set_upload_slots(upload_slots),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.set_upload_slots(upload_slots),
+        }
+    }
+
+    /// No of peers this torrent can be connected with at a time
+    pub fn max_connections(&self) -> usize {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.max_connections(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.max_connections(),
+        }
+    }
+
+    /// Sets the no of peers this torrent can be connected with at a time, the engine wide limit
+    /// still applies
+    pub fn set_max_connections(&self, max_connections: usize) {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.set_max_connections(max_connections),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.set_max_connections(max_connections),
+        }
+    }
+
+    /// Whether every tracker of the torrent is announced to at once, rather than one tracker at a
+    /// time, tier by tier
+    pub fn announce_to_all(&self) -> bool {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.announce_to_all(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.announce_to_all(),
+        }
+    }
+
+    /// Sets whether every tracker of the torrent is announced to at once, it takes effect the
+    /// next time the torrent is started
+    pub fn set_announce_to_all(&self, announce_to_all: bool) {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.set_announce_to_all(announce_to_all),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.set_announce_to_all(announce_to_all),
+        }
+    }
+
     /// Gives the total download speed in "bytes/second"
     /// NOTE: Currenlty  it holds some dummy data
     pub fn download_speed(&self) -> usize {
@@ -217,21 +540,99 @@ impl TorrentHandle {
     }
 
     /// Gives the total upload speed in "bytes/second"
-    /// NOTE: Currenlty it holds some dummy data
     pub fn upload_speed(&self) -> usize {
-        // TODO : Add implementation for upload speed
-        100000
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.upload_speed(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.upload_speed(),
+        }
+    }
+
+    /// Gives the total "bytes" uploaded
+    pub fn bytes_uploaded(&self) -> usize {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.bytes_uploaded(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.bytes_uploaded(),
+        }
+    }
+
+    /// Lets the download know about the files that are to be downloaded, it must be called after
+    /// changing "should_download" of any file in the file tree
+    pub async fn update_wanted_pieces(&self) {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.update_wanted_pieces().await,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.update_wanted_pieces().await,
+        }
     }
 
     pub fn getFileTree(&self) -> Arc<Mutex<crate::core::File>> {
         return match self.inner {
             Torrent::FileTorrent(ref file_trnt) => file_trnt.state.file_tree.as_ref().unwrap().clone(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.file_tree.as_ref().unwrap().clone(),
         };
     }
 
     pub fn getTrackers(&self) -> Arc<RwLock<Vec<Vec<Arc<Tracker>>>>> {
         match self.inner {
             Torrent::FileTorrent(ref file_trnt) => file_trnt.state.trackers.clone(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.trackers.clone(),
+        }
+    }
+
+    /// The address of each tracker of the torrent along with its state, which holds the message
+    /// of the tracker when it refused the announce or warned about something
+    pub async fn tracker_states(&self) -> Vec<(Url, TrackerState)> {
+        let state = match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.clone(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.clone(),
+        };
+        let trackers = state.trackers.read().await;
+        trackers
+            .iter()
+            .flatten()
+            .map(|tracker| (tracker.address.clone(), tracker.tracker_state()))
+            .collect()
+    }
+
+    /// Adds a tracker with the given URL at the end of the given tier, or in a new tier after the
+    /// last one if the tier doesn't exist, the trackers are saved to the resume data of the torrent
+    pub async fn add_tracker(&self, announce_url: &String, tier_index: usize) -> Result<(), TrackerEditError> {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.add_tracker(announce_url, tier_index).await,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().add_tracker(announce_url, tier_index).await,
+        }
+    }
+
+    /// Removes the tracker with the given URL, the trackers are saved to the resume data of the
+    /// torrent
+    pub async fn remove_tracker(&self, announce_url: &Url) -> Result<(), TrackerEditError> {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.remove_tracker(announce_url).await,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().remove_tracker(announce_url).await,
+        }
+    }
+
+    /// Announces to the tracker with the given URL right away, rather than waiting for the
+    /// interval it asked for
+    pub async fn reannounce(&self, announce_url: &Url) -> Result<(), TrackerEditError> {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.reannounce(announce_url).await,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().reannounce(announce_url).await,
+        }
+    }
+
+    /// Scrapes the tracker with the given URL right away, gives back whether the numbers were
+    /// received, which is always "false" for a HTTP or HTTPS tracker as they aren't scraped
+    pub async fn scrape(&self, announce_url: &Url) -> Result<bool, TrackerEditError> {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.scrape(announce_url).await,
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().scrape(announce_url).await,
+        }
+    }
+
+    pub fn getPeers(&self) -> Arc<Mutex<Vec<Arc<Peer>>>> {
+        match self.inner {
+            Torrent::FileTorrent(ref file_trnt) => file_trnt.state.peers.clone(),
+            Torrent::MagnetUriTorrent(ref magnet_trnt) => magnet_trnt.torrent().state.peers.clone(),
         }
     }
 }
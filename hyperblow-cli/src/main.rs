@@ -9,6 +9,7 @@ mod utils;
 use arguments::Arguments;
 use clap::Parser;
 use engine::{Engine, TorrentSource};
+use hyperblow::parser::magnet_uri_parser::MagnetURIMeta;
 use std::sync::Arc;
 
 //use std::{env, error::Error, sync::Arc, thread, time::Instant};
@@ -20,8 +21,13 @@ fn main() -> Result<()> {
     let args = Arguments::parse();
     args.check();
 
+    // A magnet URI without a BitTorrent info hash can't be downloaded, so it's let known right away
+    if let Some(ref uri) = args.magnet_uri {
+        MagnetURIMeta::fromMagnetURI(uri)?;
+    }
+
     // Creates engine
-    let engine = Engine::new();
+    let engine = Engine::new(args.directory.clone());
     spawn_in_engine(engine.clone(), &args);
 
     tui::ui::draw_ui(engine.clone())?;
@@ -31,8 +37,11 @@ fn main() -> Result<()> {
 
 #[tokio::main(flavor = "current_thread")]
 async fn spawn_in_engine(engine: Arc<Engine>, args: &Arguments) -> Result<()> {
-    engine
-        .spawn(TorrentSource::FilePath(args.torrent_file.clone().unwrap()))
-        .await;
+    let src = match (args.torrent_file.clone(), args.magnet_uri.clone()) {
+        (Some(path), _) => TorrentSource::FilePath(path),
+        (None, Some(uri)) => TorrentSource::MagnetURI(uri),
+        (None, None) => return Ok(()),
+    };
+    engine.spawn(src).await;
     Ok(())
 }
@@ -1,14 +1,33 @@
-use crate::tui::tui_state::TUIState;
+use crate::{core::peer::PeerState, tui::tui_state::TUIState};
 use ratatui::{
     backend::Backend,
     layout::{Constraint, Layout, Rect},
+    style::{Color, Style},
     terminal::Frame,
-    widgets::{Block, BorderType, Borders},
+    widgets::{Block, BorderType, Borders, Cell, Row, Table},
 };
 
 use std::rc::Rc;
 
-/// Data for the Bandwidth Tab Section of TUI
+const SN: &str = "SN";
+const SN_PERC: u16 = 5;
+
+const ADDRESS: &str = "Address";
+const ADDRESS_PERC: u16 = 25;
+
+const SOURCE: &str = "Source";
+const SOURCE_PERC: u16 = 10;
+
+const TYPE: &str = "Type";
+const TYPE_PERC: u16 = 10;
+
+const PIECES: &str = "Pieces";
+const PIECES_PERC: u16 = 10;
+
+const STATUS: &str = "Status";
+const STATUS_PERC: u16 = 40;
+
+/// Data for the Peers Tab Section of TUI
 pub struct PeersTab;
 
 impl PeersTab {
@@ -23,8 +42,81 @@ impl PeersTab {
             .cloned()
             .collect();
 
-        //Self::draw_header_row(frame, area[0]);
-        //Self::draw_tracker_rows(frame, area[1], state.clone());
+        Self::draw_header_row(frame, area[0]);
+        Self::draw_peer_rows(frame, area[1], state.clone());
+    }
+
+    // Draws header row and leaves one row spacing below
+    fn draw_header_row<B: Backend>(frame: &mut Frame<B>, area: Rect) {
+        let table = Table::new([Row::new(vec![SN, ADDRESS, SOURCE, TYPE, PIECES, STATUS]), Row::new([""; 6])]).widths(&[
+            Constraint::Percentage(SN_PERC),
+            Constraint::Percentage(ADDRESS_PERC),
+            Constraint::Percentage(SOURCE_PERC),
+            Constraint::Percentage(TYPE_PERC),
+            Constraint::Percentage(PIECES_PERC),
+            Constraint::Percentage(STATUS_PERC),
+        ]);
+        frame.render_widget(table, area.to_owned());
+    }
+
+    // Draws all peers informations that could be fit in the given area
+    fn draw_peer_rows<B: Backend>(frame: &mut Frame<B>, area: Rect, state: Rc<TUIState>) {
+        let mut row_s = Vec::default();
+
+        // Load the "torrent handle" from the currently selected torrent's index (which is the
+        // currently selected torrent session)
+        let current_torrent_index = state.torrent_index();
+        let current_torrent_handle = { &(*state.engine.torrents.blocking_lock())[current_torrent_index] };
+
+        // Go through all the peers
+        let peers = current_torrent_handle.getPeers();
+        let peers = peers.blocking_lock();
+
+        for (index, peer) in peers.iter().enumerate() {
+            let info = peer.info.blocking_lock();
+
+            let sn_widget = Cell::from((index + 1).to_string());
+            let address_widget = Cell::from(peer.socket_adr.to_string());
+            let source_widget = Cell::from(peer.source.to_string());
+            let type_widget = Cell::from(info.peer_type.to_string());
+            let pieces_widget = Cell::from(format!("{}/{}", info.pieces_count(), info.pieces_have.len()));
+
+            let (status, status_color) = match info.peer_state {
+                PeerState::Disconnected => {
+                    let reason = info.disconnect_reason.as_ref().map(|reason| reason.to_string()).unwrap_or_default();
+                    (format!("{} ({})", info.peer_state, reason), Color::Red)
+                }
+                PeerState::ConnectionTimeoutIdle | PeerState::ConnectionErrorIdle => (info.peer_state.to_string(), Color::Red),
+                PeerState::RequestingPiece | PeerState::Downloading => (info.peer_state.to_string(), Color::Green),
+                _ => {
+                    // Shows who is choking whom, once the connection is made
+                    let choking = if info.peer_choking { "Choked" } else { "Unchoked" };
+                    let interested = if info.am_interested { "Interested" } else { "Not Interested" };
+                    (format!("{} ({choking}, {interested})", info.peer_state), Color::Yellow)
+                }
+            };
+            let status_widget = Cell::from(status).style(Style::default().fg(status_color));
+
+            row_s.push(Row::new([
+                sn_widget,
+                address_widget,
+                source_widget,
+                type_widget,
+                pieces_widget,
+                status_widget,
+            ]));
+        }
+
+        let table = Table::new(row_s).widths(&[
+            Constraint::Percentage(SN_PERC),
+            Constraint::Percentage(ADDRESS_PERC),
+            Constraint::Percentage(SOURCE_PERC),
+            Constraint::Percentage(TYPE_PERC),
+            Constraint::Percentage(PIECES_PERC),
+            Constraint::Percentage(STATUS_PERC),
+        ]);
+
+        frame.render_widget(table, area.to_owned());
     }
 
     // Given an area, it draws border around that area and then it simply returns a new area with a
@@ -2,7 +2,7 @@ use crate::{core::tracker::TrackerState, tui::tui_state::TUIState};
 use ratatui::{
     backend::Backend,
     layout::{Constraint, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     terminal::Frame,
     widgets::{Block, BorderType, Borders, Cell, Row, Table},
 };
@@ -11,11 +11,23 @@ use std::rc::Rc;
 const SN: &str = "SN";
 const SN_PERC: u16 = 5;
 
+const TIER: &str = "Tier";
+const TIER_PERC: u16 = 5;
+
 const URL: &str = "URL";
-const URL_PERC: u16 = 35;
+const URL_PERC: u16 = 30;
 
 const STATUS: &str = "Status";
-const STATUS_PERC: u16 = 60;
+const STATUS_PERC: u16 = 36;
+
+const SEEDERS: &str = "Seeders";
+const SEEDERS_PERC: u16 = 8;
+
+const COMPLETED: &str = "Completed";
+const COMPLETED_PERC: u16 = 8;
+
+const LEECHERS: &str = "Leechers";
+const LEECHERS_PERC: u16 = 8;
 
 pub struct TrackersTab {}
 
@@ -23,11 +35,18 @@ impl TrackersTab {
     /// Draws all the trackers informations on the given area from the given TUIState in the given
     /// area
     pub fn draw<B: Backend>(frame: &mut Frame<B>, area: Rect, state: Rc<TUIState>) {
-        // Create and render the border first
+        // Create and render the border first, titled with the URL of the tracker being added, or
+        // why the last edit failed, or else the keys that act on the trackers
+        let title = match (state.tracker_input(), state.tracker_error()) {
+            (Some(input), _) => format!(" Add tracker : {input}_ (Enter : Add | Esc : Cancel) "),
+            (None, Some(error)) => format!(" {error} "),
+            (None, None) => " a : Add | d : Remove | r : Reannounce | s : Scrape ".to_string(),
+        };
         let widget_border = Block::default()
             .border_type(BorderType::Thick)
             .borders(Borders::ALL)
-            .border_type(BorderType::Rounded);
+            .border_type(BorderType::Rounded)
+            .title(title);
 
         frame.render_widget(widget_border, area.clone());
 
@@ -50,10 +69,18 @@ impl TrackersTab {
 
     // Draws header row and leaves one row spacing below
     fn draw_header_row<B: Backend>(frame: &mut Frame<B>, area: Rect) {
-        let table = Table::new([Row::new(vec![SN, URL, STATUS]), Row::new([""; 3])]).widths(&[
+        let table = Table::new([
+            Row::new(vec![SN, TIER, URL, STATUS, SEEDERS, COMPLETED, LEECHERS]),
+            Row::new([""; 7]),
+        ])
+        .widths(&[
             Constraint::Percentage(SN_PERC),
+            Constraint::Percentage(TIER_PERC),
             Constraint::Percentage(URL_PERC),
             Constraint::Percentage(STATUS_PERC),
+            Constraint::Percentage(SEEDERS_PERC),
+            Constraint::Percentage(COMPLETED_PERC),
+            Constraint::Percentage(LEECHERS_PERC),
         ]);
         frame.render_widget(table, area.to_owned());
     }
@@ -73,11 +100,12 @@ impl TrackersTab {
         let ref trackers = *trackers.blocking_read();
 
         let mut sn = 1_u16;
-        for tracker_s in trackers {
+        for (tier_index, tracker_s) in trackers.iter().enumerate() {
             for tracker in tracker_s {
                 let sn_widget = Cell::from(sn.to_string());
+                let tier_widget = Cell::from((tier_index + 1).to_string());
                 let url_widget = Cell::from(tracker.address.to_string());
-                let tracker_state = tracker.tracker_state.load();
+                let tracker_state = tracker.tracker_state();
                 let tracker_state_color = match tracker_state {
                     TrackerState::Idle => Color::Red,
                     TrackerState::DNSResolved => Color::Green,
@@ -85,10 +113,40 @@ impl TrackersTab {
                     TrackerState::DNSUnresolved {
                         retry_time: _,
                     } => Color::Red,
+                    TrackerState::AnnounceFailed {
+                        retry_time: _,
+                    } => Color::Red,
+                    TrackerState::Error {
+                        ..
+                    } => Color::Red,
+                    TrackerState::Warning {
+                        ..
+                    } => Color::Yellow,
                     _ => Color::Green,
                 };
                 let tracker_state_widget = Cell::from(tracker_state.to_string()).style(Style::default().fg(tracker_state_color));
-                let row = Row::new([sn_widget, url_widget, tracker_state_widget]);
+
+                // The numbers are unknown until the tracker has been scraped
+                let (seeders, completed, leechers) = match tracker.scrape_stats.load() {
+                    Some(stats) => (stats.seeders.to_string(), stats.completed.to_string(), stats.leechers.to_string()),
+                    None => ("-".to_string(), "-".to_string(), "-".to_string()),
+                };
+                let row = Row::new([
+                    sn_widget,
+                    tier_widget,
+                    url_widget,
+                    tracker_state_widget,
+                    Cell::from(seeders),
+                    Cell::from(completed),
+                    Cell::from(leechers),
+                ]);
+
+                // The selected tracker is the one the keys act on
+                let row = if usize::from(sn) == state.tracker_index() + 1 {
+                    row.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    row
+                };
                 row_s.push(row);
                 sn = sn + 1;
             }
@@ -96,8 +154,12 @@ impl TrackersTab {
 
         let table = Table::new(row_s).widths(&[
             Constraint::Percentage(SN_PERC),
+            Constraint::Percentage(TIER_PERC),
             Constraint::Percentage(URL_PERC),
             Constraint::Percentage(STATUS_PERC),
+            Constraint::Percentage(SEEDERS_PERC),
+            Constraint::Percentage(COMPLETED_PERC),
+            Constraint::Percentage(LEECHERS_PERC),
         ]);
 
         frame.render_widget(table, area.to_owned());
@@ -54,7 +54,7 @@ impl TorrentsSection {
     /// It includes info such as
     /// - Name of the torrent
     /// - Progress of the torrent file in percentage,
-    /// - Status : Downloading, Seeding, Paused
+    /// - Status : Downloading, Seeding, Paused, Fetching Metadata
     /// - Bytes : "12 GiB / 20 GiB" Total Bytes Downloaded
     /// - In - "12 GiB/s" Total Download Speed
     /// - Out - "1 GiB/s" Total Upload Speed
@@ -170,11 +170,20 @@ impl TorrentsSection {
             // Widget to display status to show either the torrent session is Paused, Downloading
             // or Seeding
             let widget_status = {
-                let status = TorrentStatus::Downloading;
+                let status = if handle.is_paused() {
+                    TorrentStatus::Paused
+                } else if handle.is_fetching_metadata() {
+                    TorrentStatus::FetchingMetadata
+                } else if handle.bytes_complete() >= handle.bytes_total() {
+                    TorrentStatus::Seeding
+                } else {
+                    TorrentStatus::Downloading
+                };
                 let (title, fg_color) = match status {
                     TorrentStatus::Downloading => (status.to_string(), Color::Green),
                     TorrentStatus::Seeding => (status.to_string(), Color::Red),
                     TorrentStatus::Paused => (status.to_string(), Color::Blue),
+                    TorrentStatus::FetchingMetadata => (status.to_string(), Color::Yellow),
                 };
                 Block::default()
                     .title(title)
@@ -229,6 +238,7 @@ enum TorrentStatus {
     Downloading,
     Seeding,
     Paused,
+    FetchingMetadata,
 }
 
 impl Display for TorrentStatus {
@@ -237,6 +247,7 @@ impl Display for TorrentStatus {
             TorrentStatus::Downloading => write!(f, "Downloading"),
             TorrentStatus::Seeding => write!(f, "Seeding"),
             TorrentStatus::Paused => write!(f, "Paused"),
+            TorrentStatus::FetchingMetadata => write!(f, "Fetching Metadata"),
         }?;
         Ok(())
     }
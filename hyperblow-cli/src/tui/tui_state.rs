@@ -12,6 +12,7 @@ use ratatui::{
     widgets,
     widgets::{Block, BorderType, Borders, Gauge},
 };
+use reqwest::Url;
 use std::{
     cell::{Cell, RefCell},
     fmt::format,
@@ -229,6 +230,16 @@ pub struct TUIState {
     torrent_index: Cell<usize>,
 
     max_torrent_index: Cell<usize>,
+
+    /// Index of the selected tracker in the Trackers Tab, counting the trackers of every tier
+    /// one after another
+    tracker_index: Cell<usize>,
+
+    /// URL of the tracker being typed in to be added, "None" unless a tracker is being added
+    tracker_input: RefCell<Option<String>>,
+
+    /// Why the last edit of the trackers failed, shown in the Trackers Tab until the next edit
+    tracker_error: RefCell<Option<String>>,
 }
 
 impl TUIState {
@@ -259,6 +270,9 @@ impl TUIState {
             tab,
             torrent_index,
             max_torrent_index,
+            tracker_index: Cell::new(0),
+            tracker_input: RefCell::default(),
+            tracker_error: RefCell::default(),
         }
     }
 
@@ -279,6 +293,7 @@ impl TUIState {
 
     pub fn set_torrent_index(&self, index: usize) {
         self.torrent_index.set(index);
+        self.tracker_index.set(0);
     }
 
     // Gets you the maximum tab index that can be achieved
@@ -310,7 +325,131 @@ impl TUIState {
 
     /// Toggles either pause or resume of the torrent, which means that when this method is called
     /// with an index of torrent, it shall be paused or resumed
-    pub fn toggle_torrent(&self, index: usize) {}
+    pub fn toggle_torrent(&self, index: usize) {
+        let is_paused = match self.engine.torrents.blocking_lock().get(index) {
+            Some(handle) => handle.is_paused(),
+            None => return,
+        };
+        if is_paused {
+            self.engine.resume(index);
+        } else {
+            self.engine.pause(index);
+        }
+    }
+
+    /// Index of the selected tracker in the Trackers Tab
+    pub fn tracker_index(&self) -> usize {
+        self.tracker_index.get()
+    }
+
+    /// Selects the tracker above the selected one in the Trackers Tab
+    pub fn select_previous_tracker(&self) {
+        self.tracker_index.set(self.tracker_index().saturating_sub(1));
+    }
+
+    /// Selects the tracker below the selected one in the Trackers Tab, unless it's the last one
+    pub fn select_next_tracker(&self) {
+        let trackers_count = self.trackers().len();
+        if self.tracker_index() + 1 < trackers_count {
+            self.tracker_index.set(self.tracker_index() + 1);
+        }
+    }
+
+    /// The tier and the URL of every tracker of the selected torrent, in the order they're shown
+    /// in the Trackers Tab
+    fn trackers(&self) -> Vec<(usize, Url)> {
+        let handle = match self.engine.torrents.blocking_lock().get(self.torrent_index()) {
+            Some(handle) => handle.clone(),
+            None => return Vec::new(),
+        };
+        let trackers = handle.getTrackers();
+        let trackers = trackers.blocking_read();
+        trackers
+            .iter()
+            .enumerate()
+            .flat_map(|(tier_index, tier)| tier.iter().map(move |tracker| (tier_index, tracker.address.clone())))
+            .collect()
+    }
+
+    /// The tier and the URL of the selected tracker in the Trackers Tab
+    fn selected_tracker(&self) -> Option<(usize, Url)> {
+        self.trackers().get(self.tracker_index()).cloned()
+    }
+
+    /// URL of the tracker being typed in to be added, "None" unless a tracker is being added
+    pub fn tracker_input(&self) -> Option<String> {
+        self.tracker_input.borrow().clone()
+    }
+
+    /// Why the last edit of the trackers failed, if it did
+    pub fn tracker_error(&self) -> Option<String> {
+        self.tracker_error.borrow().clone()
+    }
+
+    /// Starts taking the URL of a tracker to be added, the keys typed after this go into the URL
+    pub fn start_adding_tracker(&self) {
+        *self.tracker_input.borrow_mut() = Some(String::new());
+    }
+
+    /// Stops taking the URL of the tracker to be added, without adding it
+    pub fn cancel_adding_tracker(&self) {
+        *self.tracker_input.borrow_mut() = None;
+    }
+
+    /// Adds the given character to the URL of the tracker being typed in
+    pub fn push_tracker_input(&self, c: char) {
+        if let Some(ref mut input) = *self.tracker_input.borrow_mut() {
+            input.push(c);
+        }
+    }
+
+    /// Removes the last character of the URL of the tracker being typed in
+    pub fn pop_tracker_input(&self) {
+        if let Some(ref mut input) = *self.tracker_input.borrow_mut() {
+            input.pop();
+        }
+    }
+
+    /// Adds the tracker whose URL was typed in to the tier of the selected tracker, or to the
+    /// first tier if there's no tracker yet
+    pub fn finish_adding_tracker(&self) {
+        let Some(announce_url) = self.tracker_input.borrow_mut().take() else {
+            return;
+        };
+        let tier_index = self.selected_tracker().map(|(tier_index, _)| tier_index).unwrap_or(0);
+        let result = self.engine.add_tracker(self.torrent_index(), &announce_url, tier_index);
+        *self.tracker_error.borrow_mut() = result.err().map(|e| e.to_string());
+    }
+
+    /// Removes the selected tracker from the selected torrent
+    pub fn remove_selected_tracker(&self) {
+        let Some((_, announce_url)) = self.selected_tracker() else {
+            return;
+        };
+        let result = self.engine.remove_tracker(self.torrent_index(), &announce_url);
+        *self.tracker_error.borrow_mut() = result.err().map(|e| e.to_string());
+
+        // Keep the selection within the trackers that are left
+        let trackers_count = self.trackers().len();
+        if self.tracker_index() >= trackers_count {
+            self.tracker_index.set(trackers_count.saturating_sub(1));
+        }
+    }
+
+    /// Announces to the selected tracker right away
+    pub fn reannounce_selected_tracker(&self) {
+        if let Some((_, announce_url)) = self.selected_tracker() {
+            self.engine.reannounce(self.torrent_index(), &announce_url);
+        }
+    }
+
+    /// Scrapes the selected tracker right away
+    pub fn scrape_selected_tracker(&self) {
+        if let Some((_, announce_url)) = self.selected_tracker() {
+            self.engine.scrape(self.torrent_index(), &announce_url);
+        }
+    }
+
     // Gets the data to be displayed on the TorrentsSection
     // It has following structure of HashMap represented in JSON Structure:
     // {
@@ -330,6 +469,10 @@ impl TUIState {
             *self.tab.borrow_mut() = Tab::Files;
         } else if self.tab_index() == 3 {
             *self.tab.borrow_mut() = Tab::Trackers;
+        } else if self.tab_index() == 4 {
+            *self.tab.borrow_mut() = Tab::Peers;
+        } else if self.tab_index() == 5 {
+            *self.tab.borrow_mut() = Tab::Pieces;
         } else {
             *self.tab.borrow_mut() = Tab::None;
         }
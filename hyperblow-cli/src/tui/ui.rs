@@ -61,12 +61,36 @@ fn draw<B: Backend>(terminal: &mut Terminal<B>, engine: Arc<Engine>) -> crosster
         })?;
 
         if event::poll(Duration::from_millis(200))? {
+            let trackers_tab = matches!(*state.tab.borrow(), Tab::Trackers);
             match event::read()? {
+                // The keys go into the URL of the tracker being added, until it's added or cancelled
+                event::Event::Key(key) if state.tracker_input().is_some() => match key.code {
+                    event::KeyCode::Char(c) => state.push_tracker_input(c),
+                    event::KeyCode::Backspace => state.pop_tracker_input(),
+                    event::KeyCode::Enter => state.finish_adding_tracker(),
+                    event::KeyCode::Esc => state.cancel_adding_tracker(),
+                    _ => {}
+                },
+
                 event::Event::Key(key) => match key.code {
-                    event::KeyCode::Char('q') => return Ok(()),
+                    event::KeyCode::Char('q') => {
+                        state.engine.shutdown();
+                        return Ok(());
+                    }
+                    event::KeyCode::Char('p') => {
+                        state.toggle_torrent(state.torrent_index());
+                    }
                     event::KeyCode::Tab => {
                         state.increment_tab_index();
                     }
+
+                    // The keys that act on the trackers, while the Trackers Tab is shown
+                    event::KeyCode::Up if trackers_tab => state.select_previous_tracker(),
+                    event::KeyCode::Down if trackers_tab => state.select_next_tracker(),
+                    event::KeyCode::Char('a') if trackers_tab => state.start_adding_tracker(),
+                    event::KeyCode::Char('d') if trackers_tab => state.remove_selected_tracker(),
+                    event::KeyCode::Char('r') if trackers_tab => state.reannounce_selected_tracker(),
+                    event::KeyCode::Char('s') if trackers_tab => state.scrape_selected_tracker(),
                     _ => {}
                 },
 
@@ -1,17 +1,26 @@
 #![allow(non_snake_case, dead_code)]
 
 use magnet_url::Magnet;
+use percent_encoding::percent_decode_str;
 use std::{error, fmt};
 
+/// Alphabet of the base32 encoding of RFC4648, which some magnet URIs use for the info hash
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
 #[derive(Debug)]
 pub enum MagnetURIMetaError {
     InvalidURI,
+
+    /// The "xt" field isn't a BitTorrent info hash, i.e "urn:btih:" followed by the 20 bytes of
+    /// the hash in hex or base32
+    InvalidInfoHash,
 }
 
 impl fmt::Display for MagnetURIMetaError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             MagnetURIMetaError::InvalidURI => write!(f, "Invalid magnet URI"),
+            MagnetURIMetaError::InvalidInfoHash => write!(f, "Invalid info hash in magnet URI"),
         }
     }
 }
@@ -24,18 +33,22 @@ impl error::Error for MagnetURIMetaError {}
 ///
 /// Some of the fields from the crate "magnet_url" itself
 ///
-struct MagnetURIMeta {
+#[derive(Debug, Clone)]
+pub struct MagnetURIMeta {
+    /// Info hash of the torrent decoded from "xt", which is either in hex or in base32
+    pub info_hash: Vec<u8>,
+
     /// **(Required)** Exact Topic : Info Hash of the torrent and the type of hash being used is
     /// also kept here
     pub xt: Option<String>,
 
-    /// **(Optional)** Display name : The filename to display to the user
+    /// **(Optional)** Display name : The filename to display to the user, percent decoded
     pub dn: Option<String>,
 
     /// **(Optional)** Exact Length : The size of the file in bytes
     pub xl: Option<u64>,
 
-    /// **(Optional)** Address Tracker : The url of the tracker
+    /// **(Optional)** Address Tracker : The url of each tracker, percent decoded
     pub tr: Option<Vec<String>>,
 
     /// **(Optional)** Web Seed : They payload data served over HTTP(S)
@@ -61,14 +74,16 @@ struct MagnetURIMeta {
 }
 
 impl MagnetURIMeta {
-    /// Tries to create [MagnetURIMeta] from given magnet URI
-    fn fromMagnetURI(uri: &String) -> Result<MagnetURIMeta, MagnetURIMetaError> {
+    /// Tries to create [MagnetURIMeta] from given magnet URI, which must have the info hash of a
+    /// BitTorrent torrent in its "xt" field
+    pub fn fromMagnetURI(uri: &String) -> Result<MagnetURIMeta, MagnetURIMetaError> {
         return match Magnet::new(uri) {
             Ok(d) => Ok(MagnetURIMeta {
+                info_hash: Self::infoHash(uri).ok_or(MagnetURIMetaError::InvalidInfoHash)?,
                 xt: d.xt,
-                dn: d.dn,
+                dn: d.dn.as_deref().map(Self::percentDecode),
                 xl: d.xl,
-                tr: Some(d.tr),
+                tr: Some(d.tr.iter().map(|tr| Self::percentDecode(tr)).collect()),
                 ws: d.ws,
                 xs: d.xs,
                 kt: d.kt,
@@ -80,10 +95,117 @@ impl MagnetURIMeta {
     }
 
     /// Checks if the Magnet URI is valid or not
-    fn checkIfMagnetURIIsValid(uri: &String) -> bool {
+    pub fn checkIfMagnetURIIsValid(uri: &String) -> bool {
         return match Magnet::new(uri) {
             Ok(_) => true,
             Err(_) => false,
         };
     }
+
+    /// Finds the "xt" field with the BitTorrent info hash, i.e "xt=urn:btih:<info hash>" and
+    /// decodes it
+    ///
+    /// NOTE : It's not taken from "magnet_url", as it cuts a base32 info hash short at the first
+    /// character that isn't hex
+    fn infoHash(uri: &str) -> Option<Vec<u8>> {
+        let (_, query) = uri.split_once('?')?;
+        query
+            .split('&')
+            .find_map(|field| field.strip_prefix("xt=urn:btih:"))
+            .and_then(Self::decodeInfoHash)
+    }
+
+    /// Decodes the 20 bytes of the info hash from its 40 characters in hex, or its 32 characters
+    /// in base32
+    fn decodeInfoHash(xt: &str) -> Option<Vec<u8>> {
+        match xt.len() {
+            40 => (0..40).step_by(2).map(|i| u8::from_str_radix(xt.get(i..i + 2)?, 16).ok()).collect(),
+            32 => {
+                // Every character holds 5 bits, which are put together 8 bits at a time
+                let mut info_hash = Vec::with_capacity(20);
+                let (mut bits, mut bits_count) = (0_u64, 0);
+                for c in xt.bytes() {
+                    let value = BASE32_ALPHABET.iter().position(|&a| a == c.to_ascii_uppercase())?;
+                    bits = (bits << 5) | value as u64;
+                    bits_count += 5;
+                    if bits_count >= 8 {
+                        bits_count -= 8;
+                        info_hash.push((bits >> bits_count) as u8);
+                    }
+                }
+                Some(info_hash)
+            }
+            _ => None,
+        }
+    }
+
+    /// Decodes the percent encoded value of a field, e.g "udp%3A%2F%2Ftracker" to "udp://tracker"
+    fn percentDecode(value: &str) -> String {
+        percent_decode_str(value).decode_utf8_lossy().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Info hash of the torrent all the magnet URIs are of, in hex and in base32
+    const INFO_HASH_HEX: &str = "dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c";
+    const INFO_HASH_BASE32: &str = "3WBFL3G4PSSV7MF37AJSHWDQMLNR63I4";
+
+    fn infoHash() -> Vec<u8> {
+        (0..40)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&INFO_HASH_HEX[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn info_hash_in_hex() {
+        let meta = MagnetURIMeta::fromMagnetURI(&format!("magnet:?xt=urn:btih:{INFO_HASH_HEX}")).unwrap();
+        assert_eq!(meta.info_hash, infoHash());
+
+        let uri = format!("magnet:?xt=urn:btih:{}", INFO_HASH_HEX.to_uppercase());
+        assert_eq!(MagnetURIMeta::fromMagnetURI(&uri).unwrap().info_hash, infoHash());
+    }
+
+    #[test]
+    fn info_hash_in_base32() {
+        let meta = MagnetURIMeta::fromMagnetURI(&format!("magnet:?xt=urn:btih:{INFO_HASH_BASE32}")).unwrap();
+        assert_eq!(meta.info_hash, infoHash());
+
+        let uri = format!("magnet:?xt=urn:btih:{}", INFO_HASH_BASE32.to_lowercase());
+        assert_eq!(MagnetURIMeta::fromMagnetURI(&uri).unwrap().info_hash, infoHash());
+    }
+
+    #[test]
+    fn trackers_and_name_are_percent_decoded() {
+        let uri = format!(
+            "magnet:?xt=urn:btih:{INFO_HASH_HEX}&dn=Big%20Buck%20Bunny&tr=udp%3A%2F%2Fexplodie.org%3A6969\
+             &tr=wss%3A%2F%2Ftracker.btorrent.xyz"
+        );
+        let meta = MagnetURIMeta::fromMagnetURI(&uri).unwrap();
+        assert_eq!(meta.dn.as_deref(), Some("Big Buck Bunny"));
+        let trackers = vec!["udp://explodie.org:6969".to_string(), "wss://tracker.btorrent.xyz".to_string()];
+        assert_eq!(meta.tr, Some(trackers));
+    }
+
+    #[test]
+    fn info_hash_of_wrong_length_is_rejected() {
+        for xt in [
+            &INFO_HASH_HEX[1..],
+            &INFO_HASH_BASE32[1..],
+            "",
+            "zz8255ecdc7ca55fb0bbf81323d87062db1f6d1c",
+        ] {
+            let uri = format!("magnet:?xt=urn:btih:{xt}&dn=a");
+            assert!(matches!(
+                MagnetURIMeta::fromMagnetURI(&uri),
+                Err(MagnetURIMetaError::InvalidInfoHash)
+            ));
+        }
+        assert!(MagnetURIMeta::fromMagnetURI(&format!("magnet:?xt=urn:btih:{INFO_HASH_HEX}0")).is_err());
+        assert!(MagnetURIMeta::fromMagnetURI(&format!("magnet:?xt=urn:btih:{INFO_HASH_BASE32}A")).is_err());
+        assert!(MagnetURIMeta::fromMagnetURI(&"magnet:?dn=a".to_string()).is_err());
+    }
 }
@@ -1,5 +1,8 @@
 #![allow(non_snake_case, dead_code)]
 
+use super::magnet_uri_parser::MagnetURIMeta;
+use serde::{Deserialize as _, Deserializer};
+use serde_bencode::value::Value;
 use serde_derive::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
 use std::{error, fmt, fs, io};
@@ -15,6 +18,9 @@ pub enum FileMetaError {
         encoding: String,
         error: serde_bencode::Error,
     },
+
+    #[error("MissingInfo")]
+    MissingInfo,
 }
 
 /// DataStructure that maps all the data inside of bencode encoded ".torrent" file
@@ -49,6 +55,37 @@ pub struct FileMeta {
     /// **(Optional)** As "as" is a reserved keyword in rust, acceptable_source as in whole word is
     /// written, which Refers to a direct download from a web server. It's URL encoded
     pub acceptable_source: Option<String>,
+
+    /// **(Optional)** DHT nodes to bootstrap from for a trackerless torrent, each one being a host
+    /// and a port (BEP5)
+    #[serde(default, deserialize_with = "deserializeNodes")]
+    pub nodes: Option<Vec<(String, u16)>>,
+
+    /// The exact bytes of the bencoded "info" field, as they're in the ".torrent" file
+    ///
+    /// NOTE : The info hash is the SHA1 hash of these bytes, [Info] doesn't hold every key the
+    /// "info" field can have, e.g "source" or the keys of BEP47 and BEP52, so serializing it back
+    /// doesn't give the same bytes
+    #[serde(skip)]
+    pub raw_info: Vec<u8>,
+}
+
+/// Deserializes the "nodes" field, a list of lists each holding a host and a port, the nodes that
+/// aren't of that form are left out
+///
+/// NOTE : serde_bencode doesn't consume the end of a list deserialized into a tuple, so each node
+/// is deserialized as a list of values instead
+fn deserializeNodes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<(String, u16)>>, D::Error> {
+    let nodes: Option<Vec<Vec<Value>>> = Option::deserialize(deserializer)?;
+    Ok(nodes.map(|nodes| {
+        nodes
+            .iter()
+            .filter_map(|node| match node.as_slice() {
+                [Value::Bytes(host), Value::Int(port)] => Some((String::from_utf8(host.clone()).ok()?, u16::try_from(*port).ok()?)),
+                _ => None,
+            })
+            .collect()
+    }))
 }
 
 /// The fields within the Info DataStructure are used to build "info hash", so it must the required
@@ -63,6 +100,8 @@ pub struct Info {
     /// Consists of byte string of concatenation of all 20-byte SHA1 hash values, one per piece
     #[serde(with = "serde_bytes")]
     pub pieces: Vec<u8>,
+    /// 1 if the peers must only be got from the trackers of the torrent (BEP27)
+    pub private: Option<i64>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -130,7 +169,10 @@ impl FileMeta {
     ///
     pub fn fromRawTorrentFile(file: Vec<u8>) -> Result<FileMeta, FileMetaError> {
         match serde_bencode::de::from_bytes::<FileMeta>(&file) {
-            Ok(d) => Ok(d),
+            Ok(mut d) => {
+                d.raw_info = Self::rawInfo(&file).ok_or(FileMetaError::MissingInfo)?.to_vec();
+                Ok(d)
+            }
             Err(err) => Err(FileMetaError::InvalidEncoding {
                 encoding: "Bencode".to_string(),
                 error: err,
@@ -138,6 +180,107 @@ impl FileMeta {
         }
     }
 
+    /// Creates a [FileMeta] of a torrent added through a magnet URI, whose "info" field hasn't been
+    /// received yet, so [Info] only has the display name and the exact length of the magnet URI
+    /// and there's no [FileMeta::raw_info]
+    ///
+    /// The trackers of the magnet URI are all put in a single tier
+    pub fn fromMagnetURI(magnet: &MagnetURIMeta) -> FileMeta {
+        let trackers = magnet.tr.clone().unwrap_or_default();
+        FileMeta {
+            announce: trackers.first().cloned().unwrap_or_default(),
+            announce_list: (!trackers.is_empty()).then(|| vec![trackers]),
+            info: Info {
+                name: magnet.dn.clone(),
+                length: magnet.xl.map(|xl| xl as i64),
+                files: None,
+                piece_length: None,
+                pieces: Vec::new(),
+                private: None,
+            },
+            creation_data: None,
+            comment: None,
+            encoding: None,
+            created_by: None,
+            acceptable_source: magnet.acceptable_source.clone(),
+            nodes: None,
+            raw_info: Vec::new(),
+        }
+    }
+
+    /// Creates a [FileMeta] from the bencoded "info" field received from the peers, for a torrent
+    /// added through a magnet URI, along with the trackers the torrent has been using
+    pub fn fromRawInfo(raw_info: Vec<u8>, announce_list: Vec<Vec<String>>) -> Result<FileMeta, FileMetaError> {
+        let info = match serde_bencode::de::from_bytes::<Info>(&raw_info) {
+            Ok(info) => info,
+            Err(err) => {
+                return Err(FileMetaError::InvalidEncoding {
+                    encoding: "Bencode".to_string(),
+                    error: err,
+                })
+            }
+        };
+        Ok(FileMeta {
+            announce: announce_list.iter().flatten().next().cloned().unwrap_or_default(),
+            announce_list: (!announce_list.is_empty()).then_some(announce_list),
+            info,
+            creation_data: None,
+            comment: None,
+            encoding: None,
+            created_by: None,
+            acceptable_source: None,
+            nodes: None,
+            raw_info,
+        })
+    }
+
+    /// Whether the "info" field is there, it isn't for a torrent added through a magnet URI until
+    /// it's received from the peers
+    pub fn hasInfo(&self) -> bool {
+        !self.raw_info.is_empty()
+    }
+
+    /// Finds the bytes of the value of the "info" key, within the bencoded dictionary of the
+    /// ".torrent" file
+    fn rawInfo(v: &[u8]) -> Option<&[u8]> {
+        if *v.first()? != b'd' {
+            return None;
+        }
+        let mut i = 1;
+        while *v.get(i)? != b'e' {
+            let key_end = Self::skipValue(v, i)?;
+            let value_end = Self::skipValue(v, key_end)?;
+            if &v[i..key_end] == b"4:info" {
+                return Some(&v[key_end..value_end]);
+            }
+            i = value_end;
+        }
+        None
+    }
+
+    /// Gives the index right after the bencoded value that starts at the given index, i.e an
+    /// integer, a byte string, a list or a dictionary, "None" if the value is malformed or cut short
+    pub fn skipValue(v: &[u8], start: usize) -> Option<usize> {
+        match *v.get(start)? {
+            b'i' => Some(start + v[start..].iter().position(|&b| b == b'e')? + 1),
+            b'l' | b'd' => {
+                // The keys of a dictionary are byte strings, so they're skipped just like values
+                let mut i = start + 1;
+                while *v.get(i)? != b'e' {
+                    i = Self::skipValue(v, i)?;
+                }
+                Some(i + 1)
+            }
+            b'0'..=b'9' => {
+                let colon = start + v[start..].iter().position(|&b| b == b':')?;
+                let length: usize = std::str::from_utf8(&v[start..colon]).ok()?.parse().ok()?;
+                let end = colon.checked_add(1)?.checked_add(length)?;
+                (end <= v.len()).then_some(end)
+            }
+            _ => None,
+        }
+    }
+
     /// InfoHash is the SHA1 hash of all the fields within the "info" field of bencode encoded
     /// torrent file
     /// Generates and gives you the info hash of the
@@ -162,14 +305,27 @@ impl FileMeta {
     /// ```
     /// Gets you the Info Hash
     pub fn generateInfoHash(&self) -> Vec<u8> {
-        // Serialize the info section of FileMeta and get all bytes in info field of a torrent file
-        // i.e Converts the data of info field to "bytes", to generate Info Hash
-        let info_byte = serde_bencode::ser::to_bytes(&self.info).unwrap();
+        // Hash the exact bytes of the info field, see [FileMeta::raw_info]
         let mut hasher = Sha1::new();
-        hasher.update(info_byte);
+        hasher.update(&self.raw_info);
         hasher.finalize().into_iter().collect()
     }
 
+    /// Whether the torrent is private, in which case the peers must only be got from its trackers
+    /// i.e no DHT, PEX or LSD (BEP27)
+    pub fn isPrivate(&self) -> bool {
+        self.info.private == Some(1)
+    }
+
+    /// Gets the total size of the torrent in bytes, which is the "length" field in single file
+    /// mode and the sum of "length" of all the files in multiple file mode
+    pub fn getTotalLength(&self) -> i64 {
+        match self.info.files {
+            Some(ref files) => files.iter().map(|file| file.length).sum(),
+            None => self.info.length.unwrap_or(0),
+        }
+    }
+
     // Gets all the hash of the pieces stored in the bencode encoded ".torrent" file's
     // "pieces" field
     pub fn getPiecesHash(&self) -> Vec<[u8; 20]> {
@@ -184,3 +340,128 @@ impl FileMeta {
         pieces_hash
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wraps the given bencoded "info" field into a ".torrent" file, with a few keys around it
+    fn torrentFile(info: &[u8]) -> Vec<u8> {
+        let mut file = b"d8:announce39:udp://tracker.example.org:6969/announce".to_vec();
+        file.extend_from_slice(b"10:created by13:mktorrent 1.113:creation datei1700000000e4:info");
+        file.extend_from_slice(info);
+        file.extend_from_slice(b"8:url-listl27:https://mirror.example.org/ee");
+        file
+    }
+
+    fn hex(v: &[u8]) -> String {
+        v.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    // A private torrent with a "source", as made by "mktorrent -p -s RED", both of which are
+    // left out when the Info is serialized back
+    #[test]
+    fn info_hash_of_private_torrent_with_source() {
+        let mut info = b"d6:lengthi1048576e4:name12:album.tar.gz12:piece lengthi262144e6:pieces80:".to_vec();
+        info.extend([b'A'; 20].iter().chain(&[b'B'; 20]).chain(&[b'C'; 20]).chain(&[b'D'; 20]));
+        info.extend_from_slice(b"7:privatei1e6:source3:REDe");
+
+        let meta = FileMeta::fromRawTorrentFile(torrentFile(&info)).unwrap();
+        assert_eq!(meta.raw_info, info);
+        assert_eq!(hex(&meta.generateInfoHash()), "d03a3f4d8e53382adfa014aac0d435f1f07ce525");
+        assert!(meta.isPrivate());
+        assert_eq!(meta.getPiecesHash().len(), 4);
+    }
+
+    // A hybrid v1 and v2 torrent with an "md5sum", a BEP47 padding file and the BEP52 "file tree"
+    // and "meta version", whose keys aren't even in the sorted order
+    #[test]
+    fn info_hash_of_hybrid_torrent_with_padding_files() {
+        let mut info = b"d5:filesl".to_vec();
+        info.extend_from_slice(b"d6:lengthi700e6:md5sum32:0123456789abcdef0123456789abcdef4:pathl10:readme.txtee");
+        info.extend_from_slice(b"d4:attr1:p6:lengthi15684e4:pathl4:.pad5:15684ee");
+        info.extend_from_slice(b"d6:lengthi16384e4:pathl6:images9:cover.pngeee");
+        info.extend_from_slice(b"9:file treed6:imagesd9:cover.pngd0:d6:lengthi16384e11:pieces root32:");
+        info.extend_from_slice(&[b'R'; 32]);
+        info.extend_from_slice(b"eee10:readme.txtd0:d6:lengthi700eeee");
+        info.extend_from_slice(b"12:meta versioni2e4:name6:bundle12:piece lengthi16384e6:pieces40:");
+        info.extend([b'E'; 20].iter().chain(&[b'F'; 20]));
+        info.push(b'e');
+
+        let meta = FileMeta::fromRawTorrentFile(torrentFile(&info)).unwrap();
+        assert_eq!(meta.raw_info, info);
+        assert_eq!(hex(&meta.generateInfoHash()), "94bf1d8e1db5a1a3399ff00d0589f9cb831ddf8d");
+        assert!(!meta.isPrivate());
+        assert_eq!(meta.getTotalLength(), 32768);
+    }
+
+    // A trackerless torrent, whose peers come from the DHT nodes it lists
+    #[test]
+    fn nodes_of_trackerless_torrent() {
+        let mut file = b"d8:announce0:4:info".to_vec();
+        file.extend_from_slice(b"d6:lengthi1e4:name1:a12:piece lengthi1e6:pieces20:aaaaaaaaaaaaaaaaaaaae");
+        file.extend_from_slice(b"5:nodesll9:127.0.0.1i6881eel17:router.example.ini25401eeee");
+
+        let meta = FileMeta::fromRawTorrentFile(file).unwrap();
+        let nodes = vec![("127.0.0.1".to_string(), 6881), ("router.example.in".to_string(), 25401)];
+        assert_eq!(meta.nodes, Some(nodes));
+    }
+
+    #[test]
+    fn truncated_torrent_is_rejected() {
+        let file = torrentFile(b"d6:lengthi1e4:name1:a12:piece lengthi1e6:pieces20:aaaaaaaaaaaaaaaaaaaae");
+        assert!(FileMeta::fromRawTorrentFile(file[..file.len() - 10].to_vec()).is_err());
+        assert_eq!(FileMeta::rawInfo(&file[..60]), None);
+    }
+
+    /// Path of a ".torrent" file kept in "tests/fixtures"
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    // A single file torrent laid out the way mktorrent writes it, with an "azureus_properties"
+    // dictionary before the "info" field and the "url-list" of BEP19 after it
+    #[test]
+    fn info_hash_of_single_file_torrent_fixture() {
+        let meta = FileMeta::fromTorrentFile(&fixture("single_file.torrent")).unwrap();
+        assert_eq!(hex(&meta.generateInfoHash()), "3706eb764eaba08531a2bd58b7757a01b9d0974a");
+        assert_eq!(meta.info.name.as_deref(), Some("hyperblow-1.0-amd64.iso"));
+        assert_eq!(meta.getTotalLength(), 300000);
+        assert_eq!(meta.getPiecesHash().len(), 5);
+        assert_eq!(meta.announce_list.as_ref().map(|tiers| tiers.len()), Some(2));
+    }
+
+    // A hybrid v1 and v2 torrent, whose v1 files are padded to the piece boundary and whose
+    // "piece layers" come after the "info" field
+    #[test]
+    fn info_hash_of_hybrid_torrent_fixture() {
+        let meta = FileMeta::fromTorrentFile(&fixture("hybrid.torrent")).unwrap();
+        assert_eq!(hex(&meta.generateInfoHash()), "9867b31e951a971753d7ab5dcbd9c0aea09a9011");
+        assert_eq!(meta.info.files.as_ref().map(|files| files.len()), Some(3));
+        assert_eq!(meta.getTotalLength(), 37768);
+        assert_eq!(meta.getPiecesHash().len(), 3);
+    }
+
+    #[test]
+    fn info_is_found_when_it_is_not_the_last_key() {
+        let info = b"d6:lengthi1e4:name1:a12:piece lengthi1e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+        let mut file = b"d8:announce0:4:info".to_vec();
+        file.extend_from_slice(info);
+        file.extend_from_slice(b"5:nodesll9:127.0.0.1i6881eee8:url-listl0:ee");
+        assert_eq!(FileMeta::rawInfo(&file), Some(info.as_ref()));
+    }
+
+    // The "info" key inside of a nested dictionary that comes first isn't the "info" field
+    #[test]
+    fn info_is_found_after_a_nested_dictionary() {
+        let info = b"d6:lengthi1e4:name1:a12:piece lengthi1e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+        let mut file = b"d8:announce0:18:azureus_propertiesd4:infod4:infoi1ee4:listli1eee".to_vec();
+        file.extend_from_slice(b"4:info");
+        file.extend_from_slice(info);
+        file.push(b'e');
+        assert_eq!(FileMeta::rawInfo(&file), Some(info.as_ref()));
+
+        let meta = FileMeta::fromRawTorrentFile(file).unwrap();
+        assert_eq!(meta.raw_info, info);
+    }
+}